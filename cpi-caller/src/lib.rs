@@ -0,0 +1,119 @@
+//! Test program that trades through the escrow program on behalf of its own PDA.
+//!
+//! The PDA at `[AUTHORITY_SEED]` acts as alice: it pays rent, deposits the x leg and
+//! withdraws the y leg, signing every escrow instruction through `invoke_signed`.
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use solana_escrow::cpi::{self, CpiContext};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub const AUTHORITY_SEED: &[u8] = b"authority";
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum CallerInstruction {
+    /// Accounts: escrow program, then the `InitEscrow` accounts with the authority as alice,
+    /// optionally followed by a rent payer
+    InitEscrow {
+        amount_a: u64,
+        amount_b: u64,
+    },
+    /// Accounts: escrow program, then the `Deposit` accounts with the authority as depositor
+    Deposit {
+        amount: u64,
+    },
+    /// Accounts: escrow program, then the `Withdraw` accounts with the authority as withdrawer
+    Withdraw {
+        amount: u64,
+    },
+    /// Accounts: escrow program, escrow. Fails unless the escrow is in `expected_state`.
+    CheckStatus {
+        expected_state: u8,
+    },
+}
+
+pub fn find_authority_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUTHORITY_SEED], program_id)
+}
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = CallerInstruction::try_from_slice(instruction_data)?;
+    let account_info_iter = &mut accounts.iter();
+    let escrow_program = next_account_info(account_info_iter)?.clone();
+
+    let (_, bump) = find_authority_address(program_id);
+    let signer_seeds: &[&[&[u8]]] = &[&[AUTHORITY_SEED, &[bump]]];
+    let mut next = || next_account_info(account_info_iter).cloned();
+
+    match instruction {
+        CallerInstruction::InitEscrow { amount_a, amount_b } => {
+            msg!("Caller: InitEscrow");
+            let accounts = cpi::InitEscrow {
+                alice: next()?,
+                bob: next()?,
+                x_mint: next()?,
+                y_mint: next()?,
+                x_vault: next()?,
+                y_vault: next()?,
+                escrow: next()?,
+                token_program: next()?,
+                system_program: next()?,
+                rent: next()?,
+                config: next()?,
+                x_mint_rule: next()?,
+                y_mint_rule: next()?,
+                rent_payer: next().ok(),
+            };
+            cpi::init_escrow(CpiContext::new_with_signer(escrow_program, accounts, signer_seeds), amount_a, amount_b)
+        }
+        CallerInstruction::Deposit { amount } => {
+            msg!("Caller: Deposit");
+            let accounts = cpi::Deposit {
+                depositor: next()?,
+                vault: next()?,
+                escrow: next()?,
+                token_program: next()?,
+                source: next()?,
+                associated_token_program: next()?,
+                config: next()?,
+            };
+            cpi::deposit(CpiContext::new_with_signer(escrow_program.clone(), accounts, signer_seeds), amount)?;
+            msg!("Escrow state: {}", cpi::last_status(&escrow_program)?.state);
+            Ok(())
+        }
+        CallerInstruction::Withdraw { amount } => {
+            msg!("Caller: Withdraw");
+            let accounts = cpi::Withdraw {
+                withdrawer: next()?,
+                vault: next()?,
+                escrow: next()?,
+                token_program: next()?,
+                destination: next()?,
+                associated_token_program: next()?,
+                config: next()?,
+            };
+            cpi::withdraw(CpiContext::new_with_signer(escrow_program, accounts, signer_seeds), amount)
+        }
+        CallerInstruction::CheckStatus { expected_state } => {
+            msg!("Caller: CheckStatus");
+            let accounts = cpi::GetEscrowStatus { escrow: next()? };
+            let status = cpi::get_escrow_status(CpiContext::new(escrow_program, accounts))?;
+            if status.state != expected_state {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use borsh::BorshSerialize;
+use escrow_cpi_caller::{find_authority_address, process_instruction, CallerInstruction};
+use solana_escrow::{
+    instruction,
+    processor::Processor,
+    state::{find_config_address, find_escrow_address, find_mint_rule_address, find_vault_address},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+    transport::TransportError,
+};
+
+async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransportError> {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+async fn create_mint(context: &mut ProgramTestContext) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), &context.payer.pubkey(), None, 0)
+            .unwrap(),
+    ];
+    send(context, &instructions, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+async fn create_token_account(context: &mut ProgramTestContext, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let account = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &account.pubkey(),
+            rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(&spl_token::id(), &account.pubkey(), mint, owner).unwrap(),
+        spl_token::instruction::mint_to(&spl_token::id(), mint, &account.pubkey(), &context.payer.pubkey(), &[], amount)
+            .unwrap(),
+    ];
+    send(context, &instructions, &[&account]).await.unwrap();
+    account.pubkey()
+}
+
+async fn token_balance(context: &mut ProgramTestContext, account: &Pubkey) -> u64 {
+    let account = context.banks_client.get_account(*account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data).unwrap().amount
+}
+
+/// Wraps an escrow instruction into a caller instruction, marking the authority PDA as a
+/// non-signer since the caller signs for it.
+fn caller_instruction(caller_id: &Pubkey, escrow_id: &Pubkey, escrow_ix: Instruction, data: CallerInstruction) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(*escrow_id, false)];
+    accounts.extend(escrow_ix.accounts.into_iter().map(|mut meta| {
+        meta.is_signer = false;
+        meta
+    }));
+    Instruction {
+        program_id: *caller_id,
+        accounts,
+        data: data.try_to_vec().unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn test_pda_party_trades_through_cpi() {
+    let escrow_id = Pubkey::new_unique();
+    let caller_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("solana_escrow", escrow_id, processor!(Processor::process));
+    program_test.add_program("escrow_cpi_caller", caller_id, processor!(process_instruction));
+    let mut context = program_test.start_with_context().await;
+
+    let (alice, _) = find_authority_address(&caller_id);
+    let bob = Keypair::new();
+    let transfer = system_instruction::transfer(&context.payer.pubkey(), &alice, 1_000_000_000);
+    send(&mut context, &[transfer], &[]).await.unwrap();
+
+    let x_mint = create_mint(&mut context).await;
+    let y_mint = create_mint(&mut context).await;
+    let alice_x = create_token_account(&mut context, &x_mint, &alice, 100).await;
+    let alice_y = create_token_account(&mut context, &y_mint, &alice, 0).await;
+    let bob_x = create_token_account(&mut context, &x_mint, &bob.pubkey(), 0).await;
+    let bob_y = create_token_account(&mut context, &y_mint, &bob.pubkey(), 100).await;
+
+    let (x_vault, _) = find_vault_address(&escrow_id, &x_mint, &alice, &bob.pubkey());
+    let (y_vault, _) = find_vault_address(&escrow_id, &y_mint, &alice, &bob.pubkey());
+    let (escrow, _) = find_escrow_address(&escrow_id, &x_vault, &y_vault, &alice, &bob.pubkey());
+    let (config, _) = find_config_address(&escrow_id);
+    let (x_mint_rule, _) = find_mint_rule_address(&escrow_id, &x_mint);
+    let (y_mint_rule, _) = find_mint_rule_address(&escrow_id, &y_mint);
+
+    let init = instruction::init_escrow(
+        &escrow_id, &alice, &bob.pubkey(), &x_mint, &y_mint, &x_vault, &y_vault, &escrow, &config,
+        &x_mint_rule, &y_mint_rule, 10, 20,
+    )
+    .unwrap();
+    let init = caller_instruction(&caller_id, &escrow_id, init, CallerInstruction::InitEscrow { amount_a: 10, amount_b: 20 });
+    send(&mut context, &[init], &[]).await.unwrap();
+
+    let deposit = instruction::deposit(&escrow_id, &alice, &x_vault, &escrow, &alice_x, &config, 10).unwrap();
+    let deposit = caller_instruction(&caller_id, &escrow_id, deposit, CallerInstruction::Deposit { amount: 10 });
+    send(&mut context, &[deposit], &[]).await.unwrap();
+
+    let deposit = instruction::deposit(&escrow_id, &bob.pubkey(), &y_vault, &escrow, &bob_y, &config, 20).unwrap();
+    send(&mut context, &[deposit], &[&bob]).await.unwrap();
+
+    let status = instruction::get_escrow_status(&escrow_id, &escrow).unwrap();
+    let check = caller_instruction(&caller_id, &escrow_id, status, CallerInstruction::CheckStatus { expected_state: 3 });
+    send(&mut context, &[check], &[]).await.unwrap();
+
+    let withdraw = instruction::withdraw(&escrow_id, &alice, &y_vault, &escrow, &alice_y, &config, 20).unwrap();
+    let withdraw = caller_instruction(&caller_id, &escrow_id, withdraw, CallerInstruction::Withdraw { amount: 20 });
+    send(&mut context, &[withdraw], &[]).await.unwrap();
+
+    let withdraw = instruction::withdraw(&escrow_id, &bob.pubkey(), &x_vault, &escrow, &bob_x, &config, 10).unwrap();
+    send(&mut context, &[withdraw], &[&bob]).await.unwrap();
+
+    assert_eq!(token_balance(&mut context, &alice_x).await, 90);
+    assert_eq!(token_balance(&mut context, &alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &bob_y).await, 80);
+
+    let status = instruction::get_escrow_status(&escrow_id, &escrow).unwrap();
+    let check = caller_instruction(&caller_id, &escrow_id, status, CallerInstruction::CheckStatus { expected_state: 0 });
+    send(&mut context, &[check], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_withdraw_requires_party_signature() {
+    let escrow_id = Pubkey::new_unique();
+    let caller_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("solana_escrow", escrow_id, processor!(Processor::process));
+    program_test.add_program("escrow_cpi_caller", caller_id, processor!(process_instruction));
+    let mut context = program_test.start_with_context().await;
+
+    let (alice, _) = find_authority_address(&caller_id);
+    let bob = Keypair::new();
+    let transfer = system_instruction::transfer(&context.payer.pubkey(), &alice, 1_000_000_000);
+    send(&mut context, &[transfer], &[]).await.unwrap();
+
+    let x_mint = create_mint(&mut context).await;
+    let y_mint = create_mint(&mut context).await;
+    let alice_x = create_token_account(&mut context, &x_mint, &alice, 100).await;
+    let thief_x = create_token_account(&mut context, &x_mint, &bob.pubkey(), 0).await;
+
+    let (x_vault, _) = find_vault_address(&escrow_id, &x_mint, &alice, &bob.pubkey());
+    let (y_vault, _) = find_vault_address(&escrow_id, &y_mint, &alice, &bob.pubkey());
+    let (escrow, _) = find_escrow_address(&escrow_id, &x_vault, &y_vault, &alice, &bob.pubkey());
+    let (config, _) = find_config_address(&escrow_id);
+    let (x_mint_rule, _) = find_mint_rule_address(&escrow_id, &x_mint);
+    let (y_mint_rule, _) = find_mint_rule_address(&escrow_id, &y_mint);
+
+    let init = instruction::init_escrow(
+        &escrow_id, &alice, &bob.pubkey(), &x_mint, &y_mint, &x_vault, &y_vault, &escrow, &config,
+        &x_mint_rule, &y_mint_rule, 10, 20,
+    )
+    .unwrap();
+    let init = caller_instruction(&caller_id, &escrow_id, init, CallerInstruction::InitEscrow { amount_a: 10, amount_b: 20 });
+    send(&mut context, &[init], &[]).await.unwrap();
+
+    let deposit = instruction::deposit(&escrow_id, &alice, &x_vault, &escrow, &alice_x, &config, 10).unwrap();
+    let deposit = caller_instruction(&caller_id, &escrow_id, deposit, CallerInstruction::Deposit { amount: 10 });
+    send(&mut context, &[deposit], &[]).await.unwrap();
+
+    // refund of alice's deposit into someone else's account, without alice's signature
+    let mut withdraw = instruction::withdraw(&escrow_id, &alice, &x_vault, &escrow, &thief_x, &config, 10).unwrap();
+    withdraw.accounts[0].is_signer = false;
+    assert!(send(&mut context, &[withdraw], &[]).await.is_err());
+    assert_eq!(token_balance(&mut context, &thief_x).await, 0);
+}
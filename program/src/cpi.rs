@@ -0,0 +1,174 @@
+//! Helpers for calling the escrow program from other on-chain programs.
+//!
+//! Parties may be PDAs of the calling program: pass their seeds with
+//! `CpiContext::new_with_signer` and they sign through `invoke_signed`.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program::{get_return_data, invoke_signed},
+    program_error::ProgramError,
+};
+use crate::{instruction, state::EscrowStatus};
+use borsh::BorshDeserialize;
+
+pub struct CpiContext<'a, 'b, T> {
+    /// the escrow program
+    pub program: AccountInfo<'a>,
+    pub accounts: T,
+    pub signer_seeds: &'b [&'b [&'b [u8]]],
+}
+
+impl<'a, 'b, T> CpiContext<'a, 'b, T> {
+    pub fn new(program: AccountInfo<'a>, accounts: T) -> Self {
+        Self { program, accounts, signer_seeds: &[] }
+    }
+
+    pub fn new_with_signer(program: AccountInfo<'a>, accounts: T, signer_seeds: &'b [&'b [&'b [u8]]]) -> Self {
+        Self { program, accounts, signer_seeds }
+    }
+}
+
+pub struct InitEscrow<'a> {
+    pub alice: AccountInfo<'a>,
+    pub bob: AccountInfo<'a>,
+    pub x_mint: AccountInfo<'a>,
+    pub y_mint: AccountInfo<'a>,
+    pub x_vault: AccountInfo<'a>,
+    pub y_vault: AccountInfo<'a>,
+    pub escrow: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    pub system_program: AccountInfo<'a>,
+    pub rent: AccountInfo<'a>,
+    pub config: AccountInfo<'a>,
+    pub x_mint_rule: AccountInfo<'a>,
+    pub y_mint_rule: AccountInfo<'a>,
+    /// pays rent instead of alice when set
+    pub rent_payer: Option<AccountInfo<'a>>,
+}
+
+pub struct Deposit<'a> {
+    pub depositor: AccountInfo<'a>,
+    pub vault: AccountInfo<'a>,
+    pub escrow: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    pub source: AccountInfo<'a>,
+    pub associated_token_program: AccountInfo<'a>,
+    pub config: AccountInfo<'a>,
+}
+
+pub struct Withdraw<'a> {
+    pub withdrawer: AccountInfo<'a>,
+    pub vault: AccountInfo<'a>,
+    pub escrow: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    pub destination: AccountInfo<'a>,
+    pub associated_token_program: AccountInfo<'a>,
+    pub config: AccountInfo<'a>,
+}
+
+pub struct GetEscrowStatus<'a> {
+    pub escrow: AccountInfo<'a>,
+}
+
+pub fn init_escrow<'a>(ctx: CpiContext<'a, '_, InitEscrow<'a>>, amount_a: u64, amount_b: u64) -> ProgramResult {
+    let a = ctx.accounts;
+    let ix = match &a.rent_payer {
+        Some(rent_payer) => instruction::init_escrow_sponsored(
+            ctx.program.key,
+            rent_payer.key,
+            a.alice.key,
+            a.bob.key,
+            a.x_mint.key,
+            a.y_mint.key,
+            a.x_vault.key,
+            a.y_vault.key,
+            a.escrow.key,
+            a.config.key,
+            a.x_mint_rule.key,
+            a.y_mint_rule.key,
+            amount_a,
+            amount_b,
+        )?,
+        None => instruction::init_escrow(
+            ctx.program.key,
+            a.alice.key,
+            a.bob.key,
+            a.x_mint.key,
+            a.y_mint.key,
+            a.x_vault.key,
+            a.y_vault.key,
+            a.escrow.key,
+            a.config.key,
+            a.x_mint_rule.key,
+            a.y_mint_rule.key,
+            amount_a,
+            amount_b,
+        )?,
+    };
+    let mut account_infos = vec![
+        a.alice, a.bob, a.x_mint, a.y_mint, a.x_vault, a.y_vault, a.escrow, a.token_program,
+        a.system_program, a.rent, a.config, a.x_mint_rule, a.y_mint_rule, ctx.program,
+    ];
+    account_infos.extend(a.rent_payer);
+    invoke_signed(&ix, &account_infos, ctx.signer_seeds)
+}
+
+pub fn deposit<'a>(ctx: CpiContext<'a, '_, Deposit<'a>>, amount: u64) -> ProgramResult {
+    let a = ctx.accounts;
+    let ix = instruction::deposit(
+        ctx.program.key,
+        a.depositor.key,
+        a.vault.key,
+        a.escrow.key,
+        a.source.key,
+        a.config.key,
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            a.depositor, a.vault, a.escrow, a.token_program, a.source, a.associated_token_program,
+            a.config, ctx.program,
+        ],
+        ctx.signer_seeds,
+    )
+}
+
+pub fn withdraw<'a>(ctx: CpiContext<'a, '_, Withdraw<'a>>, amount: u64) -> ProgramResult {
+    let a = ctx.accounts;
+    let ix = instruction::withdraw(
+        ctx.program.key,
+        a.withdrawer.key,
+        a.vault.key,
+        a.escrow.key,
+        a.destination.key,
+        a.config.key,
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            a.withdrawer, a.vault, a.escrow, a.token_program, a.destination, a.associated_token_program,
+            a.config, ctx.program,
+        ],
+        ctx.signer_seeds,
+    )
+}
+
+/// Returns the status set by the last escrow instruction invoked through this module.
+pub fn last_status(program: &AccountInfo) -> Result<EscrowStatus, ProgramError> {
+    match get_return_data() {
+        Some((program_id, data)) if program_id == *program.key => {
+            Ok(EscrowStatus::try_from_slice(&data)?)
+        }
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+pub fn get_escrow_status<'a>(ctx: CpiContext<'a, '_, GetEscrowStatus<'a>>) -> Result<EscrowStatus, ProgramError> {
+    let a = ctx.accounts;
+    let ix = instruction::get_escrow_status(ctx.program.key, a.escrow.key)?;
+    invoke_signed(&ix, &[a.escrow, ctx.program.clone()], ctx.signer_seeds)?;
+    last_status(&ctx.program)
+}
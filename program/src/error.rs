@@ -21,6 +21,110 @@ pub enum EscrowError {
     EmptyEscrow,
     #[error("Already withdrawn")]
     AlreadyWithdrawn,
+    #[error("Escrow program is paused")]
+    Paused,
+    #[error("Invalid config account")]
+    InvalidConfig,
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("Escrow program is not paused")]
+    NotPaused,
+    #[error("Escrow is partially settled")]
+    PartiallySettled,
+    #[error("Mint is not allowed")]
+    MintNotAllowed,
+    #[error("Escrow size out of range for mint")]
+    EscrowSizeOutOfRange,
+    #[error("Invalid mint rule account")]
+    InvalidMintRule,
+    #[error("Account uses an old layout and needs to be migrated")]
+    AccountNeedsMigration,
+    #[error("Unsupported account version")]
+    UnsupportedAccountVersion,
+    #[error("Invalid account type")]
+    InvalidAccountType,
+    #[error("Escrow is already initialized")]
+    AlreadyInitialized,
+    #[error("Escrow size must be non-zero")]
+    ZeroEscrowSize,
+    #[error("Parties must be distinct")]
+    IdenticalParties,
+    #[error("Mints must be distinct")]
+    IdenticalMints,
+    #[error("Mint is not an spl-token mint")]
+    InvalidMint,
+    #[error("Mint is not initialized")]
+    UninitializedMint,
+    #[error("Escrow still holds deposits")]
+    EscrowNotEmpty,
+    #[error("Token account does not delegate the escrow size to the escrow")]
+    InvalidDelegation,
+    #[error("Token account no longer holds the delegated amount")]
+    InsufficientDelegatedFunds,
+    #[error("Order is not signed by its maker in the preceding ed25519 instruction")]
+    InvalidOrderSignature,
+    #[error("Order has expired")]
+    OrderExpired,
+    #[error("Order nonce was already used")]
+    OrderNonceUsed,
+    #[error("Order nonces account does not match the maker and nonce")]
+    InvalidOrderNonces,
+    #[error("Order was invalidated by its maker")]
+    OrderInvalidated,
+    #[error("Maker state account does not match the maker")]
+    InvalidMakerState,
+    #[error("Invalidation must raise the maker's minimum valid nonce")]
+    MinNonceNotIncreased,
+    #[error("Dutch auction terms are invalid or do not belong to the escrow")]
+    InvalidDutchAuction,
+    #[error("Current auction price is above the amount offered")]
+    PriceAboveLimit,
+    #[error("Dutch auction escrow is priced by bob's deposit")]
+    UnpricedDutchAuction,
+    #[error("Auction terms are invalid or the account does not belong to the auction")]
+    InvalidAuction,
+    #[error("Auction is closed for bids")]
+    AuctionEnded,
+    #[error("Auction has not ended yet")]
+    AuctionNotEnded,
+    #[error("Bid is below the reserve price or the minimum increment")]
+    BidTooLow,
+    #[error("Bids can only be revealed between the commit and reveal deadlines")]
+    RevealNotOpen,
+    #[error("Revealed bid does not match its commitment or exceeds the collateral")]
+    InvalidReveal,
+    #[error("Auction is already settled")]
+    AuctionSettled,
+    #[error("Vesting schedule is invalid or not supported by this instruction")]
+    InvalidVestingSchedule,
+    #[error("Withdrawal exceeds the vested amount not yet claimed")]
+    AmountNotVested,
+    #[error("Invalid milestone escrow")]
+    InvalidMilestoneEscrow,
+    #[error("Deadline has not passed yet")]
+    DeadlineNotReached,
+    #[error("Invalid stream")]
+    InvalidStream,
+    #[error("Invalid loan")]
+    InvalidLoan,
+    #[error("Loan is not in the state this instruction expects")]
+    InvalidLoanState,
+    #[error("Escrow holds a loan, use the loan instructions")]
+    LoanInProgress,
+    #[error("Loan deadline has passed")]
+    LoanExpired,
+    #[error("Invalid option")]
+    InvalidOption,
+    #[error("Option has already been bought")]
+    OptionAlreadySold,
+    #[error("Option has expired")]
+    OptionExpired,
+    #[error("Invalid condition")]
+    InvalidCondition,
+    #[error("Condition account does not match the escrow's condition")]
+    InvalidConditionAccount,
+    #[error("Escrow condition is not met")]
+    ConditionNotMet,
 }
 
 impl From<EscrowError> for ProgramError {
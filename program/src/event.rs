@@ -0,0 +1,83 @@
+use solana_program::{log::sol_log_data, pubkey::Pubkey};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+/// Bumped whenever the layout of `EscrowEvent` changes.
+pub const EVENT_VERSION: u8 = 1;
+
+const PROGRAM_DATA: &str = "Program data: ";
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub enum EscrowEventKind {
+    EscrowCreated,
+    Deposited,
+    Withdrawn,
+    Settled,
+    Cancelled,
+    Closed,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct EscrowEvent {
+    pub kind: EscrowEventKind,
+    pub escrow: Pubkey,
+    pub party: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub old_state: u8,
+    pub new_state: u8,
+}
+
+impl EscrowEvent {
+    /// Logs the event as `[EVENT_VERSION, borsh(event)]` through `sol_log_data`.
+    pub fn emit(&self) {
+        let mut data = vec![EVENT_VERSION];
+        // writing into a Vec cannot fail
+        self.serialize(&mut data).unwrap();
+        sol_log_data(&[&data]);
+    }
+
+    /// Decodes a single `sol_log_data` field written by `emit`. Returns `None` for other data or
+    /// other event versions.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match data.split_first() {
+            Some((&EVENT_VERSION, event)) => Self::try_from_slice(event).ok(),
+            _ => None,
+        }
+    }
+
+    /// Extracts the events emitted by `program_id` from a transaction's log messages. Data logged
+    /// by other programs, including ones invoked by this program, is skipped.
+    pub fn from_logs<S: AsRef<str>>(program_id: &Pubkey, logs: &[S]) -> Vec<Self> {
+        let program_id = program_id.to_string();
+        let mut stack: Vec<bool> = vec![];
+        let mut events = vec![];
+        for log in logs {
+            let log = log.as_ref();
+            if let Some(data) = log.strip_prefix(PROGRAM_DATA) {
+                if stack.last() != Some(&true) {
+                    continue;
+                }
+                for field in data.split_whitespace() {
+                    if let Some(event) = base64::decode(field).ok().and_then(|bytes| Self::decode(&bytes)) {
+                        events.push(event);
+                    }
+                }
+                continue;
+            }
+
+            // "Program <id> invoke [<depth>]", "Program <id> success" or "Program <id> failed: <err>"
+            let words: Vec<&str> = log.split_whitespace().collect();
+            match words.as_slice() {
+                ["Program", id, "invoke", _] => stack.push(*id == program_id),
+                ["Program", _, "success"] => {
+                    stack.pop();
+                }
+                ["Program", _, "failed:", ..] => {
+                    stack.pop();
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+}
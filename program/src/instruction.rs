@@ -1,5 +1,22 @@
-use solana_program::program_error::ProgramError;
+use solana_program::{
+    ed25519_program,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
+use crate::state::{
+    find_auction_vault_address, find_dutch_auction_address, find_english_auction_address, find_loan_address,
+    find_maker_state_address, find_milestone_escrow_address, find_milestone_vault_address, find_option_address,
+    find_option_vault_address, find_order_authority_address, find_order_nonces_address, find_sealed_auction_address,
+    find_sealed_bid_address, find_stream_address, find_stream_vault_address, Condition, EscrowTerms, Order,
+};
+
+/// Passed as the associated token program account of `Deposit` and `Withdraw`.
+pub mod associated_token_program {
+    solana_program::declare_id!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+}
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum EscrowInstruction {
@@ -10,7 +27,1340 @@ pub enum EscrowInstruction {
     Deposit {
         amount: u64,
     },
+    /// Also takes a stream in place of the escrow, for the recipient to withdraw any `amount` up
+    /// to what has accrued and not been withdrawn yet. Settling a conditional escrow takes the
+    /// condition account after the config.
     Withdraw {
         amount: u64,
     },
+    /// Creates the config PDA. The signer becomes the admin.
+    InitConfig {
+        guardian: Pubkey,
+    },
+    /// Pauses or unpauses the program. Signed by the admin or the guardian.
+    SetPaused {
+        paused: bool,
+    },
+    /// Returns each party's deposit to their own token account. Guardian only, while paused.
+    EmergencyUnwind,
+    /// Creates or updates the registry entry of a mint. Admin only.
+    SetMintRule {
+        allowed: bool,
+        min_size: u64,
+        max_size: u64,
+    },
+    /// Switches between denylist (default) and allowlist-only mint checks. Admin only.
+    SetAllowlistOnly {
+        allowlist_only: bool,
+    },
+    /// Read-only. Returns the `EscrowStatus` of an escrow as return data.
+    GetEscrowStatus,
+    /// Reallocs a v0, v1, v2 or v3 escrow to the current layout. The payer tops up rent.
+    Migrate,
+    /// Closes an empty escrow and its vaults, returning their rent to the rent payer. Signed by
+    /// either party or the rent payer. A Dutch auction escrow that bob never deposited into also
+    /// takes its auction account.
+    CloseEscrow,
+    /// Non-custodial settlement of an empty escrow. Each party approves the escrow as delegate
+    /// of exactly its size on their own token account, and this pulls both legs at once. Needs
+    /// no signature, so anyone can settle once both approvals are in place: the legs only move
+    /// at the escrow's sizes into token accounts owned by the other party. A conditional escrow
+    /// takes the condition account after the config.
+    SettleDelegated,
+    /// Swaps `amount_a` of alice's x for `amount_b` of bob's y in one transaction signed by both,
+    /// without an escrow account or vaults.
+    AtomicSwap {
+        amount_a: u64,
+        amount_b: u64,
+    },
+    /// Fills an order signed off-chain by its maker. Must directly follow the ed25519 program
+    /// instruction built by `verify_order`. The maker's x leg is pulled through their approval
+    /// of the order authority, the taker signs for the y leg.
+    FillOrder {
+        order: Order,
+    },
+    /// Cancels every signed order of the maker with a nonce up to and including `nonce`. The
+    /// maker signs and pays for their maker state when it does not exist yet.
+    InvalidateOrders {
+        nonce: u64,
+    },
+    /// `InitEscrow` where the y leg is a Dutch auction, asking `start_amount_b` until
+    /// `start_time` and falling linearly to `end_amount_b` at `end_time`. Takes the accounts of
+    /// `InitEscrow` without bob, and the auction account right after the mint rules. Bob is
+    /// left open: the escrow and its vaults are derived with `OPEN_ESCROW_SEED` in his place,
+    /// and the first taker's `Deposit` makes them bob.
+    /// It offers at most `amount` and pays the price of the moment, which becomes the escrow's
+    /// `size_b`.
+    InitDutchEscrow {
+        amount_a: u64,
+        start_amount_b: u64,
+        end_amount_b: u64,
+        start_time: i64,
+        end_time: i64,
+    },
+    /// `InitEscrow` with `terms`. Each leg is released to the counterparty along its vesting
+    /// schedule once both are deposited: `Withdraw` of a vesting leg then takes any `amount` up
+    /// to what has vested and not been claimed yet, and can be repeated until the whole leg is
+    /// out. With a condition, legs are only released while it holds on the data of the
+    /// condition account, such as a price feed or another program's flag. Refunds of unmatched
+    /// deposits are not gated.
+    InitEscrowWithTerms {
+        amount_a: u64,
+        amount_b: u64,
+        terms: EscrowTerms,
+    },
+    /// Starts an English auction of `size_a` of the seller's x for bids in y until `deadline`.
+    /// The seller signs, pays rent and moves the lot into the auction's x vault.
+    InitEnglishAuction {
+        size_a: u64,
+        min_increment: u64,
+        reserve_price: u64,
+        deadline: i64,
+    },
+    /// Bids `amount` of y on an English auction. The previous highest bid is refunded to the y
+    /// account passed last, which belongs to the previous highest bidder.
+    PlaceBid {
+        amount: u64,
+    },
+    /// Once the deadline has passed, sends the lot to the highest bidder and the bid to the
+    /// seller, or the lot back to the seller when nobody bid, and closes the auction. Anyone can
+    /// send it.
+    SettleEnglishAuction,
+    /// Starts a sealed-bid auction of `size_a` of the seller's x for bids in y. Bids are committed
+    /// until `commit_deadline` and revealed until `reveal_deadline`. Bidders who never reveal
+    /// forfeit up to `penalty` of their collateral to the seller. Takes the same accounts as
+    /// `InitEnglishAuction`.
+    InitSealedAuction {
+        size_a: u64,
+        reserve_price: u64,
+        penalty: u64,
+        commit_deadline: i64,
+        reveal_deadline: i64,
+    },
+    /// Commits to a bid on a sealed-bid auction with `sealed_bid_commitment`, moving `collateral`
+    /// of y into the auction's y vault. The bid can be at most the collateral.
+    CommitBid {
+        commitment: [u8; 32],
+        collateral: u64,
+    },
+    /// Opens the bidder's commitment during the reveal phase.
+    RevealBid {
+        bid: u64,
+        salt: [u8; 32],
+    },
+    /// After the reveal deadline, sends the lot to the highest valid bidder and their bid to the
+    /// seller, or the lot back to the seller without one. Anyone can send it.
+    SettleSealedAuction,
+    /// Returns a bidder's collateral once the auction is settled, minus the winning bid or the
+    /// penalty for not revealing, and closes their bid. The last withdrawal closes the auction.
+    WithdrawCollateral,
+    /// Escrows the sum of `milestones` of the payer's x for the payee, released one tranche per
+    /// approved milestone. The payer signs and pays rent. An arbiter who may approve milestones
+    /// besides the payer can be passed after the mint rule. Tranches not approved by `deadline`
+    /// can be refunded to the payer. A `condition` other than the default gates every approval
+    /// like the condition of `InitEscrowWithTerms`, refunds are not gated.
+    InitMilestoneEscrow {
+        milestones: Vec<u64>,
+        deadline: i64,
+        condition: Condition,
+    },
+    /// Approves the next milestone and pays its tranche to the payee. Signed by the payer or the
+    /// arbiter. The last approval closes the milestone escrow.
+    ApproveMilestone,
+    /// After the deadline, refunds the tranches not approved yet to the payer and closes the
+    /// milestone escrow.
+    RefundMilestones,
+    /// Streams `rate` of the sender's tokens per second to the recipient from `start` to `end`,
+    /// moving the whole amount into the stream's vault. The sender signs and pays rent. The
+    /// recipient takes out what has accrued with `Withdraw`.
+    InitStream {
+        rate: u64,
+        start: i64,
+        end: i64,
+    },
+    /// Ends a stream early or after its end. What has accrued and not been withdrawn goes to the
+    /// recipient, the rest back to the sender, and the stream is closed. Signed by the sender,
+    /// also while the program is paused.
+    CancelStream,
+    /// `InitEscrow` for a loan of `amount_b` of the lender's y against `amount_a` of the
+    /// borrower's x as collateral. Takes the loan account and the borrower's x source after the
+    /// mint rules, and moves the collateral into the x vault right away. The borrower owes
+    /// `interest` on top of the principal by `deadline`.
+    InitLoan {
+        amount_a: u64,
+        amount_b: u64,
+        interest: u64,
+        deadline: i64,
+    },
+    /// Pays the principal from the lender straight to the borrower before the deadline.
+    AcceptLoan,
+    /// Pays the principal and interest from the borrower to the lender before the deadline and
+    /// returns the collateral to the borrower.
+    RepayLoan,
+    /// After the deadline of an unpaid loan, gives the collateral to the lender.
+    ClaimCollateral,
+    /// Returns the collateral of a loan nobody accepted to the borrower.
+    CancelLoan,
+    /// Writes a covered option on `amount` of the writer's x, moving it into the option's vault.
+    /// The buyer pays `premium` in y for the right to buy the x for `strike` in y before
+    /// `expiry`. The writer signs and pays rent.
+    WriteOption {
+        amount: u64,
+        premium: u64,
+        strike: u64,
+        expiry: i64,
+    },
+    /// Pays the premium to the writer and makes the buyer the owner of the option.
+    BuyOption,
+    /// Hands the option to `new_owner`. Signed by the current owner.
+    TransferOption {
+        new_owner: Pubkey,
+    },
+    /// Pays the strike to the writer and the underlying to the owner, and closes the option.
+    /// Signed by the owner before expiry.
+    ExerciseOption,
+    /// Returns the underlying to the writer and closes the option, once it has expired or at
+    /// any time while nobody has bought it.
+    ReclaimOption,
+}
+
+/// Creates an `InitEscrow` instruction. `alice` signs and pays rent, see `init_escrow_sponsored`
+/// for rent paid by someone else.
+#[allow(clippy::too_many_arguments)]
+pub fn init_escrow(
+    program_id: &Pubkey,
+    alice: &Pubkey,
+    bob: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    escrow: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    y_mint_rule: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::InitEscrow { amount_a, amount_b }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*alice, true),
+        AccountMeta::new_readonly(*bob, false),
+        AccountMeta::new_readonly(*x_mint, false),
+        AccountMeta::new_readonly(*y_mint, false),
+        AccountMeta::new(*x_vault, false),
+        AccountMeta::new(*y_vault, false),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*x_mint_rule, false),
+        AccountMeta::new_readonly(*y_mint_rule, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `InitEscrow` instruction with rent paid by `rent_payer`. Both `alice` and
+/// `rent_payer` sign, and `rent_payer` gets the rent back when the escrow is closed.
+#[allow(clippy::too_many_arguments)]
+pub fn init_escrow_sponsored(
+    program_id: &Pubkey,
+    rent_payer: &Pubkey,
+    alice: &Pubkey,
+    bob: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    escrow: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    y_mint_rule: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = init_escrow(
+        program_id, alice, bob, x_mint, y_mint, x_vault, y_vault, escrow, config, x_mint_rule, y_mint_rule,
+        amount_a, amount_b,
+    )?;
+    instruction.accounts[0] = AccountMeta::new_readonly(*alice, true);
+    instruction.accounts.push(AccountMeta::new(*rent_payer, true));
+    Ok(instruction)
+}
+
+/// Creates an `InitDutchEscrow` instruction. `alice` signs and pays rent. The vaults and the
+/// escrow are at `find_open_vault_address` and `find_open_escrow_address`.
+#[allow(clippy::too_many_arguments)]
+pub fn init_dutch_escrow(
+    program_id: &Pubkey,
+    alice: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    escrow: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    y_mint_rule: &Pubkey,
+    amount_a: u64,
+    start_amount_b: u64,
+    end_amount_b: u64,
+    start_time: i64,
+    end_time: i64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = init_escrow(
+        program_id, alice, alice, x_mint, y_mint, x_vault, y_vault, escrow, config, x_mint_rule, y_mint_rule,
+        amount_a, start_amount_b,
+    )?;
+    // without bob
+    instruction.accounts.remove(1);
+    instruction.data =
+        EscrowInstruction::InitDutchEscrow { amount_a, start_amount_b, end_amount_b, start_time, end_time }
+            .try_to_vec()?;
+    let (dutch_auction, _) = find_dutch_auction_address(program_id, escrow);
+    instruction.accounts.push(AccountMeta::new(dutch_auction, false));
+    Ok(instruction)
+}
+
+/// Creates an `InitEscrowWithTerms` instruction. `alice` signs and pays rent.
+#[allow(clippy::too_many_arguments)]
+pub fn init_escrow_with_terms(
+    program_id: &Pubkey,
+    alice: &Pubkey,
+    bob: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    escrow: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    y_mint_rule: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+    terms: EscrowTerms,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = init_escrow(
+        program_id, alice, bob, x_mint, y_mint, x_vault, y_vault, escrow, config, x_mint_rule, y_mint_rule,
+        amount_a, amount_b,
+    )?;
+    instruction.data = EscrowInstruction::InitEscrowWithTerms { amount_a, amount_b, terms }.try_to_vec()?;
+    Ok(instruction)
+}
+
+/// Creates a `Deposit` instruction moving `amount` from `source` into `vault`.
+pub fn deposit(
+    program_id: &Pubkey,
+    depositor: &Pubkey,
+    vault: &Pubkey,
+    escrow: &Pubkey,
+    source: &Pubkey,
+    config: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Deposit { amount }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*depositor, true),
+        AccountMeta::new(*vault, false),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*source, false),
+        AccountMeta::new_readonly(associated_token_program::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a taker's `Deposit` into a Dutch auction escrow, paying the current price as long as
+/// it is at most `max_amount`. The first taker to deposit becomes bob.
+pub fn deposit_dutch(
+    program_id: &Pubkey,
+    taker: &Pubkey,
+    y_vault: &Pubkey,
+    escrow: &Pubkey,
+    source: &Pubkey,
+    config: &Pubkey,
+    max_amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit(program_id, taker, y_vault, escrow, source, config, max_amount)?;
+    let (dutch_auction, _) = find_dutch_auction_address(program_id, escrow);
+    instruction.accounts.push(AccountMeta::new(dutch_auction, false));
+    Ok(instruction)
+}
+
+/// Creates a `Withdraw` instruction moving `amount` from `vault` into `destination`.
+pub fn withdraw(
+    program_id: &Pubkey,
+    withdrawer: &Pubkey,
+    vault: &Pubkey,
+    escrow: &Pubkey,
+    destination: &Pubkey,
+    config: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Withdraw { amount }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*withdrawer, true),
+        AccountMeta::new(*vault, false),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(associated_token_program::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `Withdraw` instruction settling a leg of a conditional escrow, passing the account
+/// its condition reads.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_conditional(
+    program_id: &Pubkey,
+    withdrawer: &Pubkey,
+    vault: &Pubkey,
+    escrow: &Pubkey,
+    destination: &Pubkey,
+    config: &Pubkey,
+    condition_account: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = withdraw(program_id, withdrawer, vault, escrow, destination, config, amount)?;
+    instruction.accounts.push(AccountMeta::new_readonly(*condition_account, false));
+    Ok(instruction)
+}
+
+/// Creates an `InitConfig` instruction. `admin` signs and pays rent.
+pub fn init_config(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    config: &Pubkey,
+    guardian: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::InitConfig { guardian: *guardian }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*config, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `SetPaused` instruction signed by the admin or the guardian.
+pub fn set_paused(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    config: &Pubkey,
+    paused: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SetPaused { paused }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `EmergencyUnwind` instruction refunding into `alice_x` and `bob_y`.
+#[allow(clippy::too_many_arguments)]
+pub fn emergency_unwind(
+    program_id: &Pubkey,
+    guardian: &Pubkey,
+    config: &Pubkey,
+    escrow: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    alice_x: &Pubkey,
+    bob_y: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::EmergencyUnwind.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*guardian, true),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(*x_vault, false),
+        AccountMeta::new(*y_vault, false),
+        AccountMeta::new(*alice_x, false),
+        AccountMeta::new(*bob_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `SetMintRule` instruction. `admin` signs and pays rent.
+#[allow(clippy::too_many_arguments)]
+pub fn set_mint_rule(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    config: &Pubkey,
+    mint: &Pubkey,
+    mint_rule: &Pubkey,
+    allowed: bool,
+    min_size: u64,
+    max_size: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SetMintRule { allowed, min_size, max_size }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(*mint_rule, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `SetAllowlistOnly` instruction signed by the admin.
+pub fn set_allowlist_only(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    config: &Pubkey,
+    allowlist_only: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SetAllowlistOnly { allowlist_only }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `GetEscrowStatus` instruction.
+pub fn get_escrow_status(program_id: &Pubkey, escrow: &Pubkey) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::GetEscrowStatus.try_to_vec()?;
+    let accounts = vec![AccountMeta::new_readonly(*escrow, false)];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `Migrate` instruction. `payer` signs and tops up rent.
+pub fn migrate(program_id: &Pubkey, payer: &Pubkey, escrow: &Pubkey) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Migrate.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `CloseEscrow` instruction returning rent to `rent_payer`.
+pub fn close_escrow(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    escrow: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    rent_payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::CloseEscrow.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(*x_vault, false),
+        AccountMeta::new(*y_vault, false),
+        AccountMeta::new(*rent_payer, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `CloseEscrow` instruction for a Dutch auction escrow that bob never deposited
+/// into, which also closes the auction account.
+pub fn close_dutch_escrow(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    escrow: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    rent_payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = close_escrow(program_id, authority, escrow, x_vault, y_vault, rent_payer)?;
+    let (dutch_auction, _) = find_dutch_auction_address(program_id, escrow);
+    instruction.accounts.push(AccountMeta::new(dutch_auction, false));
+    Ok(instruction)
+}
+
+/// Creates a `SettleDelegated` instruction. `alice_x` and `bob_y` must have approved the escrow
+/// for `size_a` and `size_b`.
+#[allow(clippy::too_many_arguments)]
+pub fn settle_delegated(
+    program_id: &Pubkey,
+    escrow: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    alice_x: &Pubkey,
+    alice_y: &Pubkey,
+    bob_x: &Pubkey,
+    bob_y: &Pubkey,
+    config: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SettleDelegated.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*escrow, false),
+        AccountMeta::new_readonly(*x_vault, false),
+        AccountMeta::new_readonly(*y_vault, false),
+        AccountMeta::new(*alice_x, false),
+        AccountMeta::new(*alice_y, false),
+        AccountMeta::new(*bob_x, false),
+        AccountMeta::new(*bob_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `AtomicSwap` instruction. `alice` and `bob` both sign.
+#[allow(clippy::too_many_arguments)]
+pub fn atomic_swap(
+    program_id: &Pubkey,
+    alice: &Pubkey,
+    bob: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    alice_x: &Pubkey,
+    alice_y: &Pubkey,
+    bob_x: &Pubkey,
+    bob_y: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    y_mint_rule: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::AtomicSwap { amount_a, amount_b }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*alice, true),
+        AccountMeta::new_readonly(*bob, true),
+        AccountMeta::new_readonly(*x_mint, false),
+        AccountMeta::new_readonly(*y_mint, false),
+        AccountMeta::new(*alice_x, false),
+        AccountMeta::new(*alice_y, false),
+        AccountMeta::new(*bob_x, false),
+        AccountMeta::new(*bob_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*x_mint_rule, false),
+        AccountMeta::new_readonly(*y_mint_rule, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates the ed25519 program instruction that `FillOrder` of `program_id` expects right before
+/// it, checking `signature` by the maker over `order.message(program_id)`.
+pub fn verify_order(program_id: &Pubkey, order: &Order, signature: &[u8; 64]) -> Result<Instruction, ProgramError> {
+    let message = order.message(program_id)?;
+    // one signature, its offsets, then the public key, signature and message, all in this instruction
+    let public_key_offset: u16 = 2 + 14;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+    let offsets = [
+        signature_offset,
+        u16::MAX,
+        public_key_offset,
+        u16::MAX,
+        message_offset,
+        message.len() as u16,
+        u16::MAX,
+    ];
+
+    let mut data = vec![1, 0];
+    for offset in offsets {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(order.maker.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(&message);
+    Ok(Instruction { program_id: ed25519_program::id(), accounts: vec![], data })
+}
+
+/// Creates a `FillOrder` instruction, to be sent after `verify_order`. `taker` signs and pays
+/// for the maker's nonce account when it does not exist yet.
+#[allow(clippy::too_many_arguments)]
+pub fn fill_order(
+    program_id: &Pubkey,
+    taker: &Pubkey,
+    order: &Order,
+    maker_x: &Pubkey,
+    maker_y: &Pubkey,
+    taker_x: &Pubkey,
+    taker_y: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    y_mint_rule: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (order_authority, _) = find_order_authority_address(program_id);
+    let (order_nonces, _) = find_order_nonces_address(program_id, &order.maker, order.nonce);
+    let (maker_state, _) = find_maker_state_address(program_id, &order.maker);
+    let data = EscrowInstruction::FillOrder { order: order.clone() }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*taker, true),
+        AccountMeta::new_readonly(order.x_mint, false),
+        AccountMeta::new_readonly(order.y_mint, false),
+        AccountMeta::new(*maker_x, false),
+        AccountMeta::new(*maker_y, false),
+        AccountMeta::new(*taker_x, false),
+        AccountMeta::new(*taker_y, false),
+        AccountMeta::new_readonly(order_authority, false),
+        AccountMeta::new(order_nonces, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*x_mint_rule, false),
+        AccountMeta::new_readonly(*y_mint_rule, false),
+        AccountMeta::new_readonly(maker_state, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `InvalidateOrders` instruction.
+pub fn invalidate_orders(program_id: &Pubkey, maker: &Pubkey, nonce: u64) -> Result<Instruction, ProgramError> {
+    let (maker_state, _) = find_maker_state_address(program_id, maker);
+    let data = EscrowInstruction::InvalidateOrders { nonce }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*maker, true),
+        AccountMeta::new(maker_state, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `InitEnglishAuction` instruction for the auction of `seller`'s `x_mint` against
+/// `y_mint`, moving `size_a` from `seller_x`.
+#[allow(clippy::too_many_arguments)]
+pub fn init_english_auction(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    seller_x: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    size_a: u64,
+    min_increment: u64,
+    reserve_price: u64,
+    deadline: i64,
+) -> Result<Instruction, ProgramError> {
+    let (auction, _) = find_english_auction_address(program_id, seller, x_mint, y_mint);
+    let (x_vault, _) = find_auction_vault_address(program_id, &auction, x_mint);
+    let (y_vault, _) = find_auction_vault_address(program_id, &auction, y_mint);
+    let data = EscrowInstruction::InitEnglishAuction { size_a, min_increment, reserve_price, deadline }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*seller, true),
+        AccountMeta::new_readonly(*x_mint, false),
+        AccountMeta::new_readonly(*y_mint, false),
+        AccountMeta::new(auction, false),
+        AccountMeta::new(x_vault, false),
+        AccountMeta::new(y_vault, false),
+        AccountMeta::new(*seller_x, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*x_mint_rule, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `PlaceBid` instruction. `previous_bidder_y` is required once the auction has a bid.
+#[allow(clippy::too_many_arguments)]
+pub fn place_bid(
+    program_id: &Pubkey,
+    bidder: &Pubkey,
+    auction: &Pubkey,
+    y_mint: &Pubkey,
+    bidder_y: &Pubkey,
+    config: &Pubkey,
+    y_mint_rule: &Pubkey,
+    previous_bidder_y: Option<&Pubkey>,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let (y_vault, _) = find_auction_vault_address(program_id, auction, y_mint);
+    let data = EscrowInstruction::PlaceBid { amount }.try_to_vec()?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*bidder, true),
+        AccountMeta::new(*auction, false),
+        AccountMeta::new(y_vault, false),
+        AccountMeta::new(*bidder_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*y_mint_rule, false),
+    ];
+    if let Some(previous_bidder_y) = previous_bidder_y {
+        accounts.push(AccountMeta::new(*previous_bidder_y, false));
+    }
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `SettleEnglishAuction` instruction. `x_destination` is the winner's x account, or
+/// the seller's when nobody bid.
+#[allow(clippy::too_many_arguments)]
+pub fn settle_english_auction(
+    program_id: &Pubkey,
+    auction: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    seller: &Pubkey,
+    seller_y: &Pubkey,
+    x_destination: &Pubkey,
+    config: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (x_vault, _) = find_auction_vault_address(program_id, auction, x_mint);
+    let (y_vault, _) = find_auction_vault_address(program_id, auction, y_mint);
+    let data = EscrowInstruction::SettleEnglishAuction.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*auction, false),
+        AccountMeta::new(x_vault, false),
+        AccountMeta::new(y_vault, false),
+        AccountMeta::new(*seller, false),
+        AccountMeta::new(*seller_y, false),
+        AccountMeta::new(*x_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `InitSealedAuction` instruction for the auction of `seller`'s `x_mint` against
+/// `y_mint`, moving `size_a` from `seller_x`.
+#[allow(clippy::too_many_arguments)]
+pub fn init_sealed_auction(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    seller_x: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    size_a: u64,
+    reserve_price: u64,
+    penalty: u64,
+    commit_deadline: i64,
+    reveal_deadline: i64,
+) -> Result<Instruction, ProgramError> {
+    let (auction, _) = find_sealed_auction_address(program_id, seller, x_mint, y_mint);
+    let (x_vault, _) = find_auction_vault_address(program_id, &auction, x_mint);
+    let (y_vault, _) = find_auction_vault_address(program_id, &auction, y_mint);
+    let data = EscrowInstruction::InitSealedAuction { size_a, reserve_price, penalty, commit_deadline, reveal_deadline }
+        .try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*seller, true),
+        AccountMeta::new_readonly(*x_mint, false),
+        AccountMeta::new_readonly(*y_mint, false),
+        AccountMeta::new(auction, false),
+        AccountMeta::new(x_vault, false),
+        AccountMeta::new(y_vault, false),
+        AccountMeta::new(*seller_x, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*x_mint_rule, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `CommitBid` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn commit_bid(
+    program_id: &Pubkey,
+    bidder: &Pubkey,
+    auction: &Pubkey,
+    y_mint: &Pubkey,
+    bidder_y: &Pubkey,
+    config: &Pubkey,
+    commitment: [u8; 32],
+    collateral: u64,
+) -> Result<Instruction, ProgramError> {
+    let (bid, _) = find_sealed_bid_address(program_id, auction, bidder);
+    let (y_vault, _) = find_auction_vault_address(program_id, auction, y_mint);
+    let data = EscrowInstruction::CommitBid { commitment, collateral }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*bidder, true),
+        AccountMeta::new(*auction, false),
+        AccountMeta::new(bid, false),
+        AccountMeta::new(y_vault, false),
+        AccountMeta::new(*bidder_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `RevealBid` instruction.
+pub fn reveal_bid(
+    program_id: &Pubkey,
+    bidder: &Pubkey,
+    auction: &Pubkey,
+    config: &Pubkey,
+    y_mint_rule: &Pubkey,
+    bid: u64,
+    salt: [u8; 32],
+) -> Result<Instruction, ProgramError> {
+    let (bid_account, _) = find_sealed_bid_address(program_id, auction, bidder);
+    let data = EscrowInstruction::RevealBid { bid, salt }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*bidder, true),
+        AccountMeta::new(*auction, false),
+        AccountMeta::new(bid_account, false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*y_mint_rule, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `SettleSealedAuction` instruction. `x_destination` is the winner's x account, or
+/// the seller's without a valid bid.
+#[allow(clippy::too_many_arguments)]
+pub fn settle_sealed_auction(
+    program_id: &Pubkey,
+    auction: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    seller: &Pubkey,
+    seller_y: &Pubkey,
+    x_destination: &Pubkey,
+    config: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (x_vault, _) = find_auction_vault_address(program_id, auction, x_mint);
+    let (y_vault, _) = find_auction_vault_address(program_id, auction, y_mint);
+    let data = EscrowInstruction::SettleSealedAuction.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*auction, false),
+        AccountMeta::new(x_vault, false),
+        AccountMeta::new(y_vault, false),
+        AccountMeta::new(*seller, false),
+        AccountMeta::new(*seller_y, false),
+        AccountMeta::new(*x_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `WithdrawCollateral` instruction.
+pub fn withdraw_collateral(
+    program_id: &Pubkey,
+    bidder: &Pubkey,
+    auction: &Pubkey,
+    y_mint: &Pubkey,
+    bidder_y: &Pubkey,
+    seller: &Pubkey,
+    seller_y: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (bid, _) = find_sealed_bid_address(program_id, auction, bidder);
+    let (y_vault, _) = find_auction_vault_address(program_id, auction, y_mint);
+    let data = EscrowInstruction::WithdrawCollateral.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*bidder, true),
+        AccountMeta::new(*auction, false),
+        AccountMeta::new(bid, false),
+        AccountMeta::new(y_vault, false),
+        AccountMeta::new(*bidder_y, false),
+        AccountMeta::new(*seller, false),
+        AccountMeta::new(*seller_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `InitMilestoneEscrow` instruction paying `payee` from `payer_source`.
+#[allow(clippy::too_many_arguments)]
+pub fn init_milestone_escrow(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    payee: &Pubkey,
+    mint: &Pubkey,
+    payer_source: &Pubkey,
+    config: &Pubkey,
+    mint_rule: &Pubkey,
+    arbiter: Option<&Pubkey>,
+    milestones: Vec<u64>,
+    deadline: i64,
+    condition: Condition,
+) -> Result<Instruction, ProgramError> {
+    let (milestone_escrow, _) = find_milestone_escrow_address(program_id, payer, payee, mint);
+    let (vault, _) = find_milestone_vault_address(program_id, &milestone_escrow);
+    let data = EscrowInstruction::InitMilestoneEscrow { milestones, deadline, condition }.try_to_vec()?;
+    let mut accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*payee, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(milestone_escrow, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*payer_source, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*mint_rule, false),
+    ];
+    if let Some(arbiter) = arbiter {
+        accounts.push(AccountMeta::new_readonly(*arbiter, false));
+    }
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `ApproveMilestone` instruction. `approver` is the payer or the arbiter.
+pub fn approve_milestone(
+    program_id: &Pubkey,
+    approver: &Pubkey,
+    milestone_escrow: &Pubkey,
+    payer: &Pubkey,
+    payee_destination: &Pubkey,
+    config: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (vault, _) = find_milestone_vault_address(program_id, milestone_escrow);
+    let data = EscrowInstruction::ApproveMilestone.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*approver, true),
+        AccountMeta::new(*milestone_escrow, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*payee_destination, false),
+        AccountMeta::new(*payer, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `ApproveMilestone` instruction for a milestone escrow with a condition, read from
+/// `condition_account`.
+pub fn approve_milestone_conditional(
+    program_id: &Pubkey,
+    approver: &Pubkey,
+    milestone_escrow: &Pubkey,
+    payer: &Pubkey,
+    payee_destination: &Pubkey,
+    config: &Pubkey,
+    condition_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = approve_milestone(program_id, approver, milestone_escrow, payer, payee_destination, config)?;
+    instruction.accounts.push(AccountMeta::new_readonly(*condition_account, false));
+    Ok(instruction)
+}
+
+/// Creates a `RefundMilestones` instruction.
+pub fn refund_milestones(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    milestone_escrow: &Pubkey,
+    payer_destination: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (vault, _) = find_milestone_vault_address(program_id, milestone_escrow);
+    let data = EscrowInstruction::RefundMilestones.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(*milestone_escrow, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*payer_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `InitStream` instruction paying `recipient` from `sender_source`.
+#[allow(clippy::too_many_arguments)]
+pub fn init_stream(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    mint: &Pubkey,
+    sender_source: &Pubkey,
+    config: &Pubkey,
+    mint_rule: &Pubkey,
+    rate: u64,
+    start: i64,
+    end: i64,
+) -> Result<Instruction, ProgramError> {
+    let (stream, _) = find_stream_address(program_id, sender, recipient, mint);
+    let (vault, _) = find_stream_vault_address(program_id, &stream);
+    let data = EscrowInstruction::InitStream { rate, start, end }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*sender, true),
+        AccountMeta::new_readonly(*recipient, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(stream, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*sender_source, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*mint_rule, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `CancelStream` instruction.
+pub fn cancel_stream(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    stream: &Pubkey,
+    sender_destination: &Pubkey,
+    recipient_destination: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (vault, _) = find_stream_vault_address(program_id, stream);
+    let data = EscrowInstruction::CancelStream.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*sender, true),
+        AccountMeta::new(*stream, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*sender_destination, false),
+        AccountMeta::new(*recipient_destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `InitLoan` instruction. `alice` is the borrower, signs and pays rent, and `bob`
+/// the lender.
+#[allow(clippy::too_many_arguments)]
+pub fn init_loan(
+    program_id: &Pubkey,
+    alice: &Pubkey,
+    bob: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    escrow: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    y_mint_rule: &Pubkey,
+    alice_x: &Pubkey,
+    amount_a: u64,
+    amount_b: u64,
+    interest: u64,
+    deadline: i64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = init_escrow(
+        program_id, alice, bob, x_mint, y_mint, x_vault, y_vault, escrow, config, x_mint_rule, y_mint_rule,
+        amount_a, amount_b,
+    )?;
+    instruction.data = EscrowInstruction::InitLoan { amount_a, amount_b, interest, deadline }.try_to_vec()?;
+    let (loan, _) = find_loan_address(program_id, escrow);
+    instruction.accounts.push(AccountMeta::new(loan, false));
+    instruction.accounts.push(AccountMeta::new(*alice_x, false));
+    Ok(instruction)
+}
+
+/// Creates an `AcceptLoan` instruction.
+pub fn accept_loan(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    escrow: &Pubkey,
+    lender_y: &Pubkey,
+    borrower_y: &Pubkey,
+    config: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (loan, _) = find_loan_address(program_id, escrow);
+    let data = EscrowInstruction::AcceptLoan.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*lender, true),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(loan, false),
+        AccountMeta::new(*lender_y, false),
+        AccountMeta::new(*borrower_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `RepayLoan` instruction.
+pub fn repay_loan(
+    program_id: &Pubkey,
+    borrower: &Pubkey,
+    escrow: &Pubkey,
+    x_vault: &Pubkey,
+    borrower_x: &Pubkey,
+    borrower_y: &Pubkey,
+    lender_y: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (loan, _) = find_loan_address(program_id, escrow);
+    let data = EscrowInstruction::RepayLoan.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*borrower, true),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(loan, false),
+        AccountMeta::new(*x_vault, false),
+        AccountMeta::new(*borrower_x, false),
+        AccountMeta::new(*borrower_y, false),
+        AccountMeta::new(*lender_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `ClaimCollateral` instruction. The loan's rent goes back to `borrower`.
+pub fn claim_collateral(
+    program_id: &Pubkey,
+    lender: &Pubkey,
+    borrower: &Pubkey,
+    escrow: &Pubkey,
+    x_vault: &Pubkey,
+    lender_x: &Pubkey,
+    config: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (loan, _) = find_loan_address(program_id, escrow);
+    let data = EscrowInstruction::ClaimCollateral.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*lender, true),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(loan, false),
+        AccountMeta::new(*x_vault, false),
+        AccountMeta::new(*lender_x, false),
+        AccountMeta::new(*borrower, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `CancelLoan` instruction.
+pub fn cancel_loan(
+    program_id: &Pubkey,
+    borrower: &Pubkey,
+    escrow: &Pubkey,
+    x_vault: &Pubkey,
+    borrower_x: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (loan, _) = find_loan_address(program_id, escrow);
+    let data = EscrowInstruction::CancelLoan.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*borrower, true),
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(loan, false),
+        AccountMeta::new(*x_vault, false),
+        AccountMeta::new(*borrower_x, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `WriteOption` instruction on `writer`'s `x_mint` against `y_mint`, moving `amount`
+/// from `writer_x`.
+#[allow(clippy::too_many_arguments)]
+pub fn write_option(
+    program_id: &Pubkey,
+    writer: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+    writer_x: &Pubkey,
+    config: &Pubkey,
+    x_mint_rule: &Pubkey,
+    y_mint_rule: &Pubkey,
+    amount: u64,
+    premium: u64,
+    strike: u64,
+    expiry: i64,
+) -> Result<Instruction, ProgramError> {
+    let (option, _) = find_option_address(program_id, writer, x_mint, y_mint);
+    let (vault, _) = find_option_vault_address(program_id, &option);
+    let data = EscrowInstruction::WriteOption { amount, premium, strike, expiry }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*writer, true),
+        AccountMeta::new_readonly(*x_mint, false),
+        AccountMeta::new_readonly(*y_mint, false),
+        AccountMeta::new(option, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*writer_x, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new_readonly(*x_mint_rule, false),
+        AccountMeta::new_readonly(*y_mint_rule, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `BuyOption` instruction.
+pub fn buy_option(
+    program_id: &Pubkey,
+    buyer: &Pubkey,
+    option: &Pubkey,
+    buyer_y: &Pubkey,
+    writer_y: &Pubkey,
+    config: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::BuyOption.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*buyer, true),
+        AccountMeta::new(*option, false),
+        AccountMeta::new(*buyer_y, false),
+        AccountMeta::new(*writer_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `TransferOption` instruction.
+pub fn transfer_option(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    option: &Pubkey,
+    new_owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::TransferOption { new_owner: *new_owner }.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*option, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates an `ExerciseOption` instruction. The option's rent goes back to `writer`.
+#[allow(clippy::too_many_arguments)]
+pub fn exercise_option(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    option: &Pubkey,
+    owner_x: &Pubkey,
+    owner_y: &Pubkey,
+    writer: &Pubkey,
+    writer_y: &Pubkey,
+    config: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (vault, _) = find_option_vault_address(program_id, option);
+    let data = EscrowInstruction::ExerciseOption.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*option, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*owner_x, false),
+        AccountMeta::new(*owner_y, false),
+        AccountMeta::new(*writer, false),
+        AccountMeta::new(*writer_y, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*config, false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+/// Creates a `ReclaimOption` instruction.
+pub fn reclaim_option(
+    program_id: &Pubkey,
+    writer: &Pubkey,
+    option: &Pubkey,
+    writer_x: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (vault, _) = find_option_vault_address(program_id, option);
+    let data = EscrowInstruction::ReclaimOption.try_to_vec()?;
+    let accounts = vec![
+        AccountMeta::new(*writer, true),
+        AccountMeta::new(*option, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*writer_x, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction { program_id: *program_id, accounts, data })
 }
@@ -1,4 +1,6 @@
+pub mod cpi;
 pub mod error;
+pub mod event;
 pub mod instruction;
 pub mod processor;
 pub mod state;
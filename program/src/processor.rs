@@ -1,22 +1,43 @@
 use {
     solana_program::{
         account_info::{next_account_info, AccountInfo},
+        ed25519_program,
         entrypoint::ProgramResult,
         msg,
-        program::{invoke, invoke_signed},
+        program::{invoke, invoke_signed, set_return_data},
         program_error::ProgramError,
         pubkey::Pubkey,
         system_instruction,
-        sysvar::{rent::Rent, Sysvar},
+        sysvar::{
+            clock::Clock,
+            instructions::{load_current_index_checked, load_instruction_at_checked},
+            rent::Rent,
+            Sysvar,
+        },
+        program_option::COption,
         program_pack::{IsInitialized, Pack}
     },
     std::convert::TryInto,
     spl_token::{
-        instruction::{initialize_account, transfer},
-        state::Account,
+        instruction::{close_account, initialize_account, transfer},
+        state::{Account, Mint},
     },
 };
-use crate::{instruction::EscrowInstruction, state::Escrow, error::EscrowError};
+use crate::{
+    event::{EscrowEvent, EscrowEventKind},
+    instruction::EscrowInstruction,
+    state::{
+        find_auction_vault_address, find_dutch_auction_address, find_escrow_address_with_seed, find_loan_address,
+        find_option_address, find_option_vault_address, find_order_nonces_address, find_sealed_bid_address,
+        find_vault_address_with_seed, sealed_bid_commitment, Condition, Config, CoveredOption, DutchAuction,
+        EnglishAuction, Escrow, EscrowTerms, EscrowV0, EscrowV1, EscrowV2, EscrowV3, Loan, MakerState, MilestoneEscrow,
+        MintRule, Order, OrderNonces, SealedAuction, SealedBid, Stream, AUCTION_VAULT_SEED, CONFIG_SEED,
+        DUTCH_AUCTION_SEED, ENGLISH_AUCTION_SEED, LOAN_SEED, MAKER_STATE_SEED, MILESTONE_ESCROW_SEED,
+        MILESTONE_VAULT_SEED, MINT_RULE_SEED, OPEN_ESCROW_SEED, OPTION_SEED, OPTION_VAULT_SEED, ORDER_AUTHORITY_SEED,
+        ORDER_NONCES_SEED, SEALED_AUCTION_SEED, SEALED_BID_SEED, STREAM_SEED, STREAM_VAULT_SEED,
+    },
+    error::EscrowError,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
 
 pub struct Processor;
@@ -31,7 +52,7 @@ impl Processor {
         match instruction {
             EscrowInstruction::InitEscrow { amount_a, amount_b } => {
                 msg!("Instruction: InitEscrow");
-                Self::process_init_escrow(accounts, amount_a, amount_b, program_id)
+                Self::process_init_escrow(accounts, amount_a, amount_b, &EscrowTerms::default(), false, program_id)
             }
             EscrowInstruction::Deposit { amount } => {
                 msg!("Instruction: Deposit");
@@ -41,6 +62,164 @@ impl Processor {
                 msg!("Instruction: Withdraw");
                 Self::withdraw(accounts, amount, program_id)
             }
+            EscrowInstruction::InitConfig { guardian } => {
+                msg!("Instruction: InitConfig");
+                Self::process_init_config(accounts, guardian, program_id)
+            }
+            EscrowInstruction::SetPaused { paused } => {
+                msg!("Instruction: SetPaused");
+                Self::process_set_paused(accounts, paused, program_id)
+            }
+            EscrowInstruction::EmergencyUnwind => {
+                msg!("Instruction: EmergencyUnwind");
+                Self::process_emergency_unwind(accounts, program_id)
+            }
+            EscrowInstruction::SetMintRule { allowed, min_size, max_size } => {
+                msg!("Instruction: SetMintRule");
+                Self::process_set_mint_rule(accounts, allowed, min_size, max_size, program_id)
+            }
+            EscrowInstruction::SetAllowlistOnly { allowlist_only } => {
+                msg!("Instruction: SetAllowlistOnly");
+                Self::process_set_allowlist_only(accounts, allowlist_only, program_id)
+            }
+            EscrowInstruction::GetEscrowStatus => {
+                msg!("Instruction: GetEscrowStatus");
+                Self::process_get_escrow_status(accounts, program_id)
+            }
+            EscrowInstruction::Migrate => {
+                msg!("Instruction: Migrate");
+                Self::process_migrate(accounts, program_id)
+            }
+            EscrowInstruction::CloseEscrow => {
+                msg!("Instruction: CloseEscrow");
+                Self::process_close_escrow(accounts, program_id)
+            }
+            EscrowInstruction::SettleDelegated => {
+                msg!("Instruction: SettleDelegated");
+                Self::process_settle_delegated(accounts, program_id)
+            }
+            EscrowInstruction::AtomicSwap { amount_a, amount_b } => {
+                msg!("Instruction: AtomicSwap");
+                Self::process_atomic_swap(accounts, amount_a, amount_b, program_id)
+            }
+            EscrowInstruction::FillOrder { order } => {
+                msg!("Instruction: FillOrder");
+                Self::process_fill_order(accounts, order, program_id)
+            }
+            EscrowInstruction::InvalidateOrders { nonce } => {
+                msg!("Instruction: InvalidateOrders");
+                Self::process_invalidate_orders(accounts, nonce, program_id)
+            }
+            EscrowInstruction::InitDutchEscrow { amount_a, start_amount_b, end_amount_b, start_time, end_time } => {
+                msg!("Instruction: InitDutchEscrow");
+                Self::process_init_dutch_escrow(
+                    accounts, amount_a, start_amount_b, end_amount_b, start_time, end_time, program_id,
+                )
+            }
+            EscrowInstruction::InitEscrowWithTerms { amount_a, amount_b, terms } => {
+                msg!("Instruction: InitEscrowWithTerms");
+                Self::process_init_escrow(accounts, amount_a, amount_b, &terms, false, program_id)
+            }
+            EscrowInstruction::InitEnglishAuction { size_a, min_increment, reserve_price, deadline } => {
+                msg!("Instruction: InitEnglishAuction");
+                Self::process_init_english_auction(accounts, size_a, min_increment, reserve_price, deadline, program_id)
+            }
+            EscrowInstruction::PlaceBid { amount } => {
+                msg!("Instruction: PlaceBid");
+                Self::process_place_bid(accounts, amount, program_id)
+            }
+            EscrowInstruction::SettleEnglishAuction => {
+                msg!("Instruction: SettleEnglishAuction");
+                Self::process_settle_english_auction(accounts, program_id)
+            }
+            EscrowInstruction::InitSealedAuction {
+                size_a,
+                reserve_price,
+                penalty,
+                commit_deadline,
+                reveal_deadline,
+            } => {
+                msg!("Instruction: InitSealedAuction");
+                Self::process_init_sealed_auction(
+                    accounts, size_a, reserve_price, penalty, commit_deadline, reveal_deadline, program_id,
+                )
+            }
+            EscrowInstruction::CommitBid { commitment, collateral } => {
+                msg!("Instruction: CommitBid");
+                Self::process_commit_bid(accounts, commitment, collateral, program_id)
+            }
+            EscrowInstruction::RevealBid { bid, salt } => {
+                msg!("Instruction: RevealBid");
+                Self::process_reveal_bid(accounts, bid, salt, program_id)
+            }
+            EscrowInstruction::SettleSealedAuction => {
+                msg!("Instruction: SettleSealedAuction");
+                Self::process_settle_sealed_auction(accounts, program_id)
+            }
+            EscrowInstruction::WithdrawCollateral => {
+                msg!("Instruction: WithdrawCollateral");
+                Self::process_withdraw_collateral(accounts, program_id)
+            }
+            EscrowInstruction::InitMilestoneEscrow { milestones, deadline, condition } => {
+                msg!("Instruction: InitMilestoneEscrow");
+                Self::process_init_milestone_escrow(accounts, milestones, deadline, condition, program_id)
+            }
+            EscrowInstruction::ApproveMilestone => {
+                msg!("Instruction: ApproveMilestone");
+                Self::process_approve_milestone(accounts, program_id)
+            }
+            EscrowInstruction::RefundMilestones => {
+                msg!("Instruction: RefundMilestones");
+                Self::process_refund_milestones(accounts, program_id)
+            }
+            EscrowInstruction::InitStream { rate, start, end } => {
+                msg!("Instruction: InitStream");
+                Self::process_init_stream(accounts, rate, start, end, program_id)
+            }
+            EscrowInstruction::CancelStream => {
+                msg!("Instruction: CancelStream");
+                Self::process_cancel_stream(accounts, program_id)
+            }
+            EscrowInstruction::InitLoan { amount_a, amount_b, interest, deadline } => {
+                msg!("Instruction: InitLoan");
+                Self::process_init_loan(accounts, amount_a, amount_b, interest, deadline, program_id)
+            }
+            EscrowInstruction::AcceptLoan => {
+                msg!("Instruction: AcceptLoan");
+                Self::process_accept_loan(accounts, program_id)
+            }
+            EscrowInstruction::RepayLoan => {
+                msg!("Instruction: RepayLoan");
+                Self::process_repay_loan(accounts, program_id)
+            }
+            EscrowInstruction::ClaimCollateral => {
+                msg!("Instruction: ClaimCollateral");
+                Self::process_claim_collateral(accounts, program_id)
+            }
+            EscrowInstruction::CancelLoan => {
+                msg!("Instruction: CancelLoan");
+                Self::process_cancel_loan(accounts, program_id)
+            }
+            EscrowInstruction::WriteOption { amount, premium, strike, expiry } => {
+                msg!("Instruction: WriteOption");
+                Self::process_write_option(accounts, amount, premium, strike, expiry, program_id)
+            }
+            EscrowInstruction::BuyOption => {
+                msg!("Instruction: BuyOption");
+                Self::process_buy_option(accounts, program_id)
+            }
+            EscrowInstruction::TransferOption { new_owner } => {
+                msg!("Instruction: TransferOption");
+                Self::process_transfer_option(accounts, new_owner, program_id)
+            }
+            EscrowInstruction::ExerciseOption => {
+                msg!("Instruction: ExerciseOption");
+                Self::process_exercise_option(accounts, program_id)
+            }
+            EscrowInstruction::ReclaimOption => {
+                msg!("Instruction: ReclaimOption");
+                Self::process_reclaim_option(accounts, program_id)
+            }
         }
     }
 
@@ -48,67 +227,171 @@ impl Processor {
         accounts: &[AccountInfo],
         amount_a: u64,
         amount_b: u64,
+        terms: &EscrowTerms,
+        open: bool,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         // alice
         let alice = next_account_info(account_info_iter)?;
 
-        // make alice always the payer for rent
+        // alice agrees to the escrow, and pays rent unless a rent payer is passed
         if !alice.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
-        // bob
+        // bob, not read when the escrow is open to any taker
         let bob = next_account_info(account_info_iter)?;
         // mints
         let x_mint = next_account_info(account_info_iter)?;
         let y_mint = next_account_info(account_info_iter)?;
-        // vaults
-        let x_vault = next_account_info(account_info_iter)?;
-        let y_vault = next_account_info(account_info_iter)?;
+        // vaults, created by create_pda_vault
+        let _x_vault = next_account_info(account_info_iter)?;
+        let _y_vault = next_account_info(account_info_iter)?;
         // escrow 
         let escrow = next_account_info(account_info_iter)?;
 
         // token program
         let token_program = next_account_info(account_info_iter)?;
-        // system program
-        let system_program = next_account_info(account_info_iter)?;
-        // rent program
-        let rent_program = next_account_info(account_info_iter)?;
+        // system program and rent sysvar, passed on to the account creation CPIs
+        let _system_program = next_account_info(account_info_iter)?;
+        let _rent_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // mint rules
+        let x_mint_rule = next_account_info(account_info_iter)?;
+        let y_mint_rule = next_account_info(account_info_iter)?;
+        // rent payer, optional
+        let rent_payer = next_account_info(account_info_iter).unwrap_or(alice);
+
+        if !rent_payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let (bob_key, bob_seed) = if open { (None, OPEN_ESCROW_SEED) } else { (Some(bob.key), bob.key.as_ref()) };
+        Self::check_trade(
+            alice.key, bob_key, x_mint, y_mint, token_program, config, x_mint_rule, y_mint_rule, amount_a, amount_b,
+            program_id,
+        )?;
+        Self::check_terms(terms)?;
 
         let x_seed = x_mint.key.as_ref(); 
         let y_seed = y_mint.key.as_ref();
 
         if escrow.data_len() > 0 {
-            let mut escrow_data = Escrow::try_from_slice(&escrow.data.borrow_mut())?;
-            msg!("{}", escrow_data.state);
-            return Ok(());
+            return Err(EscrowError::AlreadyInitialized.into());
         }
 
+        // vault bumps are searched for once here and stored in the escrow
+        let (_, bump_vault_x) = find_vault_address_with_seed(program_id, x_mint.key, alice.key, bob_seed);
+        let (_, bump_vault_y) = find_vault_address_with_seed(program_id, y_mint.key, alice.key, bob_seed);
+
         // create x_vault
-        Self::create_pda_vault(accounts, program_id, x_seed);
+        Self::create_pda_vault(accounts, rent_payer, x_seed, bob_seed, bump_vault_x)?;
 
         // create y_vault
-        Self::create_pda_vault(accounts, program_id, y_seed);
+        Self::create_pda_vault(accounts, rent_payer, y_seed, bob_seed, bump_vault_y)?;
 
         // create escrow
-        Self::create_pda_escrow(accounts, program_id, amount_a, amount_b);
+        Self::create_pda_escrow(
+            accounts, rent_payer, program_id, amount_a, amount_b, terms, open, bump_vault_x, bump_vault_y,
+        )?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::EscrowCreated,
+            escrow: *escrow.key,
+            party: *alice.key,
+            mint: *x_mint.key,
+            amount: amount_a,
+            old_state: 0,
+            new_state: 0,
+        }.emit();
+
+        Self::return_status(Escrow::load(&escrow.data.borrow())?)?;
         
         Ok(())
     }
 
-    fn create_pda_vault(
-        accounts: &[AccountInfo],
+    /// Checks the terms of trading `amount_a` of `x_mint` from `alice` against `amount_b` of
+    /// `y_mint` from `bob`, or from any taker without one, shared by the escrow and the atomic
+    /// swap.
+    #[allow(clippy::too_many_arguments)]
+    fn check_trade(
+        alice: &Pubkey,
+        bob: Option<&Pubkey>,
+        x_mint: &AccountInfo,
+        y_mint: &AccountInfo,
+        token_program: &AccountInfo,
+        config: &AccountInfo,
+        x_mint_rule: &AccountInfo,
+        y_mint_rule: &AccountInfo,
+        amount_a: u64,
+        amount_b: u64,
         program_id: &Pubkey,
+    ) -> ProgramResult {
+        if amount_a == 0 || amount_b == 0 {
+            return Err(EscrowError::ZeroEscrowSize.into());
+        }
+        if bob == Some(alice) {
+            return Err(EscrowError::IdenticalParties.into());
+        }
+        if x_mint.key == y_mint.key {
+            return Err(EscrowError::IdenticalMints.into());
+        }
+        if *token_program.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_mint(x_mint)?;
+        Self::check_mint(y_mint)?;
+
+        let config_data = Self::load_config_or_default(config, program_id)?;
+        if config_data.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        Self::check_mint_rule(x_mint_rule, x_mint.key, amount_a, &config_data, program_id)?;
+        Self::check_mint_rule(y_mint_rule, y_mint.key, amount_b, &config_data, program_id)
+    }
+
+    /// Checks that the vesting schedules of `terms` are ordered and that its condition is valid.
+    fn check_terms(terms: &EscrowTerms) -> ProgramResult {
+        if !terms.vesting_a.is_valid() || !terms.vesting_b.is_valid() {
+            return Err(EscrowError::InvalidVestingSchedule.into());
+        }
+        Self::check_new_condition(&terms.condition)
+    }
+
+    /// Checks that `condition`, unless it is the default, names an account and compares in a way
+    /// its value type supports.
+    fn check_new_condition(condition: &Condition) -> ProgramResult {
+        if !condition.is_none() && (condition.account == Pubkey::default() || !condition.is_valid()) {
+            return Err(EscrowError::InvalidCondition.into());
+        }
+        Ok(())
+    }
+
+    /// Checks that `mint` is an initialized spl-token mint.
+    fn check_mint(mint: &AccountInfo) -> ProgramResult {
+        if *mint.owner != spl_token::id() || mint.data_len() != Mint::LEN {
+            return Err(EscrowError::InvalidMint.into());
+        }
+        if !Mint::unpack_unchecked(&mint.data.borrow())?.is_initialized() {
+            return Err(EscrowError::UninitializedMint.into());
+        }
+        Ok(())
+    }
+
+    fn create_pda_vault<'a>(
+        accounts: &[AccountInfo<'a>],
+        payer: &AccountInfo<'a>,
         vault_seed: &[u8],
+        bob_seed: &[u8],
+        bump: u8,
     ) -> ProgramResult {
         // get accounts
         let account_info_iter = &mut accounts.iter();
         // alice
         let alice = next_account_info(account_info_iter)?;
-        // bob
-        let bob = next_account_info(account_info_iter)?;
+        // bob, passed in as bob_seed
+        let _ = next_account_info(account_info_iter)?;
         // mints
         let x_mint = next_account_info(account_info_iter)?;
         let y_mint = next_account_info(account_info_iter)?;
@@ -134,28 +417,29 @@ impl Processor {
 
         // seeds
         let alice_seed = alice.key.as_ref();
-        let bob_seed = bob.key.as_ref();
-        let (_, bump) = Pubkey::find_program_address(&[vault_seed, alice_seed, bob_seed], program_id);
         let seeds_with_bump = &[vault_seed, alice_seed, bob_seed, &[bump]];
 
+        Self::create_token_vault(
+            payer, vault, mint, escrow, token_program, system_program, rent_program, seeds_with_bump,
+        )
+    }
+
+    /// Creates `vault` at the PDA of `seeds` as a token account of `mint` owned by `authority`.
+    #[allow(clippy::too_many_arguments)]
+    fn create_token_vault<'a>(
+        payer: &AccountInfo<'a>,
+        vault: &AccountInfo<'a>,
+        mint: &AccountInfo<'a>,
+        authority: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        rent_program: &AccountInfo<'a>,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
         // rent and space
         let space = Account::LEN;
         let rent = &Rent::from_account_info(rent_program)?;
-        let required_lamports = rent
-            .minimum_balance(space)
-            .max(1)
-            .saturating_sub(vault.lamports());
-        invoke_signed(
-            &system_instruction::create_account(
-                alice.key, //from_pubkey
-                vault.key, //to_pubkey
-                required_lamports, //lamports
-                space.try_into().unwrap(), //space
-                token_program.key, // owner
-            ),
-            &[alice.clone(), vault.clone(), system_program.clone()],
-            &[seeds_with_bump],
-        )?;
+        Self::create_pda_account(payer, vault, system_program, rent, space, token_program.key, seeds)?;
         msg!("Done with creating account");
 
         // initialize account from token
@@ -164,9 +448,9 @@ impl Processor {
                 token_program.key,
                 vault.key,
                 mint.key,
-                escrow.key,
+                authority.key,
             )?,
-            &[vault.clone(), mint.clone(), escrow.clone(), rent_program.clone(), token_program.clone()],
+            &[vault.clone(), mint.clone(), authority.clone(), rent_program.clone(), token_program.clone()],
         )?;
         msg!("Done with initializing");
 
@@ -174,28 +458,34 @@ impl Processor {
         let mut vault_data = Account::unpack_from_slice(&vault.data.borrow_mut())?;
         vault_data.mint = *mint.key;
         vault_data.amount = 0;
-        vault_data.owner = *escrow.key;
+        vault_data.owner = *authority.key;
         msg!("Done writing data");
         vault_data.pack_into_slice(&mut vault.data.borrow_mut());
         
         Ok(())
     }
 
-    fn create_pda_escrow(
-        accounts: &[AccountInfo],
+    #[allow(clippy::too_many_arguments)]
+    fn create_pda_escrow<'a>(
+        accounts: &[AccountInfo<'a>],
+        payer: &AccountInfo<'a>,
         program_id: &Pubkey,
         amount_a: u64,
         amount_b: u64,
+        terms: &EscrowTerms,
+        open: bool,
+        bump_vault_x: u8,
+        bump_vault_y: u8,
     ) -> ProgramResult {
         // get accounts
         let account_info_iter = &mut accounts.iter();
         // alice
         let alice = next_account_info(account_info_iter)?;
-        // bob
+        // bob, recorded by the first taker's deposit when the escrow is open
         let bob = next_account_info(account_info_iter)?;
         // mints
-        let x_mint = next_account_info(account_info_iter)?;
-        let y_mint = next_account_info(account_info_iter)?;
+        let _ = next_account_info(account_info_iter)?;
+        let _ = next_account_info(account_info_iter)?;
         // vaults
         let x_vault = next_account_info(account_info_iter)?;
         let y_vault = next_account_info(account_info_iter)?;
@@ -211,54 +501,98 @@ impl Processor {
 
         // seeds
         let alice_seed = alice.key.as_ref();
-        let bob_seed = bob.key.as_ref();
+        let (party_b, bob_seed) = if open {
+            (Pubkey::default(), OPEN_ESCROW_SEED)
+        } else {
+            (*bob.key, bob.key.as_ref())
+        };
         let x_seed= x_vault.key.as_ref();
         let y_seed = y_vault.key.as_ref();
-        let (_, bump) = Pubkey::find_program_address(&[x_seed, y_seed, alice_seed, bob_seed], program_id);
+        let (_, bump) = find_escrow_address_with_seed(program_id, x_vault.key, y_vault.key, alice.key, bob_seed);
         let seeds_with_bump = &[x_seed, y_seed, alice_seed, bob_seed, &[bump]];
 
         // rent and space
         let space = Escrow::LEN;
         let rent = &Rent::from_account_info(rent_program)?;
+        Self::create_pda_account(payer, escrow, system_program, rent, space, program_id, seeds_with_bump)?;
+
+        // write data to struct
+        let escrow_data = Escrow {
+            discriminator: Escrow::DISCRIMINATOR,
+            version: Escrow::VERSION,
+            party_a: *alice.key,
+            party_b,
+            size_a: amount_a.into(),
+            size_b: amount_b.into(),
+            vault_x: *x_vault.key,
+            vault_y: *y_vault.key,
+            state: 0,
+            bump,
+            bump_vault_x,
+            bump_vault_y,
+            rent_payer: *payer.key,
+            vesting_a: terms.vesting_a,
+            vesting_b: terms.vesting_b,
+            claimed_a: 0.into(),
+            claimed_b: 0.into(),
+            condition: terms.condition,
+            open_b: u8::from(open),
+        };
+        escrow.data.borrow_mut().copy_from_slice(bytemuck::bytes_of(&escrow_data));
+
+        Ok(())
+    }
+
+
+    /// Creates the PDA `account` with `space` bytes owned by `owner`, paid for by `payer`.
+    /// `create_account` fails once anyone has sent lamports to the address, so a funded
+    /// address is topped up to rent exemption, allocated and assigned instead.
+    fn create_pda_account<'a>(
+        payer: &AccountInfo<'a>,
+        account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        rent: &Rent,
+        space: usize,
+        owner: &Pubkey,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
         let required_lamports = rent
             .minimum_balance(space)
             .max(1)
-            .saturating_sub(escrow.lamports());
-        invoke_signed(
-            &system_instruction::create_account(
-                alice.key, //from_pubkey
-                escrow.key, //to_pubkey
-                required_lamports, //lamports
-                space.try_into().unwrap(), //space
-                program_id, // owner
-            ),
-            &[alice.clone(), escrow.clone(), system_program.clone()],
-            &[seeds_with_bump],
-        )?;
-
-        // get bump, write data to struct
-        let x_mint_seed = x_mint.key.as_ref();
-        let y_mint_seed = y_mint.key.as_ref();
-        let (_, bump_vault_x) = Pubkey::find_program_address(&[x_mint_seed, alice_seed, bob_seed], program_id);
-        let (_, bump_vault_y) = Pubkey::find_program_address(&[y_mint_seed, alice_seed, bob_seed], program_id);
-
-        let mut escrow_data = Escrow::try_from_slice(&escrow.data.borrow_mut())?;
-        escrow_data.party_a = *alice.key;
-        escrow_data.party_b = *bob.key;
-        escrow_data.size_a = amount_a;
-        escrow_data.size_b = amount_b;
-        escrow_data.vault_x = *x_vault.key;
-        escrow_data.vault_y = *y_vault.key;
-        escrow_data.state = 0;
-        escrow_data.bump = bump;
-        escrow_data.bump_vault_x = bump_vault_x;
-        escrow_data.bump_vault_y = bump_vault_y;
-        escrow_data.serialize(&mut *escrow.data.borrow_mut())?;
+            .saturating_sub(account.lamports());
+
+        if account.lamports() == 0 {
+            return invoke_signed(
+                &system_instruction::create_account(
+                    payer.key, //from_pubkey
+                    account.key, //to_pubkey
+                    required_lamports, //lamports
+                    space.try_into().unwrap(), //space
+                    owner, // owner
+                ),
+                &[payer.clone(), account.clone(), system_program.clone()],
+                &[seeds],
+            );
+        }
 
-        Ok(())
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, required_lamports),
+                &[payer.clone(), account.clone(), system_program.clone()],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(account.key, space.try_into().unwrap()),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account.key, owner),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )
     }
 
-
     fn deposit(
         accounts: &[AccountInfo],
         amount: u64,
@@ -277,17 +611,25 @@ impl Processor {
         // ata
         let ata = next_account_info(account_info_iter)?;
         // associated_token_program
-        let atp = next_account_info(account_info_iter)?;
-        let associated_token_program_key = atp.key;
+        let _ = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // dutch auction, only for bob's deposit into an unpriced Dutch auction escrow
+        let dutch_auction = next_account_info(account_info_iter).ok();
+
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
 
         // seeds
         msg!("Getting escrow data");
-        let mut escrow_data = Escrow::try_from_slice(&escrow.data.borrow_mut())?;
-        let alice_seed = escrow_data.party_a.as_ref();
-        let bob_seed = escrow_data.party_b.as_ref();
-        let mut bump_vault = escrow_data.bump_vault_x;
-        if *vault.key == escrow_data.vault_y {
-            bump_vault = escrow_data.bump_vault_y;
+        if escrow.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // copied out so that the account data is not borrowed across the transfer
+        let escrow_data = *Escrow::load(&escrow.data.borrow())?;
+        if escrow_data.is_loan() {
+            return Err(EscrowError::LoanInProgress.into());
         }
 
         // get mint
@@ -296,10 +638,17 @@ impl Processor {
 
         // val checks
         // #1 is dep Alice or Bob: do in signing
-        // #2 is vault the vault of dep: this can be taken care of via associated_token_program
+        // #2 is vault the vault of dep: checked against the vaults stored in the escrow
         // #3 amount exactly equal to amount_A or amount_B: check manually
+        // in a Dutch auction the first taker becomes bob, his amount is the most he pays, and
+        // the price becomes amount_B
+        let mut amount = amount;
+        let priced_now = escrow_data.open_b != 0 && u64::from(escrow_data.size_b) == 0;
         if *depositor.key == escrow_data.party_a {
-            if amount != escrow_data.size_a {
+            if *vault.key != escrow_data.vault_x {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if amount != u64::from(escrow_data.size_a) {
                 return Err(EscrowError::ExpectedAmountMismatch.into());
             }
 
@@ -307,8 +656,20 @@ impl Processor {
                 return Err(EscrowError::AlreadyDeposited.into());
             }
         }
-        else if *depositor.key == escrow_data.party_b {
-            if amount != escrow_data.size_b {
+        else if *depositor.key == escrow_data.party_b || priced_now {
+            if *vault.key != escrow_data.vault_y {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if priced_now {
+                let dutch_auction = dutch_auction.ok_or(EscrowError::InvalidDutchAuction)?;
+                let price = Self::load_dutch_auction(dutch_auction, escrow.key, program_id)?
+                    .price_at(Clock::get()?.unix_timestamp);
+                if amount < price {
+                    return Err(EscrowError::PriceAboveLimit.into());
+                }
+                amount = price;
+            }
+            else if amount != u64::from(escrow_data.size_b) {
                 return Err(EscrowError::ExpectedAmountMismatch.into());
             }
 
@@ -318,38 +679,6 @@ impl Processor {
         }
         else {return Err(EscrowError::InvalidParty.into());}
 
-        // let (ata, bump_ata) = spl_associated_token_account::get_associated_token_address_and_bump_seed(
-        //                 depositor.key,
-        //                 mintkey,
-        //                 program_id,
-        //                 token_program.key,
-        //             );
-        let (ata_pda, bump_ata) = Pubkey::find_program_address(&[depositor.key.as_ref(), token_program.key.as_ref(), mintkey.as_ref()], associated_token_program_key);
-        let seeds_with_bump_ata = &[depositor.key.as_ref(), token_program.key.as_ref(), mintkey.as_ref(), &[bump_ata]];
-
-
-        msg!("ATA KEY AND BUMP BELOW");
-        //msg!("{}",ata.key);
-        // msg!("{}",bump_ata);
-        //msg!("{}",ata_pda);
-
-        // let (_, bump_for_vault) = Pubkey::find_program_address(&[mintkey.as_ref(), alice_seed, bob_seed], program_id);
-        // let seeds_with_bump_vault = &[mintkey.as_ref(), alice_seed, bob_seed, &[bump_vault]];
-
-        //msg!("{}", depositor.key);
-        // invoke_signed(
-        //     &transfer(
-        //         token_program.key, 
-        //         ata.key,
-        //         vault.key,
-        //         depositor.key,
-        //         &[],
-        //         amount,
-        //     )?,
-        //     &[ata.clone(), vault.clone(), depositor.clone(), token_program.clone()],
-        //     &[seeds_with_bump_ata],
-        // )?;
-
         invoke(
             &transfer(
                 token_program.key, 
@@ -364,6 +693,9 @@ impl Processor {
 
         msg!("Done with invoke");
 
+        let old_state = escrow_data.state;
+        let mut data = escrow.data.borrow_mut();
+        let escrow_data = Escrow::load_mut(&mut data)?;
         if *depositor.key == escrow_data.party_a {
             if escrow_data.state == 0 {
                 escrow_data.state = 1;
@@ -374,17 +706,34 @@ impl Processor {
             msg!("Alice deposit");
         }
 
-        else if *depositor.key == escrow_data.party_b {
+        else {
             if escrow_data.state == 0 {
                 escrow_data.state = 2;
             }
             else if escrow_data.state == 1 {
                 escrow_data.state = 3;
             }
+            if priced_now {
+                escrow_data.party_b = *depositor.key;
+                escrow_data.size_b = amount.into();
+                // the auction is over, its rent is returned with the escrow's
+                if let Some(dutch_auction) = dutch_auction {
+                    Self::close_program_account(dutch_auction, escrow)?;
+                }
+            }
             msg!("Bob deposit");
         }
 
-        escrow_data.serialize(&mut *escrow.data.borrow_mut())?;
+        EscrowEvent {
+            kind: EscrowEventKind::Deposited,
+            escrow: *escrow.key,
+            party: *depositor.key,
+            mint: *mintkey,
+            amount,
+            old_state,
+            new_state: escrow_data.state,
+        }.emit();
+        Self::return_status(escrow_data)?;
 
         Ok(())
     }
@@ -408,12 +757,28 @@ impl Processor {
         // ata
         let ata = next_account_info(account_info_iter)?;
         // associated_token_program
-        let atp = next_account_info(account_info_iter)?;
-        let associated_token_program_key = atp.key;
+        let _ = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // condition account, only for settling a conditional escrow
+        let condition_account = next_account_info(account_info_iter).ok();
 
+        if !withdrawer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
 
         // seeds
-        let mut escrow_data = Escrow::try_from_slice(&escrow.data.borrow_mut())?;
+        if escrow.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if escrow.data.borrow().starts_with(&Stream::DISCRIMINATOR) {
+            return Self::withdraw_stream(withdrawer, vault, escrow, token_program, ata, config, amount, program_id);
+        }
+        // copied out, the escrow account signs the transfer below
+        let escrow_data = *Escrow::load(&escrow.data.borrow())?;
+        if escrow_data.is_loan() {
+            return Err(EscrowError::LoanInProgress.into());
+        }
 
         // get mint
         let vault_data = Account::unpack_from_slice(&vault.data.borrow_mut())?;
@@ -421,12 +786,10 @@ impl Processor {
 
         // val checks
         // #1 is withdrawer Alice or Bob: do in signing
-        // #2 is vault the vault of withdrawer: this can be taken care of via associated_token_program
+        // #2 is vault one of the escrow's vaults: checked against the vaults stored in the escrow
         // #3 amount exactly equal to amount_A or amount_B: check manually
-        if *withdrawer.key != escrow_data.party_a {
-            if *withdrawer.key != escrow_data.party_b {
-                return Err(EscrowError::InvalidParty.into());
-            }
+        if *withdrawer.key != escrow_data.party_a && *withdrawer.key != escrow_data.party_b {
+            return Err(EscrowError::InvalidParty.into());
         }
 
         // if empty escrow
@@ -434,66 +797,78 @@ impl Processor {
             return Err(EscrowError::EmptyEscrow.into());
         }
 
-        // check valid amounts of withdraws
-        if *vault.key == escrow_data.vault_y && amount != escrow_data.size_b {
-            return Err(EscrowError::ExpectedAmountMismatch.into());
+        if *vault.key != escrow_data.vault_x && *vault.key != escrow_data.vault_y {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // check valid amounts of withdraws, a vesting leg is released in parts once both
+        // parties have deposited and everything else moves whole
+        let (size, vesting, claimed) = if *vault.key == escrow_data.vault_y {
+            (u64::from(escrow_data.size_b), escrow_data.vesting_b, u64::from(escrow_data.claimed_b))
         }
-        else if *vault.key == escrow_data.vault_x && amount != escrow_data.size_a {
+        else {
+            (u64::from(escrow_data.size_a), escrow_data.vesting_a, u64::from(escrow_data.claimed_a))
+        };
+        let is_settlement = escrow_data.state >= 3;
+        let vests = is_settlement && !vesting.is_immediate();
+        if !vests && amount != size {
             return Err(EscrowError::ExpectedAmountMismatch.into());
         }
 
         // if party has not deposited
-        if escrow_data.state == 2 && *withdrawer.key == escrow_data.party_a {
-            return Err(EscrowError::OwnEscrowDepositIncomplete.into());
-        }
-        else if escrow_data.state == 1 && *withdrawer.key == escrow_data.party_b {
+        if (escrow_data.state == 2 && *withdrawer.key == escrow_data.party_a)
+            || (escrow_data.state == 1 && *withdrawer.key == escrow_data.party_b)
+        {
             return Err(EscrowError::OwnEscrowDepositIncomplete.into());
         }
 
         // if counterparty hasn't deposited
-        if escrow_data.state == 1 && *withdrawer.key == escrow_data.party_a && *vault.key == escrow_data.vault_y {
-            return Err(EscrowError::CounterpartyEscrowDepositIncomplete.into());
-        }
-        else if escrow_data.state == 2 && *withdrawer.key == escrow_data.party_b && *vault.key == escrow_data.vault_x {
+        if (escrow_data.state == 1 && *withdrawer.key == escrow_data.party_a && *vault.key == escrow_data.vault_y)
+            || (escrow_data.state == 2 && *withdrawer.key == escrow_data.party_b && *vault.key == escrow_data.vault_x)
+        {
             return Err(EscrowError::CounterpartyEscrowDepositIncomplete.into());
         }
 
         // if both have deposited
-        if escrow_data.state == 3 && *withdrawer.key == escrow_data.party_a && *vault.key == escrow_data.vault_x {
-            return Err(EscrowError::EscrowLocked.into());
-        }
-        else if escrow_data.state == 3 && *withdrawer.key == escrow_data.party_b && *vault.key == escrow_data.vault_y {
+        if (escrow_data.state == 3 && *withdrawer.key == escrow_data.party_a && *vault.key == escrow_data.vault_x)
+            || (escrow_data.state == 3 && *withdrawer.key == escrow_data.party_b && *vault.key == escrow_data.vault_y)
+        {
             return Err(EscrowError::EscrowLocked.into());
         }
 
         // if have already withdrawn after lock
-        if escrow_data.state == 4 && *withdrawer.key == escrow_data.party_a {
-            return Err(EscrowError::AlreadyWithdrawn.into());
-        }
-        else if escrow_data.state == 5 && *withdrawer.key == escrow_data.party_b {
+        if (escrow_data.state == 4 && *withdrawer.key == escrow_data.party_a)
+            || (escrow_data.state == 5 && *withdrawer.key == escrow_data.party_b)
+        {
             return Err(EscrowError::AlreadyWithdrawn.into());
         }
 
-        //let (_, bump_ata) = Pubkey::find_program_address(&[withdrawer.key.as_ref(), token_program.key.as_ref(), mintkey.as_ref()], associated_token_program_key);
-        //let seeds_with_bump_ata = &[withdrawer.key.as_ref(), token_program.key.as_ref(), mintkey.as_ref(), &[bump_ata]];
-
-
-        //msg!("ATA KEY AND BUMP BELOW");
-        //msg!("{}",ata.key);
-        //msg!("{}",bump_ata);
+        // only refunds of an unmatched deposit are allowed while paused
+        let is_refund = (escrow_data.state == 1 && *withdrawer.key == escrow_data.party_a && *vault.key == escrow_data.vault_x)
+            || (escrow_data.state == 2 && *withdrawer.key == escrow_data.party_b && *vault.key == escrow_data.vault_y);
+        if !is_refund && Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        if is_settlement {
+            Self::check_condition(&escrow_data.condition, condition_account)?;
+        }
+        if vests {
+            let vested = vesting.vested(size, Clock::get()?.unix_timestamp);
+            if amount == 0 || amount > vested.saturating_sub(claimed) {
+                return Err(EscrowError::AmountNotVested.into());
+            }
+        }
+        let claimed = if is_settlement { claimed + amount } else { 0 };
 
         let x_seed = escrow_data.vault_x.as_ref();
         let y_seed = escrow_data.vault_y.as_ref();
         let alice_seed = escrow_data.party_a.as_ref();
-        let bob_seed = escrow_data.party_b.as_ref();
+        let bob_seed = escrow_data.seed_b();
         let bump_escrow = escrow_data.bump;
 
         // get seeds for escrow
         let seeds_with_bump_escrow = &[x_seed, y_seed, alice_seed, bob_seed, &[bump_escrow]];
 
-        //let (_, bump_for_vault) = Pubkey::find_program_address(&[mintkey.as_ref(), alice_seed, bob_seed], program_id);
-        //let seeds_with_bump_vault = &[mintkey.as_ref(), alice_seed, bob_seed, &[bump_for_vault]];
-
         invoke_signed(
             &transfer(
                 token_program.key, 
@@ -508,34 +883,3177 @@ impl Processor {
         )?;
 
         // state transitions
-        if *withdrawer.key == escrow_data.party_a {
-            if escrow_data.state == 1 {
-                escrow_data.state = 0;
-            }
-            else if escrow_data.state == 3 {
-                escrow_data.state = 4;
-            }
-            else if escrow_data.state == 5 {
-                escrow_data.state = 0;
-            }
+        let old_state = escrow_data.state;
+        let mut data = escrow.data.borrow_mut();
+        let escrow_data = Escrow::load_mut(&mut data)?;
+        if *vault.key == escrow_data.vault_y {
+            escrow_data.claimed_b = claimed.into();
         }
-        else if *withdrawer.key == escrow_data.party_b {
-            if escrow_data.state == 2 {
-                escrow_data.state = 0;
-            }
-            else if escrow_data.state == 3 {
-                escrow_data.state = 5;
+        else {
+            escrow_data.claimed_a = claimed.into();
+        }
+        // a vesting leg only counts as withdrawn once all of it is claimed
+        if !is_settlement || claimed == size {
+            if *withdrawer.key == escrow_data.party_a {
+                if escrow_data.state == 1 {
+                    escrow_data.state = 0;
+                }
+                else if escrow_data.state == 3 {
+                    escrow_data.state = 4;
+                }
+                else if escrow_data.state == 5 {
+                    escrow_data.state = 0;
+                }
             }
-            else if escrow_data.state == 4 {
-                escrow_data.state = 0;
+            else if *withdrawer.key == escrow_data.party_b {
+                if escrow_data.state == 2 {
+                    escrow_data.state = 0;
+                }
+                else if escrow_data.state == 3 {
+                    escrow_data.state = 5;
+                }
+                else if escrow_data.state == 4 {
+                    escrow_data.state = 0;
+                }
             }
         }
+        // a fully settled escrow can be traded again
+        if escrow_data.state == 0 {
+            escrow_data.claimed_a = 0.into();
+            escrow_data.claimed_b = 0.into();
+        }
 
-        escrow_data.serialize(&mut *escrow.data.borrow_mut())?;
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Withdrawn,
+            escrow: *escrow.key,
+            party: *withdrawer.key,
+            mint: *mintkey,
+            amount,
+            old_state,
+            new_state: escrow_data.state,
+        };
+        event.emit();
+        if is_refund {
+            event.kind = EscrowEventKind::Cancelled;
+            event.emit();
+        }
+        else if old_state >= 4 && escrow_data.state == 0 {
+            event.kind = EscrowEventKind::Settled;
+            event.emit();
+        }
+        Self::return_status(escrow_data)?;
 
         Ok(())
 
     }
-}
 
+    fn process_init_config(
+        accounts: &[AccountInfo],
+        guardian: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // admin, also pays rent
+        let admin = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (config_key, bump) = Pubkey::find_program_address(&[CONFIG_SEED], program_id);
+        if config_key != *config.key {
+            return Err(EscrowError::InvalidConfig.into());
+        }
+        if config.data_len() > 0 {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        // rent and space
+        let space = Config::LEN;
+        let rent = &Rent::from_account_info(rent_program)?;
+        Self::create_pda_account(admin, config, system_program, rent, space, program_id, &[CONFIG_SEED, &[bump]])?;
+
+        let config_data = Config {
+            discriminator: Config::DISCRIMINATOR,
+            admin: *admin.key,
+            guardian,
+            paused: false,
+            allowlist_only: false,
+            bump,
+        };
+        config_data.serialize(&mut &mut config.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    fn process_set_paused(
+        accounts: &[AccountInfo],
+        paused: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // admin or guardian
+        let authority = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+
+        if !authority.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let mut config_data = Self::load_config(config, program_id)?;
+        if *authority.key != config_data.admin && *authority.key != config_data.guardian {
+            return Err(EscrowError::Unauthorized.into());
+        }
+
+        config_data.paused = paused;
+        config_data.serialize(&mut &mut config.data.borrow_mut()[..])?;
+        msg!("Paused: {}", paused);
+
+        Ok(())
+    }
+
+    fn process_emergency_unwind(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // guardian
+        let guardian = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+        // vaults
+        let x_vault = next_account_info(account_info_iter)?;
+        let y_vault = next_account_info(account_info_iter)?;
+        // refund destinations: alice's x account and bob's y account
+        let alice_x = next_account_info(account_info_iter)?;
+        let bob_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+
+        if !guardian.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let config_data = Self::load_config(config, program_id)?;
+        if *guardian.key != config_data.guardian {
+            return Err(EscrowError::Unauthorized.into());
+        }
+        if !config_data.paused {
+            return Err(EscrowError::NotPaused.into());
+        }
+
+        if escrow.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let escrow_data = *Escrow::load(&escrow.data.borrow())?;
+        if *x_vault.key != escrow_data.vault_x || *y_vault.key != escrow_data.vault_y {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // loans are unwound by their own instructions, the borrower can always cancel or repay
+        if escrow_data.is_loan() {
+            return Err(EscrowError::LoanInProgress.into());
+        }
+        if escrow_data.state == 4
+            || escrow_data.state == 5
+            || u64::from(escrow_data.claimed_a) != 0
+            || u64::from(escrow_data.claimed_b) != 0
+        {
+            return Err(EscrowError::PartiallySettled.into());
+        }
+
+        let x_seed = escrow_data.vault_x.as_ref();
+        let y_seed = escrow_data.vault_y.as_ref();
+        let alice_seed = escrow_data.party_a.as_ref();
+        let bob_seed = escrow_data.seed_b();
+        let seeds_with_bump_escrow: &[&[u8]] = &[x_seed, y_seed, alice_seed, bob_seed, &[escrow_data.bump]];
+
+        let old_state = escrow_data.state;
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Cancelled,
+            escrow: *escrow.key,
+            party: escrow_data.party_a,
+            mint: Pubkey::default(),
+            amount: 0,
+            old_state,
+            new_state: 0,
+        };
+
+        // alice's x deposit goes back to alice
+        if escrow_data.state == 1 || escrow_data.state == 3 {
+            Self::refund_vault(
+                x_vault,
+                alice_x,
+                &escrow_data.party_a,
+                escrow,
+                token_program,
+                escrow_data.size_a.into(),
+                seeds_with_bump_escrow,
+            )?;
+            msg!("Refunded alice");
+            event.mint = Account::unpack(&x_vault.data.borrow())?.mint;
+            event.amount = escrow_data.size_a.into();
+            event.emit();
+        }
+
+        // bob's y deposit goes back to bob
+        if escrow_data.state == 2 || escrow_data.state == 3 {
+            Self::refund_vault(
+                y_vault,
+                bob_y,
+                &escrow_data.party_b,
+                escrow,
+                token_program,
+                escrow_data.size_b.into(),
+                seeds_with_bump_escrow,
+            )?;
+            msg!("Refunded bob");
+            event.party = escrow_data.party_b;
+            event.mint = Account::unpack(&y_vault.data.borrow())?.mint;
+            event.amount = escrow_data.size_b.into();
+            event.emit();
+        }
+
+        let mut data = escrow.data.borrow_mut();
+        let escrow_data = Escrow::load_mut(&mut data)?;
+        escrow_data.state = 0;
+        Self::return_status(escrow_data)?;
+
+        Ok(())
+    }
+
+    /// Transfers `amount` out of `vault` into `destination`, which must be a token account of the
+    /// vault's mint owned by `owner`. The transfer is signed by the escrow, so `token_program` must
+    /// be the real token program.
+    fn refund_vault<'a>(
+        vault: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        owner: &Pubkey,
+        escrow: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        amount: u64,
+        escrow_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        if *token_program.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let vault_data = Account::unpack(&vault.data.borrow())?;
+        let destination_data = Account::unpack(&destination.data.borrow())?;
+        if destination_data.owner != *owner || destination_data.mint != vault_data.mint {
+            return Err(EscrowError::InvalidParty.into());
+        }
+
+        invoke_signed(
+            &transfer(
+                token_program.key,
+                vault.key,
+                destination.key,
+                escrow.key,
+                &[],
+                amount,
+            )?,
+            &[vault.clone(), destination.clone(), escrow.clone(), token_program.clone()],
+            &[escrow_seeds],
+        )
+    }
+
+    fn load_config(config: &AccountInfo, program_id: &Pubkey) -> Result<Config, ProgramError> {
+        if config.owner != program_id {
+            return Err(EscrowError::InvalidConfig.into());
+        }
+        let config_data = Config::load(&config.data.borrow())?;
+        let config_key = Pubkey::create_program_address(&[CONFIG_SEED, &[config_data.bump]], program_id)?;
+        if config_key != *config.key {
+            return Err(EscrowError::InvalidConfig.into());
+        }
+        Ok(config_data)
+    }
+
+    /// A config PDA that has not been created yet counts as the default, unpaused config.
+    fn load_config_or_default(config: &AccountInfo, program_id: &Pubkey) -> Result<Config, ProgramError> {
+        if config.data_len() == 0 {
+            let (config_key, _) = Pubkey::find_program_address(&[CONFIG_SEED], program_id);
+            if config_key != *config.key {
+                return Err(EscrowError::InvalidConfig.into());
+            }
+            return Ok(Config::default());
+        }
+        Self::load_config(config, program_id)
+    }
+
+    fn load_admin_config(
+        admin: &AccountInfo,
+        config: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Config, ProgramError> {
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let config_data = Self::load_config(config, program_id)?;
+        if *admin.key != config_data.admin {
+            return Err(EscrowError::Unauthorized.into());
+        }
+        Ok(config_data)
+    }
 
+    fn process_set_allowlist_only(
+        accounts: &[AccountInfo],
+        allowlist_only: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // admin
+        let admin = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+
+        let mut config_data = Self::load_admin_config(admin, config, program_id)?;
+        config_data.allowlist_only = allowlist_only;
+        config_data.serialize(&mut &mut config.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    fn process_set_mint_rule(
+        accounts: &[AccountInfo],
+        allowed: bool,
+        min_size: u64,
+        max_size: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // admin, also pays rent
+        let admin = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // mint
+        let mint = next_account_info(account_info_iter)?;
+        // mint rule
+        let mint_rule = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+
+        Self::load_admin_config(admin, config, program_id)?;
+
+        if max_size != 0 && min_size > max_size {
+            return Err(EscrowError::EscrowSizeOutOfRange.into());
+        }
+
+        let (mint_rule_key, bump) = Pubkey::find_program_address(&[MINT_RULE_SEED, mint.key.as_ref()], program_id);
+        if mint_rule_key != *mint_rule.key {
+            return Err(EscrowError::InvalidMintRule.into());
+        }
+
+        if mint_rule.data_len() == 0 {
+            // rent and space
+            let space = MintRule::LEN;
+            let rent = &Rent::from_account_info(rent_program)?;
+            let seeds: &[&[u8]] = &[MINT_RULE_SEED, mint.key.as_ref(), &[bump]];
+            Self::create_pda_account(admin, mint_rule, system_program, rent, space, program_id, seeds)?;
+        }
+        else if mint_rule.owner != program_id {
+            return Err(EscrowError::InvalidMintRule.into());
+        }
+        else {
+            MintRule::load(&mint_rule.data.borrow())?;
+        }
+
+        let mint_rule_data = MintRule {
+            discriminator: MintRule::DISCRIMINATOR,
+            mint: *mint.key,
+            allowed,
+            min_size,
+            max_size,
+            bump,
+        };
+        mint_rule_data.serialize(&mut &mut mint_rule.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    /// Checks `size` of `mint` against its registry entry. A mint without an entry is only
+    /// rejected in allowlist-only mode.
+    fn check_mint_rule(
+        mint_rule: &AccountInfo,
+        mint: &Pubkey,
+        size: u64,
+        config_data: &Config,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if mint_rule.data_len() == 0 {
+            let (mint_rule_key, _) = Pubkey::find_program_address(&[MINT_RULE_SEED, mint.as_ref()], program_id);
+            if mint_rule_key != *mint_rule.key {
+                return Err(EscrowError::InvalidMintRule.into());
+            }
+            if config_data.allowlist_only {
+                return Err(EscrowError::MintNotAllowed.into());
+            }
+            return Ok(());
+        }
+
+        if mint_rule.owner != program_id {
+            return Err(EscrowError::InvalidMintRule.into());
+        }
+        let mint_rule_data = MintRule::load(&mint_rule.data.borrow())?;
+        let mint_rule_key = Pubkey::create_program_address(&[MINT_RULE_SEED, mint.as_ref(), &[mint_rule_data.bump]], program_id)?;
+        if mint_rule_key != *mint_rule.key || mint_rule_data.mint != *mint {
+            return Err(EscrowError::InvalidMintRule.into());
+        }
+
+        if !mint_rule_data.allowed {
+            return Err(EscrowError::MintNotAllowed.into());
+        }
+        if size < mint_rule_data.min_size || (mint_rule_data.max_size != 0 && size > mint_rule_data.max_size) {
+            return Err(EscrowError::EscrowSizeOutOfRange.into());
+        }
+
+        Ok(())
+    }
+
+    fn process_get_escrow_status(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+
+        if escrow.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::return_status(Escrow::load(&escrow.data.borrow())?)
+    }
+
+    fn return_status(escrow_data: &Escrow) -> ProgramResult {
+        set_return_data(&escrow_data.status().try_to_vec()?);
+        Ok(())
+    }
+
+    fn process_migrate(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // payer for the extra rent
+        let payer = next_account_info(account_info_iter)?;
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if escrow.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let escrow_data = match escrow.data_len() {
+            Escrow::LEN => {
+                // already migrated, only make sure it is a valid escrow
+                Escrow::load(&escrow.data.borrow())?;
+                msg!("Escrow is up to date");
+                return Ok(());
+            }
+            EscrowV3::LEN => Escrow::from_v3(*EscrowV3::load(&escrow.data.borrow())?),
+            EscrowV2::LEN => Escrow::from_v2(*EscrowV2::load(&escrow.data.borrow())?),
+            EscrowV1::LEN => Escrow::from_v1(*EscrowV1::load(&escrow.data.borrow())?),
+            EscrowV0::LEN => Escrow::from_v0(EscrowV0::try_from_slice(&escrow.data.borrow())?),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        // top up rent for the larger account
+        let required_lamports = Rent::get()?
+            .minimum_balance(Escrow::LEN)
+            .saturating_sub(escrow.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, escrow.key, required_lamports),
+                &[payer.clone(), escrow.clone(), system_program.clone()],
+            )?;
+        }
+
+        escrow.realloc(Escrow::LEN, false)?;
+        escrow.data.borrow_mut().copy_from_slice(bytemuck::bytes_of(&escrow_data));
+        msg!("Migrated escrow to version {}", Escrow::VERSION);
+
+        Ok(())
+    }
+
+    fn process_close_escrow(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // either party or the rent payer
+        let authority = next_account_info(account_info_iter)?;
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+        // vaults
+        let x_vault = next_account_info(account_info_iter)?;
+        let y_vault = next_account_info(account_info_iter)?;
+        // rent payer, receives the rent
+        let rent_payer = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+
+        if !authority.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if escrow.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let escrow_data = *Escrow::load(&escrow.data.borrow())?;
+        if *authority.key != escrow_data.party_a
+            && *authority.key != escrow_data.party_b
+            && *authority.key != escrow_data.rent_payer
+        {
+            return Err(EscrowError::Unauthorized.into());
+        }
+        if *x_vault.key != escrow_data.vault_x
+            || *y_vault.key != escrow_data.vault_y
+            || *rent_payer.key != escrow_data.rent_payer
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *token_program.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if escrow_data.state != 0 {
+            return Err(EscrowError::EscrowNotEmpty.into());
+        }
+        // an auction bob never deposited into is closed with the escrow
+        let dutch_auction = if u64::from(escrow_data.size_b) == 0 {
+            let dutch_auction = next_account_info(account_info_iter)?;
+            Self::load_dutch_auction(dutch_auction, escrow.key, program_id)?;
+            Some(dutch_auction)
+        }
+        else {
+            None
+        };
+
+        let x_seed = escrow_data.vault_x.as_ref();
+        let y_seed = escrow_data.vault_y.as_ref();
+        let alice_seed = escrow_data.party_a.as_ref();
+        let bob_seed = escrow_data.seed_b();
+        let seeds_with_bump_escrow: &[&[u8]] = &[x_seed, y_seed, alice_seed, bob_seed, &[escrow_data.bump]];
+
+        for vault in [x_vault, y_vault] {
+            invoke_signed(
+                &close_account(token_program.key, vault.key, rent_payer.key, escrow.key, &[])?,
+                &[vault.clone(), rent_payer.clone(), escrow.clone(), token_program.clone()],
+                &[seeds_with_bump_escrow],
+            )?;
+        }
+
+        if let Some(dutch_auction) = dutch_auction {
+            Self::close_program_account(dutch_auction, rent_payer)?;
+        }
+        Self::close_program_account(escrow, rent_payer)?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::Closed,
+            escrow: *escrow.key,
+            party: *authority.key,
+            mint: Pubkey::default(),
+            amount: 0,
+            old_state: 0,
+            new_state: 0,
+        }.emit();
+        msg!("Returned rent to {}", rent_payer.key);
+
+        Ok(())
+    }
+
+    fn process_settle_delegated(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // escrow, the delegate of both sources
+        let escrow = next_account_info(account_info_iter)?;
+        // vaults, only read for their mints
+        let x_vault = next_account_info(account_info_iter)?;
+        let y_vault = next_account_info(account_info_iter)?;
+        // alice's x source and y destination
+        let alice_x = next_account_info(account_info_iter)?;
+        let alice_y = next_account_info(account_info_iter)?;
+        // bob's x destination and y source
+        let bob_x = next_account_info(account_info_iter)?;
+        let bob_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // condition account, only for a conditional escrow
+        let condition_account = next_account_info(account_info_iter).ok();
+
+        // nobody signs: the approvals are the parties' consent, so a keeper can settle once both
+        // are in place, and the checks below only let the tokens move between the parties
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        if escrow.owner != program_id || *token_program.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let escrow_data = *Escrow::load(&escrow.data.borrow())?;
+        if *x_vault.key != escrow_data.vault_x || *y_vault.key != escrow_data.vault_y {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // custodial deposits and delegated settlement are not mixed
+        if escrow_data.state != 0 {
+            return Err(EscrowError::EscrowNotEmpty.into());
+        }
+        if u64::from(escrow_data.size_b) == 0 {
+            return Err(EscrowError::UnpricedDutchAuction.into());
+        }
+        // vesting needs the legs in the vaults
+        if !escrow_data.vesting_a.is_immediate() || !escrow_data.vesting_b.is_immediate() {
+            return Err(EscrowError::InvalidVestingSchedule.into());
+        }
+        Self::check_condition(&escrow_data.condition, condition_account)?;
+        let x_mint = Account::unpack(&x_vault.data.borrow())?.mint;
+        let y_mint = Account::unpack(&y_vault.data.borrow())?.mint;
+        let size_a = u64::from(escrow_data.size_a);
+        let size_b = u64::from(escrow_data.size_b);
+
+        Self::check_delegated_source(alice_x, &escrow_data.party_a, &x_mint, escrow.key, size_a)?;
+        Self::check_delegated_source(bob_y, &escrow_data.party_b, &y_mint, escrow.key, size_b)?;
+        Self::check_token_account(bob_x, &escrow_data.party_b, &x_mint)?;
+        Self::check_token_account(alice_y, &escrow_data.party_a, &y_mint)?;
+
+        let x_seed = escrow_data.vault_x.as_ref();
+        let y_seed = escrow_data.vault_y.as_ref();
+        let alice_seed = escrow_data.party_a.as_ref();
+        let bob_seed = escrow_data.seed_b();
+        let seeds_with_bump_escrow: &[&[u8]] = &[x_seed, y_seed, alice_seed, bob_seed, &[escrow_data.bump]];
+
+        // the escrow signs as delegate, which uses up both approvals
+        invoke_signed(
+            &transfer(token_program.key, alice_x.key, bob_x.key, escrow.key, &[], size_a)?,
+            &[alice_x.clone(), bob_x.clone(), escrow.clone(), token_program.clone()],
+            &[seeds_with_bump_escrow],
+        )?;
+        invoke_signed(
+            &transfer(token_program.key, bob_y.key, alice_y.key, escrow.key, &[], size_b)?,
+            &[bob_y.clone(), alice_y.clone(), escrow.clone(), token_program.clone()],
+            &[seeds_with_bump_escrow],
+        )?;
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: *escrow.key,
+            party: escrow_data.party_b,
+            mint: x_mint,
+            amount: size_a,
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+        event.party = escrow_data.party_a;
+        event.mint = y_mint;
+        event.amount = size_b;
+        event.emit();
+        Self::return_status(&escrow_data)?;
+
+        Ok(())
+    }
+
+    fn process_init_dutch_escrow(
+        accounts: &[AccountInfo],
+        amount_a: u64,
+        start_amount_b: u64,
+        end_amount_b: u64,
+        start_time: i64,
+        end_time: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // alice, bob is left open for the first taker
+        let alice = next_account_info(account_info_iter)?;
+        // mints and vaults
+        let _ = next_account_info(account_info_iter)?;
+        let y_mint = next_account_info(account_info_iter)?;
+        let _ = next_account_info(account_info_iter)?;
+        let _ = next_account_info(account_info_iter)?;
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+        // token program
+        let _ = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // mint rules
+        let _ = next_account_info(account_info_iter)?;
+        let y_mint_rule = next_account_info(account_info_iter)?;
+        // dutch auction
+        let dutch_auction = next_account_info(account_info_iter)?;
+        // rent payer, optional
+        let rent_payer = next_account_info(account_info_iter).ok();
+
+        if start_time >= end_time || end_amount_b == 0 || start_amount_b < end_amount_b {
+            return Err(EscrowError::InvalidDutchAuction.into());
+        }
+        let (dutch_auction_key, bump) = find_dutch_auction_address(program_id, escrow.key);
+        if *dutch_auction.key != dutch_auction_key {
+            return Err(EscrowError::InvalidDutchAuction.into());
+        }
+
+        // a regular escrow at the start price, with the end price checked against the y mint
+        // rule as well so that every price in between is
+        // alice fills bob's slot, which an open escrow does not read
+        let init_accounts: Vec<AccountInfo> =
+            std::iter::once(alice).chain(&accounts[..12]).chain(rent_payer).cloned().collect();
+        Self::process_init_escrow(
+            &init_accounts, amount_a, start_amount_b, &EscrowTerms::default(), true, program_id,
+        )?;
+        let config_data = Self::load_config_or_default(config, program_id)?;
+        Self::check_mint_rule(y_mint_rule, y_mint.key, end_amount_b, &config_data, program_id)?;
+        Escrow::load_mut(&mut escrow.data.borrow_mut())?.size_b = 0.into();
+
+        // rent and space
+        let space = DutchAuction::LEN;
+        let rent = &Rent::from_account_info(rent_program)?;
+        let seeds: &[&[u8]] = &[DUTCH_AUCTION_SEED, escrow.key.as_ref(), &[bump]];
+        let payer = rent_payer.unwrap_or(alice);
+        Self::create_pda_account(payer, dutch_auction, system_program, rent, space, program_id, seeds)?;
+
+        let dutch_auction_data = DutchAuction {
+            discriminator: DutchAuction::DISCRIMINATOR,
+            escrow: *escrow.key,
+            start_amount_b,
+            end_amount_b,
+            start_time,
+            end_time,
+            bump,
+        };
+        dutch_auction_data.serialize(&mut &mut dutch_auction.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    /// Checks that `condition` holds on `condition_account`, which has to be the account it
+    /// names and owned by the program it names. Escrows without a condition take no account.
+    fn check_condition(condition: &Condition, condition_account: Option<&AccountInfo>) -> ProgramResult {
+        if condition.is_none() {
+            return Ok(());
+        }
+        let condition_account = condition_account.ok_or(EscrowError::InvalidConditionAccount)?;
+        if *condition_account.key != condition.account || *condition_account.owner != condition.owner {
+            return Err(EscrowError::InvalidConditionAccount.into());
+        }
+        if !condition.is_met(&condition_account.data.borrow()) {
+            return Err(EscrowError::ConditionNotMet.into());
+        }
+        Ok(())
+    }
+
+    /// Loads the Dutch auction of `escrow`.
+    fn load_dutch_auction(
+        dutch_auction: &AccountInfo,
+        escrow: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<DutchAuction, ProgramError> {
+        if dutch_auction.owner != program_id {
+            return Err(EscrowError::InvalidDutchAuction.into());
+        }
+        let dutch_auction_data = DutchAuction::load(&dutch_auction.data.borrow())?;
+        let dutch_auction_key = Pubkey::create_program_address(
+            &[DUTCH_AUCTION_SEED, escrow.as_ref(), &[dutch_auction_data.bump]],
+            program_id,
+        )?;
+        if dutch_auction_key != *dutch_auction.key || dutch_auction_data.escrow != *escrow {
+            return Err(EscrowError::InvalidDutchAuction.into());
+        }
+        Ok(dutch_auction_data)
+    }
+
+    /// Moves all lamports of an account owned by this program to `destination` and clears its data.
+    fn close_program_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+        let lamports = account.lamports();
+        **destination.lamports.borrow_mut() = destination
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(ProgramError::InvalidArgument)?;
+        **account.lamports.borrow_mut() = 0;
+        account.data.borrow_mut().fill(0);
+        Ok(())
+    }
+
+    fn process_atomic_swap(
+        accounts: &[AccountInfo],
+        amount_a: u64,
+        amount_b: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // parties, both sign
+        let alice = next_account_info(account_info_iter)?;
+        let bob = next_account_info(account_info_iter)?;
+        // mints
+        let x_mint = next_account_info(account_info_iter)?;
+        let y_mint = next_account_info(account_info_iter)?;
+        // alice's x source and y destination
+        let alice_x = next_account_info(account_info_iter)?;
+        let alice_y = next_account_info(account_info_iter)?;
+        // bob's x destination and y source
+        let bob_x = next_account_info(account_info_iter)?;
+        let bob_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // mint rules
+        let x_mint_rule = next_account_info(account_info_iter)?;
+        let y_mint_rule = next_account_info(account_info_iter)?;
+
+        if !alice.is_signer || !bob.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Self::check_trade(
+            alice.key, Some(bob.key), x_mint, y_mint, token_program, config, x_mint_rule, y_mint_rule, amount_a,
+            amount_b, program_id,
+        )?;
+        Self::check_token_account(alice_x, alice.key, x_mint.key)?;
+        Self::check_token_account(alice_y, alice.key, y_mint.key)?;
+        Self::check_token_account(bob_x, bob.key, x_mint.key)?;
+        Self::check_token_account(bob_y, bob.key, y_mint.key)?;
+
+        invoke(
+            &transfer(token_program.key, alice_x.key, bob_x.key, alice.key, &[], amount_a)?,
+            &[alice_x.clone(), bob_x.clone(), alice.clone(), token_program.clone()],
+        )?;
+        invoke(
+            &transfer(token_program.key, bob_y.key, alice_y.key, bob.key, &[], amount_b)?,
+            &[bob_y.clone(), alice_y.clone(), bob.clone(), token_program.clone()],
+        )?;
+
+        // there is no escrow account, events carry the default key instead
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: Pubkey::default(),
+            party: *bob.key,
+            mint: *x_mint.key,
+            amount: amount_a,
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+        event.party = *alice.key;
+        event.mint = *y_mint.key;
+        event.amount = amount_b;
+        event.emit();
+
+        Ok(())
+    }
+
+    fn process_fill_order(
+        accounts: &[AccountInfo],
+        order: Order,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // taker, also pays rent for the nonces
+        let taker = next_account_info(account_info_iter)?;
+        // mints
+        let x_mint = next_account_info(account_info_iter)?;
+        let y_mint = next_account_info(account_info_iter)?;
+        // maker's x source and y destination
+        let maker_x = next_account_info(account_info_iter)?;
+        let maker_y = next_account_info(account_info_iter)?;
+        // taker's x destination and y source
+        let taker_x = next_account_info(account_info_iter)?;
+        let taker_y = next_account_info(account_info_iter)?;
+        // order authority, delegate of the maker's x
+        let order_authority = next_account_info(account_info_iter)?;
+        // maker's nonces covering the order
+        let order_nonces = next_account_info(account_info_iter)?;
+        // instructions sysvar
+        let instructions = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // mint rules
+        let x_mint_rule = next_account_info(account_info_iter)?;
+        let y_mint_rule = next_account_info(account_info_iter)?;
+        // maker state
+        let maker_state = next_account_info(account_info_iter)?;
+
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *x_mint.key != order.x_mint || *y_mint.key != order.y_mint {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_trade(
+            &order.maker, Some(taker.key), x_mint, y_mint, token_program, config, x_mint_rule, y_mint_rule,
+            order.size_a, order.size_b, program_id,
+        )?;
+        Self::check_order_signature(instructions, &order, program_id)?;
+        if Clock::get()?.unix_timestamp > order.expiry {
+            return Err(EscrowError::OrderExpired.into());
+        }
+        if order.nonce < Self::load_maker_state_or_default(maker_state, &order.maker, program_id)?.min_valid_nonce {
+            return Err(EscrowError::OrderInvalidated.into());
+        }
+
+        let (order_authority_key, order_authority_bump) =
+            Pubkey::find_program_address(&[ORDER_AUTHORITY_SEED], program_id);
+        if *order_authority.key != order_authority_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let maker_x_data = Self::check_token_account(maker_x, &order.maker, &order.x_mint)?;
+        // one approval can back several orders, so it only needs to cover this one
+        if maker_x_data.delegate != COption::Some(order_authority_key) || maker_x_data.delegated_amount < order.size_a {
+            return Err(EscrowError::InvalidDelegation.into());
+        }
+        if maker_x_data.amount < order.size_a {
+            return Err(EscrowError::InsufficientDelegatedFunds.into());
+        }
+        Self::check_token_account(maker_y, &order.maker, &order.y_mint)?;
+        Self::check_token_account(taker_x, taker.key, &order.x_mint)?;
+        Self::check_token_account(taker_y, taker.key, &order.y_mint)?;
+
+        let (order_nonces_key, order_nonces_bump) = find_order_nonces_address(program_id, &order.maker, order.nonce);
+        if *order_nonces.key != order_nonces_key {
+            return Err(EscrowError::InvalidOrderNonces.into());
+        }
+        let page = order.nonce / OrderNonces::NONCES;
+        if order_nonces.data_len() == 0 {
+            // rent and space
+            let space = OrderNonces::LEN;
+            let rent = &Rent::from_account_info(rent_program)?;
+            let seeds: &[&[u8]] =
+                &[ORDER_NONCES_SEED, order.maker.as_ref(), &page.to_le_bytes(), &[order_nonces_bump]];
+            Self::create_pda_account(taker, order_nonces, system_program, rent, space, program_id, seeds)?;
+
+            let order_nonces_data = OrderNonces {
+                discriminator: OrderNonces::DISCRIMINATOR,
+                maker: order.maker,
+                page: page.into(),
+                bump: order_nonces_bump,
+                used: [0; 256],
+            };
+            order_nonces.data.borrow_mut().copy_from_slice(bytemuck::bytes_of(&order_nonces_data));
+        }
+        else if order_nonces.owner != program_id {
+            return Err(EscrowError::InvalidOrderNonces.into());
+        }
+        {
+            let mut data = order_nonces.data.borrow_mut();
+            let order_nonces_data = OrderNonces::load_mut(&mut data)?;
+            if order_nonces_data.is_used(order.nonce) {
+                return Err(EscrowError::OrderNonceUsed.into());
+            }
+            order_nonces_data.set_used(order.nonce);
+        }
+
+        invoke_signed(
+            &transfer(token_program.key, maker_x.key, taker_x.key, order_authority.key, &[], order.size_a)?,
+            &[maker_x.clone(), taker_x.clone(), order_authority.clone(), token_program.clone()],
+            &[&[ORDER_AUTHORITY_SEED, &[order_authority_bump]]],
+        )?;
+        invoke(
+            &transfer(token_program.key, taker_y.key, maker_y.key, taker.key, &[], order.size_b)?,
+            &[taker_y.clone(), maker_y.clone(), taker.clone(), token_program.clone()],
+        )?;
+
+        // there is no escrow account, events carry the nonces account instead
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: *order_nonces.key,
+            party: *taker.key,
+            mint: order.x_mint,
+            amount: order.size_a,
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+        event.party = order.maker;
+        event.mint = order.y_mint;
+        event.amount = order.size_b;
+        event.emit();
+
+        Ok(())
+    }
+
+    fn process_invalidate_orders(
+        accounts: &[AccountInfo],
+        nonce: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // maker, also pays rent
+        let maker = next_account_info(account_info_iter)?;
+        // maker state
+        let maker_state = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+
+        if !maker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut maker_state_data = Self::load_maker_state_or_default(maker_state, maker.key, program_id)?;
+        let min_valid_nonce = nonce.checked_add(1).ok_or(EscrowError::MinNonceNotIncreased)?;
+        if min_valid_nonce <= maker_state_data.min_valid_nonce {
+            return Err(EscrowError::MinNonceNotIncreased.into());
+        }
+
+        if maker_state.data_len() == 0 {
+            let (_, bump) = Pubkey::find_program_address(&[MAKER_STATE_SEED, maker.key.as_ref()], program_id);
+            // rent and space
+            let space = MakerState::LEN;
+            let rent = &Rent::from_account_info(rent_program)?;
+            let seeds: &[&[u8]] = &[MAKER_STATE_SEED, maker.key.as_ref(), &[bump]];
+            Self::create_pda_account(maker, maker_state, system_program, rent, space, program_id, seeds)?;
+            maker_state_data = MakerState {
+                discriminator: MakerState::DISCRIMINATOR,
+                maker: *maker.key,
+                min_valid_nonce: 0,
+                bump,
+            };
+        }
+        maker_state_data.min_valid_nonce = min_valid_nonce;
+        maker_state_data.serialize(&mut &mut maker_state.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    /// Loads the maker state of `maker`, or the default when it was never created.
+    fn load_maker_state_or_default(
+        maker_state: &AccountInfo,
+        maker: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<MakerState, ProgramError> {
+        if maker_state.data_len() == 0 {
+            let (maker_state_key, _) = Pubkey::find_program_address(&[MAKER_STATE_SEED, maker.as_ref()], program_id);
+            if maker_state_key != *maker_state.key {
+                return Err(EscrowError::InvalidMakerState.into());
+            }
+            return Ok(MakerState::default());
+        }
+        if maker_state.owner != program_id {
+            return Err(EscrowError::InvalidMakerState.into());
+        }
+        let maker_state_data = MakerState::load(&maker_state.data.borrow())?;
+        let maker_state_key =
+            Pubkey::create_program_address(&[MAKER_STATE_SEED, maker.as_ref(), &[maker_state_data.bump]], program_id)?;
+        if maker_state_key != *maker_state.key {
+            return Err(EscrowError::InvalidMakerState.into());
+        }
+        Ok(maker_state_data)
+    }
+
+    fn process_init_english_auction(
+        accounts: &[AccountInfo],
+        size_a: u64,
+        min_increment: u64,
+        reserve_price: u64,
+        deadline: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if min_increment == 0 || deadline <= Clock::get()?.unix_timestamp {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        let bump = Self::open_auction(accounts, size_a, EnglishAuction::LEN, ENGLISH_AUCTION_SEED, program_id)?;
+
+        let auction_data = EnglishAuction {
+            discriminator: EnglishAuction::DISCRIMINATOR,
+            seller: *accounts[0].key,
+            x_mint: *accounts[1].key,
+            y_mint: *accounts[2].key,
+            vault_x: *accounts[4].key,
+            vault_y: *accounts[5].key,
+            size_a,
+            min_increment,
+            reserve_price,
+            deadline,
+            highest_bidder: Pubkey::default(),
+            highest_bid: 0,
+            bump,
+        };
+        auction_data.serialize(&mut &mut accounts[3].data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    /// Creates an auction account of `space` bytes at the PDA of `[auction_seed, seller, x_mint,
+    /// y_mint]` with its two vaults, and moves the seller's lot of `size_a` into the x vault.
+    /// Returns the bump of the auction.
+    fn open_auction(
+        accounts: &[AccountInfo],
+        size_a: u64,
+        space: usize,
+        auction_seed: &[u8],
+        program_id: &Pubkey,
+    ) -> Result<u8, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        // seller, also pays rent
+        let seller = next_account_info(account_info_iter)?;
+        // mints
+        let x_mint = next_account_info(account_info_iter)?;
+        let y_mint = next_account_info(account_info_iter)?;
+        // auction
+        let auction = next_account_info(account_info_iter)?;
+        // vaults
+        let x_vault = next_account_info(account_info_iter)?;
+        let y_vault = next_account_info(account_info_iter)?;
+        // seller's x source
+        let seller_x = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // x mint rule
+        let x_mint_rule = next_account_info(account_info_iter)?;
+
+        if !seller.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if size_a == 0 {
+            return Err(EscrowError::ZeroEscrowSize.into());
+        }
+        if x_mint.key == y_mint.key {
+            return Err(EscrowError::IdenticalMints.into());
+        }
+        if *token_program.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_mint(x_mint)?;
+        Self::check_mint(y_mint)?;
+        let config_data = Self::load_config_or_default(config, program_id)?;
+        if config_data.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        // bids are checked against the y mint rule as they come in
+        Self::check_mint_rule(x_mint_rule, x_mint.key, size_a, &config_data, program_id)?;
+        Self::check_token_account(seller_x, seller.key, x_mint.key)?;
+
+        let (auction_key, bump) = Pubkey::find_program_address(
+            &[auction_seed, seller.key.as_ref(), x_mint.key.as_ref(), y_mint.key.as_ref()],
+            program_id,
+        );
+        let (x_vault_key, bump_vault_x) = find_auction_vault_address(program_id, &auction_key, x_mint.key);
+        let (y_vault_key, bump_vault_y) = find_auction_vault_address(program_id, &auction_key, y_mint.key);
+        if *auction.key != auction_key || *x_vault.key != x_vault_key || *y_vault.key != y_vault_key {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        if auction.data_len() != 0 {
+            return Err(EscrowError::AlreadyInitialized.into());
+        }
+
+        // rent and space
+        let rent = &Rent::from_account_info(rent_program)?;
+        let seeds: &[&[u8]] = &[auction_seed, seller.key.as_ref(), x_mint.key.as_ref(), y_mint.key.as_ref(), &[bump]];
+        Self::create_pda_account(seller, auction, system_program, rent, space, program_id, seeds)?;
+        for (vault, mint, vault_bump) in [(x_vault, x_mint, bump_vault_x), (y_vault, y_mint, bump_vault_y)] {
+            let seeds: &[&[u8]] = &[AUCTION_VAULT_SEED, auction.key.as_ref(), mint.key.as_ref(), &[vault_bump]];
+            Self::create_token_vault(
+                seller, vault, mint, auction, token_program, system_program, rent_program, seeds,
+            )?;
+        }
+
+        invoke(
+            &transfer(token_program.key, seller_x.key, x_vault.key, seller.key, &[], size_a)?,
+            &[seller_x.clone(), x_vault.clone(), seller.clone(), token_program.clone()],
+        )?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::EscrowCreated,
+            escrow: *auction.key,
+            party: *seller.key,
+            mint: *x_mint.key,
+            amount: size_a,
+            old_state: 0,
+            new_state: 0,
+        }.emit();
+
+        Ok(bump)
+    }
+
+    fn process_place_bid(
+        accounts: &[AccountInfo],
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // bidder
+        let bidder = next_account_info(account_info_iter)?;
+        // auction
+        let auction = next_account_info(account_info_iter)?;
+        // y vault
+        let y_vault = next_account_info(account_info_iter)?;
+        // bidder's y source
+        let bidder_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // y mint rule
+        let y_mint_rule = next_account_info(account_info_iter)?;
+
+        if !bidder.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let config_data = Self::load_config_or_default(config, program_id)?;
+        if config_data.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let mut auction_data = Self::load_english_auction(auction, program_id)?;
+        if *y_vault.key != auction_data.vault_y || *token_program.key != spl_token::id() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *bidder.key == auction_data.seller {
+            return Err(EscrowError::IdenticalParties.into());
+        }
+        if Clock::get()?.unix_timestamp >= auction_data.deadline {
+            return Err(EscrowError::AuctionEnded.into());
+        }
+        let min_bid = auction_data.min_bid().ok_or(EscrowError::BidTooLow)?;
+        if amount < min_bid {
+            return Err(EscrowError::BidTooLow.into());
+        }
+        Self::check_mint_rule(y_mint_rule, &auction_data.y_mint, amount, &config_data, program_id)?;
+        Self::check_token_account(bidder_y, bidder.key, &auction_data.y_mint)?;
+
+        invoke(
+            &transfer(token_program.key, bidder_y.key, y_vault.key, bidder.key, &[], amount)?,
+            &[bidder_y.clone(), y_vault.clone(), bidder.clone(), token_program.clone()],
+        )?;
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Deposited,
+            escrow: *auction.key,
+            party: *bidder.key,
+            mint: auction_data.y_mint,
+            amount,
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+
+        // the outbid bid goes back in the same instruction
+        if auction_data.highest_bid != 0 {
+            // previous highest bidder's y destination
+            let previous_bidder_y = next_account_info(account_info_iter)?;
+            Self::check_token_account(previous_bidder_y, &auction_data.highest_bidder, &auction_data.y_mint)?;
+            let seeds: &[&[u8]] = &[
+                ENGLISH_AUCTION_SEED,
+                auction_data.seller.as_ref(),
+                auction_data.x_mint.as_ref(),
+                auction_data.y_mint.as_ref(),
+                &[auction_data.bump],
+            ];
+            invoke_signed(
+                &transfer(
+                    token_program.key, y_vault.key, previous_bidder_y.key, auction.key, &[], auction_data.highest_bid,
+                )?,
+                &[y_vault.clone(), previous_bidder_y.clone(), auction.clone(), token_program.clone()],
+                &[seeds],
+            )?;
+            event.kind = EscrowEventKind::Withdrawn;
+            event.party = auction_data.highest_bidder;
+            event.amount = auction_data.highest_bid;
+            event.emit();
+        }
+
+        auction_data.highest_bidder = *bidder.key;
+        auction_data.highest_bid = amount;
+        auction_data.serialize(&mut &mut auction.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    fn process_settle_english_auction(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // auction
+        let auction = next_account_info(account_info_iter)?;
+        // vaults
+        let x_vault = next_account_info(account_info_iter)?;
+        let y_vault = next_account_info(account_info_iter)?;
+        // seller, receives the rent
+        let seller = next_account_info(account_info_iter)?;
+        // seller's y destination
+        let seller_y = next_account_info(account_info_iter)?;
+        // winner's x destination, the seller's when nobody bid
+        let x_destination = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let auction_data = Self::load_english_auction(auction, program_id)?;
+        if *x_vault.key != auction_data.vault_x
+            || *y_vault.key != auction_data.vault_y
+            || *seller.key != auction_data.seller
+            || *token_program.key != spl_token::id()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if Clock::get()?.unix_timestamp < auction_data.deadline {
+            return Err(EscrowError::AuctionNotEnded.into());
+        }
+        let winner = if auction_data.highest_bid == 0 {
+            auction_data.seller
+        }
+        else {
+            auction_data.highest_bidder
+        };
+        Self::check_token_account(x_destination, &winner, &auction_data.x_mint)?;
+        Self::check_token_account(seller_y, &auction_data.seller, &auction_data.y_mint)?;
+
+        let seeds: &[&[u8]] = &[
+            ENGLISH_AUCTION_SEED,
+            auction_data.seller.as_ref(),
+            auction_data.x_mint.as_ref(),
+            auction_data.y_mint.as_ref(),
+            &[auction_data.bump],
+        ];
+        Self::close_auction_vault(x_vault, x_destination, seller, auction, token_program, seeds)?;
+        Self::close_auction_vault(y_vault, seller_y, seller, auction, token_program, seeds)?;
+        Self::close_program_account(auction, seller)?;
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: *auction.key,
+            party: winner,
+            mint: auction_data.x_mint,
+            amount: auction_data.size_a,
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+        if auction_data.highest_bid != 0 {
+            event.party = auction_data.seller;
+            event.mint = auction_data.y_mint;
+            event.amount = auction_data.highest_bid;
+            event.emit();
+        }
+        event.kind = EscrowEventKind::Closed;
+        event.party = auction_data.seller;
+        event.mint = Pubkey::default();
+        event.amount = 0;
+        event.emit();
+
+        Ok(())
+    }
+
+    /// Sends everything left in an auction vault to `destination` and closes it to the seller.
+    /// Vaults are emptied by balance so that stray transfers into them cannot block the close.
+    /// Milestone escrows and streams close their vault the same way, with the payer in place of
+    /// the seller.
+    fn close_auction_vault<'a>(
+        vault: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        seller: &AccountInfo<'a>,
+        auction: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let amount = Account::unpack(&vault.data.borrow())?.amount;
+        if amount != 0 {
+            invoke_signed(
+                &transfer(token_program.key, vault.key, destination.key, auction.key, &[], amount)?,
+                &[vault.clone(), destination.clone(), auction.clone(), token_program.clone()],
+                &[seeds],
+            )?;
+        }
+        invoke_signed(
+            &close_account(token_program.key, vault.key, seller.key, auction.key, &[])?,
+            &[vault.clone(), seller.clone(), auction.clone(), token_program.clone()],
+            &[seeds],
+        )
+    }
+
+    /// Loads an English auction and checks that it sits at its PDA.
+    fn load_english_auction(auction: &AccountInfo, program_id: &Pubkey) -> Result<EnglishAuction, ProgramError> {
+        if auction.owner != program_id {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        let auction_data = EnglishAuction::load(&auction.data.borrow())?;
+        let auction_key = Pubkey::create_program_address(
+            &[
+                ENGLISH_AUCTION_SEED,
+                auction_data.seller.as_ref(),
+                auction_data.x_mint.as_ref(),
+                auction_data.y_mint.as_ref(),
+                &[auction_data.bump],
+            ],
+            program_id,
+        )?;
+        if auction_key != *auction.key {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        Ok(auction_data)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_init_sealed_auction(
+        accounts: &[AccountInfo],
+        size_a: u64,
+        reserve_price: u64,
+        penalty: u64,
+        commit_deadline: i64,
+        reveal_deadline: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if commit_deadline <= Clock::get()?.unix_timestamp || reveal_deadline <= commit_deadline {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        let bump = Self::open_auction(accounts, size_a, SealedAuction::LEN, SEALED_AUCTION_SEED, program_id)?;
+
+        let auction_data = SealedAuction {
+            discriminator: SealedAuction::DISCRIMINATOR,
+            seller: *accounts[0].key,
+            x_mint: *accounts[1].key,
+            y_mint: *accounts[2].key,
+            vault_x: *accounts[4].key,
+            vault_y: *accounts[5].key,
+            size_a,
+            reserve_price,
+            penalty,
+            commit_deadline,
+            reveal_deadline,
+            highest_bidder: Pubkey::default(),
+            highest_bid: 0,
+            open_bids: 0,
+            settled: false,
+            bump,
+        };
+        auction_data.serialize(&mut &mut accounts[3].data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    fn process_commit_bid(
+        accounts: &[AccountInfo],
+        commitment: [u8; 32],
+        collateral: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // bidder, also pays rent
+        let bidder = next_account_info(account_info_iter)?;
+        // auction
+        let auction = next_account_info(account_info_iter)?;
+        // bid
+        let bid = next_account_info(account_info_iter)?;
+        // y vault
+        let y_vault = next_account_info(account_info_iter)?;
+        // bidder's y source
+        let bidder_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+
+        if !bidder.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let mut auction_data = Self::load_sealed_auction(auction, program_id)?;
+        if *y_vault.key != auction_data.vault_y || *token_program.key != spl_token::id() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *bidder.key == auction_data.seller {
+            return Err(EscrowError::IdenticalParties.into());
+        }
+        if Clock::get()?.unix_timestamp >= auction_data.commit_deadline {
+            return Err(EscrowError::AuctionEnded.into());
+        }
+        if collateral == 0 {
+            return Err(EscrowError::ZeroEscrowSize.into());
+        }
+        let (bid_key, bump) = find_sealed_bid_address(program_id, auction.key, bidder.key);
+        if *bid.key != bid_key {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        if bid.data_len() != 0 {
+            return Err(EscrowError::AlreadyInitialized.into());
+        }
+        Self::check_token_account(bidder_y, bidder.key, &auction_data.y_mint)?;
+
+        // rent and space
+        let space = SealedBid::LEN;
+        let rent = &Rent::from_account_info(rent_program)?;
+        let seeds: &[&[u8]] = &[SEALED_BID_SEED, auction.key.as_ref(), bidder.key.as_ref(), &[bump]];
+        Self::create_pda_account(bidder, bid, system_program, rent, space, program_id, seeds)?;
+
+        invoke(
+            &transfer(token_program.key, bidder_y.key, y_vault.key, bidder.key, &[], collateral)?,
+            &[bidder_y.clone(), y_vault.clone(), bidder.clone(), token_program.clone()],
+        )?;
+
+        let bid_data = SealedBid {
+            discriminator: SealedBid::DISCRIMINATOR,
+            auction: *auction.key,
+            bidder: *bidder.key,
+            commitment,
+            collateral,
+            bid: 0,
+            revealed: false,
+            bump,
+        };
+        bid_data.serialize(&mut &mut bid.data.borrow_mut()[..])?;
+        auction_data.open_bids = auction_data.open_bids.checked_add(1).ok_or(ProgramError::InvalidArgument)?;
+        auction_data.serialize(&mut &mut auction.data.borrow_mut()[..])?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::Deposited,
+            escrow: *auction.key,
+            party: *bidder.key,
+            mint: auction_data.y_mint,
+            amount: collateral,
+            old_state: 0,
+            new_state: 0,
+        }.emit();
+
+        Ok(())
+    }
+
+    fn process_reveal_bid(
+        accounts: &[AccountInfo],
+        amount: u64,
+        salt: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // bidder
+        let bidder = next_account_info(account_info_iter)?;
+        // auction
+        let auction = next_account_info(account_info_iter)?;
+        // bid
+        let bid = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // y mint rule
+        let y_mint_rule = next_account_info(account_info_iter)?;
+
+        if !bidder.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let config_data = Self::load_config_or_default(config, program_id)?;
+        if config_data.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let mut auction_data = Self::load_sealed_auction(auction, program_id)?;
+        let mut bid_data = Self::load_sealed_bid(bid, auction.key, bidder.key, program_id)?;
+        let now = Clock::get()?.unix_timestamp;
+        if now < auction_data.commit_deadline || now >= auction_data.reveal_deadline {
+            return Err(EscrowError::RevealNotOpen.into());
+        }
+        if bid_data.revealed
+            || amount > bid_data.collateral
+            || sealed_bid_commitment(bidder.key, amount, &salt) != bid_data.commitment
+        {
+            return Err(EscrowError::InvalidReveal.into());
+        }
+        bid_data.revealed = true;
+        bid_data.bid = amount;
+        bid_data.serialize(&mut &mut bid.data.borrow_mut()[..])?;
+
+        // an honest reveal of a bid under the reserve or outside the y mint rule is not
+        // penalized, it just cannot win. Ties go to whoever revealed first.
+        let valid = amount >= auction_data.reserve_price.max(1)
+            && Self::check_mint_rule(y_mint_rule, &auction_data.y_mint, amount, &config_data, program_id).is_ok();
+        if valid && amount > auction_data.highest_bid {
+            auction_data.highest_bidder = *bidder.key;
+            auction_data.highest_bid = amount;
+            auction_data.serialize(&mut &mut auction.data.borrow_mut()[..])?;
+        }
+
+        Ok(())
+    }
+
+    fn process_settle_sealed_auction(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // auction
+        let auction = next_account_info(account_info_iter)?;
+        // vaults
+        let x_vault = next_account_info(account_info_iter)?;
+        let y_vault = next_account_info(account_info_iter)?;
+        // seller, receives the rent
+        let seller = next_account_info(account_info_iter)?;
+        // seller's y destination
+        let seller_y = next_account_info(account_info_iter)?;
+        // winner's x destination, the seller's without a valid bid
+        let x_destination = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let mut auction_data = Self::load_sealed_auction(auction, program_id)?;
+        if *x_vault.key != auction_data.vault_x
+            || *y_vault.key != auction_data.vault_y
+            || *seller.key != auction_data.seller
+            || *token_program.key != spl_token::id()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if auction_data.settled {
+            return Err(EscrowError::AuctionSettled.into());
+        }
+        if Clock::get()?.unix_timestamp < auction_data.reveal_deadline {
+            return Err(EscrowError::AuctionNotEnded.into());
+        }
+        let winner = if auction_data.highest_bid == 0 {
+            auction_data.seller
+        }
+        else {
+            auction_data.highest_bidder
+        };
+        Self::check_token_account(x_destination, &winner, &auction_data.x_mint)?;
+        Self::check_token_account(seller_y, &auction_data.seller, &auction_data.y_mint)?;
+
+        let seeds: &[&[u8]] = &[
+            SEALED_AUCTION_SEED,
+            auction_data.seller.as_ref(),
+            auction_data.x_mint.as_ref(),
+            auction_data.y_mint.as_ref(),
+            &[auction_data.bump],
+        ];
+        Self::close_auction_vault(x_vault, x_destination, seller, auction, token_program, seeds)?;
+        // the winning bid comes out of the winner's collateral, the rest is withdrawn per bid
+        if auction_data.highest_bid != 0 {
+            invoke_signed(
+                &transfer(token_program.key, y_vault.key, seller_y.key, auction.key, &[], auction_data.highest_bid)?,
+                &[y_vault.clone(), seller_y.clone(), auction.clone(), token_program.clone()],
+                &[seeds],
+            )?;
+        }
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: *auction.key,
+            party: winner,
+            mint: auction_data.x_mint,
+            amount: auction_data.size_a,
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+        if auction_data.highest_bid != 0 {
+            event.party = auction_data.seller;
+            event.mint = auction_data.y_mint;
+            event.amount = auction_data.highest_bid;
+            event.emit();
+        }
+
+        auction_data.settled = true;
+        if auction_data.open_bids == 0 {
+            Self::close_auction_vault(y_vault, seller_y, seller, auction, token_program, seeds)?;
+            Self::close_program_account(auction, seller)?;
+            event.kind = EscrowEventKind::Closed;
+            event.party = auction_data.seller;
+            event.mint = Pubkey::default();
+            event.amount = 0;
+            event.emit();
+        }
+        else {
+            auction_data.serialize(&mut &mut auction.data.borrow_mut()[..])?;
+        }
+
+        Ok(())
+    }
+
+    fn process_withdraw_collateral(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // bidder, receives the rent of the bid
+        let bidder = next_account_info(account_info_iter)?;
+        // auction
+        let auction = next_account_info(account_info_iter)?;
+        // bid
+        let bid = next_account_info(account_info_iter)?;
+        // y vault
+        let y_vault = next_account_info(account_info_iter)?;
+        // bidder's y destination
+        let bidder_y = next_account_info(account_info_iter)?;
+        // seller, receives the rent of the auction after the last withdrawal
+        let seller = next_account_info(account_info_iter)?;
+        // seller's y destination, receives penalties
+        let seller_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+
+        if !bidder.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut auction_data = Self::load_sealed_auction(auction, program_id)?;
+        let bid_data = Self::load_sealed_bid(bid, auction.key, bidder.key, program_id)?;
+        if *y_vault.key != auction_data.vault_y
+            || *seller.key != auction_data.seller
+            || *token_program.key != spl_token::id()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // the winner pays out of their collateral at settlement, so everyone waits for it
+        if !auction_data.settled {
+            return Err(EscrowError::AuctionNotEnded.into());
+        }
+        Self::check_token_account(bidder_y, bidder.key, &auction_data.y_mint)?;
+        Self::check_token_account(seller_y, &auction_data.seller, &auction_data.y_mint)?;
+
+        let (kept, penalty) = if !bid_data.revealed {
+            (0, auction_data.penalty.min(bid_data.collateral))
+        }
+        else if *bidder.key == auction_data.highest_bidder && auction_data.highest_bid != 0 {
+            (auction_data.highest_bid, 0)
+        }
+        else {
+            (0, 0)
+        };
+        let refund = bid_data
+            .collateral
+            .checked_sub(kept)
+            .and_then(|refund| refund.checked_sub(penalty))
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        let seeds: &[&[u8]] = &[
+            SEALED_AUCTION_SEED,
+            auction_data.seller.as_ref(),
+            auction_data.x_mint.as_ref(),
+            auction_data.y_mint.as_ref(),
+            &[auction_data.bump],
+        ];
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Withdrawn,
+            escrow: *auction.key,
+            party: *bidder.key,
+            mint: auction_data.y_mint,
+            amount: refund,
+            old_state: 0,
+            new_state: 0,
+        };
+        for (destination, amount) in [(bidder_y, refund), (seller_y, penalty)] {
+            if amount != 0 {
+                invoke_signed(
+                    &transfer(token_program.key, y_vault.key, destination.key, auction.key, &[], amount)?,
+                    &[y_vault.clone(), destination.clone(), auction.clone(), token_program.clone()],
+                    &[seeds],
+                )?;
+            }
+        }
+        event.emit();
+        if penalty != 0 {
+            event.party = auction_data.seller;
+            event.amount = penalty;
+            event.emit();
+        }
+
+        auction_data.open_bids -= 1;
+        if auction_data.open_bids == 0 {
+            Self::close_auction_vault(y_vault, seller_y, seller, auction, token_program, seeds)?;
+        }
+        Self::close_program_account(bid, bidder)?;
+        if auction_data.open_bids == 0 {
+            Self::close_program_account(auction, seller)?;
+            event.kind = EscrowEventKind::Closed;
+            event.party = auction_data.seller;
+            event.mint = Pubkey::default();
+            event.amount = 0;
+            event.emit();
+        }
+        else {
+            auction_data.serialize(&mut &mut auction.data.borrow_mut()[..])?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads a sealed-bid auction and checks that it sits at its PDA.
+    fn load_sealed_auction(auction: &AccountInfo, program_id: &Pubkey) -> Result<SealedAuction, ProgramError> {
+        if auction.owner != program_id {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        let auction_data = SealedAuction::load(&auction.data.borrow())?;
+        let auction_key = Pubkey::create_program_address(
+            &[
+                SEALED_AUCTION_SEED,
+                auction_data.seller.as_ref(),
+                auction_data.x_mint.as_ref(),
+                auction_data.y_mint.as_ref(),
+                &[auction_data.bump],
+            ],
+            program_id,
+        )?;
+        if auction_key != *auction.key {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        Ok(auction_data)
+    }
+
+    /// Loads `bidder`'s bid on `auction`.
+    fn load_sealed_bid(
+        bid: &AccountInfo,
+        auction: &Pubkey,
+        bidder: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<SealedBid, ProgramError> {
+        if bid.owner != program_id {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        let bid_data = SealedBid::load(&bid.data.borrow())?;
+        let bid_key = Pubkey::create_program_address(
+            &[SEALED_BID_SEED, auction.as_ref(), bidder.as_ref(), &[bid_data.bump]],
+            program_id,
+        )?;
+        if bid_key != *bid.key || bid_data.auction != *auction || bid_data.bidder != *bidder {
+            return Err(EscrowError::InvalidAuction.into());
+        }
+        Ok(bid_data)
+    }
+
+    fn process_init_milestone_escrow(
+        accounts: &[AccountInfo],
+        milestones: Vec<u64>,
+        deadline: i64,
+        condition: Condition,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // arbiter, optional after the accounts of `open_payment`
+        let arbiter = accounts.get(11).map(|arbiter| *arbiter.key).unwrap_or_default();
+
+        if milestones.is_empty()
+            || milestones.len() > MilestoneEscrow::MAX_MILESTONES
+            || milestones.contains(&0)
+            || deadline <= Clock::get()?.unix_timestamp
+        {
+            return Err(EscrowError::InvalidMilestoneEscrow.into());
+        }
+        let total = milestones
+            .iter()
+            .try_fold(0u64, |total, &amount| total.checked_add(amount))
+            .ok_or(EscrowError::InvalidMilestoneEscrow)?;
+        // the payee approving their own milestones would defeat the escrow
+        if accounts.get(1).map(|payee| *payee.key) == Some(arbiter) {
+            return Err(EscrowError::IdenticalParties.into());
+        }
+        Self::check_new_condition(&condition)?;
+        let space = MilestoneEscrow::space(milestones.len());
+        let (bump, bump_vault) = Self::open_payment(
+            accounts, total, space, MILESTONE_ESCROW_SEED, MILESTONE_VAULT_SEED, EscrowError::InvalidMilestoneEscrow,
+            program_id,
+        )?;
+
+        let milestone_data = MilestoneEscrow {
+            discriminator: MilestoneEscrow::DISCRIMINATOR,
+            payer: *accounts[0].key,
+            payee: *accounts[1].key,
+            mint: *accounts[2].key,
+            vault: *accounts[4].key,
+            arbiter,
+            deadline,
+            approved: 0,
+            bump,
+            bump_vault,
+            condition,
+            milestones,
+        };
+        milestone_data.serialize(&mut &mut accounts[3].data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    /// Creates a payment account of `space` bytes at the PDA of `[seed, payer, payee, mint]` with
+    /// its vault at the PDA of `[vault_seed, payment]`, and moves `total` of the payer's tokens
+    /// into the vault. Used by milestone escrows and streams, `invalid` is the error for a
+    /// payment or vault at the wrong address. Returns the bumps of the payment and the vault.
+    fn open_payment(
+        accounts: &[AccountInfo],
+        total: u64,
+        space: usize,
+        seed: &[u8],
+        vault_seed: &[u8],
+        invalid: EscrowError,
+        program_id: &Pubkey,
+    ) -> Result<(u8, u8), ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        // payer, also pays rent
+        let payer = next_account_info(account_info_iter)?;
+        // payee
+        let payee = next_account_info(account_info_iter)?;
+        // mint
+        let mint = next_account_info(account_info_iter)?;
+        // payment
+        let payment = next_account_info(account_info_iter)?;
+        // vault
+        let vault = next_account_info(account_info_iter)?;
+        // payer's source
+        let payer_source = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // mint rule
+        let mint_rule = next_account_info(account_info_iter)?;
+
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if payer.key == payee.key {
+            return Err(EscrowError::IdenticalParties.into());
+        }
+        if *token_program.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_mint(mint)?;
+        let config_data = Self::load_config_or_default(config, program_id)?;
+        if config_data.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        Self::check_mint_rule(mint_rule, mint.key, total, &config_data, program_id)?;
+        Self::check_token_account(payer_source, payer.key, mint.key)?;
+
+        let (payment_key, bump) = Pubkey::find_program_address(
+            &[seed, payer.key.as_ref(), payee.key.as_ref(), mint.key.as_ref()],
+            program_id,
+        );
+        let (vault_key, bump_vault) = Pubkey::find_program_address(&[vault_seed, payment_key.as_ref()], program_id);
+        if *payment.key != payment_key || *vault.key != vault_key {
+            return Err(invalid.into());
+        }
+        if payment.data_len() != 0 {
+            return Err(EscrowError::AlreadyInitialized.into());
+        }
+
+        // rent and space
+        let rent = &Rent::from_account_info(rent_program)?;
+        let seeds: &[&[u8]] = &[seed, payer.key.as_ref(), payee.key.as_ref(), mint.key.as_ref(), &[bump]];
+        Self::create_pda_account(payer, payment, system_program, rent, space, program_id, seeds)?;
+        let seeds: &[&[u8]] = &[vault_seed, payment.key.as_ref(), &[bump_vault]];
+        Self::create_token_vault(
+            payer, vault, mint, payment, token_program, system_program, rent_program, seeds,
+        )?;
+
+        invoke(
+            &transfer(token_program.key, payer_source.key, vault.key, payer.key, &[], total)?,
+            &[payer_source.clone(), vault.clone(), payer.clone(), token_program.clone()],
+        )?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::EscrowCreated,
+            escrow: *payment.key,
+            party: *payer.key,
+            mint: *mint.key,
+            amount: total,
+            old_state: 0,
+            new_state: 0,
+        }.emit();
+
+        Ok((bump, bump_vault))
+    }
+
+    fn process_approve_milestone(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // payer or arbiter
+        let approver = next_account_info(account_info_iter)?;
+        // milestone escrow
+        let milestone_escrow = next_account_info(account_info_iter)?;
+        // vault
+        let vault = next_account_info(account_info_iter)?;
+        // payee's destination
+        let payee_destination = next_account_info(account_info_iter)?;
+        // payer, receives the rent after the last milestone
+        let payer = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // condition account, only for a milestone escrow with a condition
+        let condition_account = next_account_info(account_info_iter).ok();
+
+        if !approver.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let config_data = Self::load_config_or_default(config, program_id)?;
+        if config_data.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let mut milestone_data = Self::load_milestone_escrow(milestone_escrow, program_id)?;
+        if *vault.key != milestone_data.vault
+            || *payer.key != milestone_data.payer
+            || *token_program.key != spl_token::id()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *approver.key != milestone_data.payer
+            && !(milestone_data.has_arbiter() && *approver.key == milestone_data.arbiter)
+        {
+            return Err(EscrowError::Unauthorized.into());
+        }
+        Self::check_condition(&milestone_data.condition, condition_account)?;
+        let tranche = milestone_data.next_tranche().ok_or(EscrowError::InvalidMilestoneEscrow)?;
+        Self::check_token_account(payee_destination, &milestone_data.payee, &milestone_data.mint)?;
+
+        let seeds: &[&[u8]] = &[
+            MILESTONE_ESCROW_SEED,
+            milestone_data.payer.as_ref(),
+            milestone_data.payee.as_ref(),
+            milestone_data.mint.as_ref(),
+            &[milestone_data.bump],
+        ];
+        invoke_signed(
+            &transfer(token_program.key, vault.key, payee_destination.key, milestone_escrow.key, &[], tranche)?,
+            &[vault.clone(), payee_destination.clone(), milestone_escrow.clone(), token_program.clone()],
+            &[seeds],
+        )?;
+        milestone_data.approved += 1;
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: *milestone_escrow.key,
+            party: milestone_data.payee,
+            mint: milestone_data.mint,
+            amount: tranche,
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+
+        if milestone_data.next_tranche().is_none() {
+            Self::close_auction_vault(vault, payee_destination, payer, milestone_escrow, token_program, seeds)?;
+            Self::close_program_account(milestone_escrow, payer)?;
+            event.kind = EscrowEventKind::Closed;
+            event.party = milestone_data.payer;
+            event.mint = Pubkey::default();
+            event.amount = 0;
+            event.emit();
+        }
+        else {
+            milestone_data.serialize(&mut &mut milestone_escrow.data.borrow_mut()[..])?;
+        }
+
+        Ok(())
+    }
+
+    fn process_refund_milestones(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // payer, receives the rent
+        let payer = next_account_info(account_info_iter)?;
+        // milestone escrow
+        let milestone_escrow = next_account_info(account_info_iter)?;
+        // vault
+        let vault = next_account_info(account_info_iter)?;
+        // payer's destination
+        let payer_destination = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let milestone_data = Self::load_milestone_escrow(milestone_escrow, program_id)?;
+        if *vault.key != milestone_data.vault || *token_program.key != spl_token::id() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *payer.key != milestone_data.payer {
+            return Err(EscrowError::Unauthorized.into());
+        }
+        // refunds stay open while paused, like refunds of unmatched deposits
+        if Clock::get()?.unix_timestamp < milestone_data.deadline {
+            return Err(EscrowError::DeadlineNotReached.into());
+        }
+        Self::check_token_account(payer_destination, payer.key, &milestone_data.mint)?;
+
+        let seeds: &[&[u8]] = &[
+            MILESTONE_ESCROW_SEED,
+            milestone_data.payer.as_ref(),
+            milestone_data.payee.as_ref(),
+            milestone_data.mint.as_ref(),
+            &[milestone_data.bump],
+        ];
+        Self::close_auction_vault(vault, payer_destination, payer, milestone_escrow, token_program, seeds)?;
+        Self::close_program_account(milestone_escrow, payer)?;
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Cancelled,
+            escrow: *milestone_escrow.key,
+            party: milestone_data.payer,
+            mint: milestone_data.mint,
+            amount: milestone_data.remaining(),
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+        event.kind = EscrowEventKind::Closed;
+        event.mint = Pubkey::default();
+        event.amount = 0;
+        event.emit();
+
+        Ok(())
+    }
+
+    /// Loads a milestone escrow and checks that it sits at its PDA.
+    fn load_milestone_escrow(
+        milestone_escrow: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<MilestoneEscrow, ProgramError> {
+        if milestone_escrow.owner != program_id {
+            return Err(EscrowError::InvalidMilestoneEscrow.into());
+        }
+        let milestone_data = MilestoneEscrow::load(&milestone_escrow.data.borrow())?;
+        let milestone_escrow_key = Pubkey::create_program_address(
+            &[
+                MILESTONE_ESCROW_SEED,
+                milestone_data.payer.as_ref(),
+                milestone_data.payee.as_ref(),
+                milestone_data.mint.as_ref(),
+                &[milestone_data.bump],
+            ],
+            program_id,
+        )?;
+        if milestone_escrow_key != *milestone_escrow.key {
+            return Err(EscrowError::InvalidMilestoneEscrow.into());
+        }
+        Ok(milestone_data)
+    }
+
+    fn process_init_stream(
+        accounts: &[AccountInfo],
+        rate: u64,
+        start: i64,
+        end: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if rate == 0 || start >= end || end <= Clock::get()?.unix_timestamp {
+            return Err(EscrowError::InvalidStream.into());
+        }
+        let total = Stream::total(rate, start, end).ok_or(EscrowError::InvalidStream)?;
+        let (bump, bump_vault) = Self::open_payment(
+            accounts, total, Stream::LEN, STREAM_SEED, STREAM_VAULT_SEED, EscrowError::InvalidStream, program_id,
+        )?;
+
+        let stream_data = Stream {
+            discriminator: Stream::DISCRIMINATOR,
+            sender: *accounts[0].key,
+            recipient: *accounts[1].key,
+            mint: *accounts[2].key,
+            vault: *accounts[4].key,
+            rate,
+            start,
+            end,
+            withdrawn: 0,
+            bump,
+            bump_vault,
+        };
+        stream_data.serialize(&mut &mut accounts[3].data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    /// `Withdraw` from a stream, which pays the recipient any `amount` that has accrued and not
+    /// been withdrawn yet. The stream stays open until the sender cancels it.
+    #[allow(clippy::too_many_arguments)]
+    fn withdraw_stream<'a>(
+        withdrawer: &AccountInfo<'a>,
+        vault: &AccountInfo<'a>,
+        stream: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        config: &AccountInfo<'a>,
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let mut stream_data = Self::load_stream(stream, program_id)?;
+        if *vault.key != stream_data.vault || *token_program.key != spl_token::id() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *withdrawer.key != stream_data.recipient {
+            return Err(EscrowError::InvalidParty.into());
+        }
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let available = stream_data.accrued(Clock::get()?.unix_timestamp).saturating_sub(stream_data.withdrawn);
+        if amount == 0 || amount > available {
+            return Err(EscrowError::AmountNotVested.into());
+        }
+
+        let seeds: &[&[u8]] = &[
+            STREAM_SEED,
+            stream_data.sender.as_ref(),
+            stream_data.recipient.as_ref(),
+            stream_data.mint.as_ref(),
+            &[stream_data.bump],
+        ];
+        invoke_signed(
+            &transfer(token_program.key, vault.key, destination.key, stream.key, &[], amount)?,
+            &[vault.clone(), destination.clone(), stream.clone(), token_program.clone()],
+            &[seeds],
+        )?;
+        stream_data.withdrawn += amount;
+        stream_data.serialize(&mut &mut stream.data.borrow_mut()[..])?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::Withdrawn,
+            escrow: *stream.key,
+            party: stream_data.recipient,
+            mint: stream_data.mint,
+            amount,
+            old_state: 0,
+            new_state: 0,
+        }.emit();
+
+        Ok(())
+    }
+
+    fn process_cancel_stream(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // sender, receives the rent
+        let sender = next_account_info(account_info_iter)?;
+        // stream
+        let stream = next_account_info(account_info_iter)?;
+        // vault
+        let vault = next_account_info(account_info_iter)?;
+        // sender's destination
+        let sender_destination = next_account_info(account_info_iter)?;
+        // recipient's destination
+        let recipient_destination = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+
+        if !sender.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let stream_data = Self::load_stream(stream, program_id)?;
+        if *vault.key != stream_data.vault || *token_program.key != spl_token::id() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *sender.key != stream_data.sender {
+            return Err(EscrowError::Unauthorized.into());
+        }
+        // not gated by the pause, like the other refunds, so that a paused stream's funds are
+        // not locked. Paying out what has accrued is part of the refund.
+        Self::check_token_account(sender_destination, sender.key, &stream_data.mint)?;
+        Self::check_token_account(recipient_destination, &stream_data.recipient, &stream_data.mint)?;
+
+        let owed = stream_data.accrued(Clock::get()?.unix_timestamp).saturating_sub(stream_data.withdrawn);
+        let refund = Account::unpack(&vault.data.borrow())?.amount.saturating_sub(owed);
+        let seeds: &[&[u8]] = &[
+            STREAM_SEED,
+            stream_data.sender.as_ref(),
+            stream_data.recipient.as_ref(),
+            stream_data.mint.as_ref(),
+            &[stream_data.bump],
+        ];
+        if owed != 0 {
+            invoke_signed(
+                &transfer(token_program.key, vault.key, recipient_destination.key, stream.key, &[], owed)?,
+                &[vault.clone(), recipient_destination.clone(), stream.clone(), token_program.clone()],
+                &[seeds],
+            )?;
+        }
+        Self::close_auction_vault(vault, sender_destination, sender, stream, token_program, seeds)?;
+        Self::close_program_account(stream, sender)?;
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Withdrawn,
+            escrow: *stream.key,
+            party: stream_data.recipient,
+            mint: stream_data.mint,
+            amount: owed,
+            old_state: 0,
+            new_state: 0,
+        };
+        if owed != 0 {
+            event.emit();
+        }
+        event.kind = EscrowEventKind::Cancelled;
+        event.party = stream_data.sender;
+        event.amount = refund;
+        event.emit();
+        event.kind = EscrowEventKind::Closed;
+        event.mint = Pubkey::default();
+        event.amount = 0;
+        event.emit();
+
+        Ok(())
+    }
+
+    /// Loads a stream and checks that it sits at its PDA.
+    fn load_stream(stream: &AccountInfo, program_id: &Pubkey) -> Result<Stream, ProgramError> {
+        if stream.owner != program_id {
+            return Err(EscrowError::InvalidStream.into());
+        }
+        let stream_data = Stream::load(&stream.data.borrow())?;
+        let stream_key = Pubkey::create_program_address(
+            &[
+                STREAM_SEED,
+                stream_data.sender.as_ref(),
+                stream_data.recipient.as_ref(),
+                stream_data.mint.as_ref(),
+                &[stream_data.bump],
+            ],
+            program_id,
+        )?;
+        if stream_key != *stream.key {
+            return Err(EscrowError::InvalidStream.into());
+        }
+        Ok(stream_data)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_init_loan(
+        accounts: &[AccountInfo],
+        amount_a: u64,
+        amount_b: u64,
+        interest: u64,
+        deadline: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // alice, the borrower
+        let alice = next_account_info(account_info_iter)?;
+        // bob, the lender
+        let _ = next_account_info(account_info_iter)?;
+        // mints
+        let x_mint = next_account_info(account_info_iter)?;
+        let _ = next_account_info(account_info_iter)?;
+        // vaults
+        let x_vault = next_account_info(account_info_iter)?;
+        let _ = next_account_info(account_info_iter)?;
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+        // config and mint rules
+        let _ = next_account_info(account_info_iter)?;
+        let _ = next_account_info(account_info_iter)?;
+        let _ = next_account_info(account_info_iter)?;
+        // loan
+        let loan = next_account_info(account_info_iter)?;
+        // alice's x source
+        let alice_x = next_account_info(account_info_iter)?;
+
+        if deadline <= Clock::get()?.unix_timestamp || amount_b.checked_add(interest).is_none() {
+            return Err(EscrowError::InvalidLoan.into());
+        }
+        let (loan_key, bump) = find_loan_address(program_id, escrow.key);
+        if *loan.key != loan_key {
+            return Err(EscrowError::InvalidLoan.into());
+        }
+
+        // a regular escrow of the collateral against the principal, funded by alice at once
+        Self::process_init_escrow(&accounts[..13], amount_a, amount_b, &EscrowTerms::default(), false, program_id)?;
+        Self::check_token_account(alice_x, alice.key, x_mint.key)?;
+        invoke(
+            &transfer(token_program.key, alice_x.key, x_vault.key, alice.key, &[], amount_a)?,
+            &[alice_x.clone(), x_vault.clone(), alice.clone(), token_program.clone()],
+        )?;
+
+        // rent and space
+        let rent = &Rent::from_account_info(rent_program)?;
+        let seeds: &[&[u8]] = &[LOAN_SEED, escrow.key.as_ref(), &[bump]];
+        Self::create_pda_account(alice, loan, system_program, rent, Loan::LEN, program_id, seeds)?;
+        let loan_data = Loan {
+            discriminator: Loan::DISCRIMINATOR,
+            escrow: *escrow.key,
+            x_mint: *x_mint.key,
+            y_mint: *accounts[3].key,
+            interest,
+            deadline,
+            bump,
+        };
+        loan_data.serialize(&mut &mut loan.data.borrow_mut()[..])?;
+
+        let mut data = escrow.data.borrow_mut();
+        let escrow_data = Escrow::load_mut(&mut data)?;
+        escrow_data.state = Escrow::LOAN_REQUESTED;
+
+        EscrowEvent {
+            kind: EscrowEventKind::Deposited,
+            escrow: *escrow.key,
+            party: *alice.key,
+            mint: *x_mint.key,
+            amount: amount_a,
+            old_state: 0,
+            new_state: escrow_data.state,
+        }.emit();
+        Self::return_status(escrow_data)?;
+
+        Ok(())
+    }
+
+    fn process_accept_loan(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // lender
+        let lender = next_account_info(account_info_iter)?;
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+        // loan
+        let loan = next_account_info(account_info_iter)?;
+        // lender's y source
+        let lender_y = next_account_info(account_info_iter)?;
+        // borrower's y destination
+        let borrower_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+
+        if !lender.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let (escrow_data, loan_data) = Self::load_loan(escrow, loan, program_id)?;
+        if *lender.key != escrow_data.party_b {
+            return Err(EscrowError::InvalidParty.into());
+        }
+        if escrow_data.state != Escrow::LOAN_REQUESTED {
+            return Err(EscrowError::InvalidLoanState.into());
+        }
+        if Clock::get()?.unix_timestamp >= loan_data.deadline {
+            return Err(EscrowError::LoanExpired.into());
+        }
+        if *token_program.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_token_account(borrower_y, &escrow_data.party_a, &loan_data.y_mint)?;
+
+        let principal = u64::from(escrow_data.size_b);
+        invoke(
+            &transfer(token_program.key, lender_y.key, borrower_y.key, lender.key, &[], principal)?,
+            &[lender_y.clone(), borrower_y.clone(), lender.clone(), token_program.clone()],
+        )?;
+
+        let mut data = escrow.data.borrow_mut();
+        let escrow_data = Escrow::load_mut(&mut data)?;
+        escrow_data.state = Escrow::LOAN_ACTIVE;
+
+        EscrowEvent {
+            kind: EscrowEventKind::Deposited,
+            escrow: *escrow.key,
+            party: *lender.key,
+            mint: loan_data.y_mint,
+            amount: principal,
+            old_state: Escrow::LOAN_REQUESTED,
+            new_state: escrow_data.state,
+        }.emit();
+        Self::return_status(escrow_data)?;
+
+        Ok(())
+    }
+
+    fn process_repay_loan(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // borrower, receives the rent of the loan
+        let borrower = next_account_info(account_info_iter)?;
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+        // loan
+        let loan = next_account_info(account_info_iter)?;
+        // x vault
+        let x_vault = next_account_info(account_info_iter)?;
+        // borrower's x destination
+        let borrower_x = next_account_info(account_info_iter)?;
+        // borrower's y source
+        let borrower_y = next_account_info(account_info_iter)?;
+        // lender's y destination
+        let lender_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+
+        if !borrower.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        // repaying stays open while paused, so that a pause cannot push a loan into default
+        let (escrow_data, loan_data) = Self::load_loan(escrow, loan, program_id)?;
+        if *borrower.key != escrow_data.party_a {
+            return Err(EscrowError::InvalidParty.into());
+        }
+        if escrow_data.state != Escrow::LOAN_ACTIVE {
+            return Err(EscrowError::InvalidLoanState.into());
+        }
+        if Clock::get()?.unix_timestamp >= loan_data.deadline {
+            return Err(EscrowError::LoanExpired.into());
+        }
+        if *x_vault.key != escrow_data.vault_x || *token_program.key != spl_token::id() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_token_account(lender_y, &escrow_data.party_b, &loan_data.y_mint)?;
+        let repayment = u64::from(escrow_data.size_b)
+            .checked_add(loan_data.interest)
+            .ok_or(EscrowError::InvalidLoan)?;
+
+        invoke(
+            &transfer(token_program.key, borrower_y.key, lender_y.key, borrower.key, &[], repayment)?,
+            &[borrower_y.clone(), lender_y.clone(), borrower.clone(), token_program.clone()],
+        )?;
+        let seeds: &[&[u8]] = &[
+            escrow_data.vault_x.as_ref(),
+            escrow_data.vault_y.as_ref(),
+            escrow_data.party_a.as_ref(),
+            escrow_data.seed_b(),
+            &[escrow_data.bump],
+        ];
+        Self::refund_vault(
+            x_vault, borrower_x, &escrow_data.party_a, escrow, token_program, escrow_data.size_a.into(), seeds,
+        )?;
+        Self::close_loan(escrow, loan, borrower)?;
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: *escrow.key,
+            party: escrow_data.party_b,
+            mint: loan_data.y_mint,
+            amount: repayment,
+            old_state: Escrow::LOAN_ACTIVE,
+            new_state: 0,
+        };
+        event.emit();
+        event.kind = EscrowEventKind::Withdrawn;
+        event.party = escrow_data.party_a;
+        event.mint = loan_data.x_mint;
+        event.amount = escrow_data.size_a.into();
+        event.emit();
+
+        Ok(())
+    }
+
+    fn process_claim_collateral(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // lender
+        let lender = next_account_info(account_info_iter)?;
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+        // loan
+        let loan = next_account_info(account_info_iter)?;
+        // x vault
+        let x_vault = next_account_info(account_info_iter)?;
+        // lender's x destination
+        let lender_x = next_account_info(account_info_iter)?;
+        // borrower, receives the rent of the loan
+        let borrower = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+
+        if !lender.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let (escrow_data, loan_data) = Self::load_loan(escrow, loan, program_id)?;
+        if *lender.key != escrow_data.party_b {
+            return Err(EscrowError::InvalidParty.into());
+        }
+        if escrow_data.state != Escrow::LOAN_ACTIVE {
+            return Err(EscrowError::InvalidLoanState.into());
+        }
+        if Clock::get()?.unix_timestamp < loan_data.deadline {
+            return Err(EscrowError::DeadlineNotReached.into());
+        }
+        if *x_vault.key != escrow_data.vault_x
+            || *borrower.key != escrow_data.party_a
+            || *token_program.key != spl_token::id()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let seeds: &[&[u8]] = &[
+            escrow_data.vault_x.as_ref(),
+            escrow_data.vault_y.as_ref(),
+            escrow_data.party_a.as_ref(),
+            escrow_data.seed_b(),
+            &[escrow_data.bump],
+        ];
+        Self::refund_vault(
+            x_vault, lender_x, &escrow_data.party_b, escrow, token_program, escrow_data.size_a.into(), seeds,
+        )?;
+        Self::close_loan(escrow, loan, borrower)?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: *escrow.key,
+            party: escrow_data.party_b,
+            mint: loan_data.x_mint,
+            amount: escrow_data.size_a.into(),
+            old_state: Escrow::LOAN_ACTIVE,
+            new_state: 0,
+        }.emit();
+
+        Ok(())
+    }
+
+    fn process_cancel_loan(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // borrower, receives the rent of the loan
+        let borrower = next_account_info(account_info_iter)?;
+        // escrow
+        let escrow = next_account_info(account_info_iter)?;
+        // loan
+        let loan = next_account_info(account_info_iter)?;
+        // x vault
+        let x_vault = next_account_info(account_info_iter)?;
+        // borrower's x destination
+        let borrower_x = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+
+        if !borrower.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        // like any refund of an unmatched deposit, allowed while paused
+        let (escrow_data, loan_data) = Self::load_loan(escrow, loan, program_id)?;
+        if *borrower.key != escrow_data.party_a {
+            return Err(EscrowError::InvalidParty.into());
+        }
+        if escrow_data.state != Escrow::LOAN_REQUESTED {
+            return Err(EscrowError::InvalidLoanState.into());
+        }
+        if *x_vault.key != escrow_data.vault_x || *token_program.key != spl_token::id() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let seeds: &[&[u8]] = &[
+            escrow_data.vault_x.as_ref(),
+            escrow_data.vault_y.as_ref(),
+            escrow_data.party_a.as_ref(),
+            escrow_data.seed_b(),
+            &[escrow_data.bump],
+        ];
+        Self::refund_vault(
+            x_vault, borrower_x, &escrow_data.party_a, escrow, token_program, escrow_data.size_a.into(), seeds,
+        )?;
+        Self::close_loan(escrow, loan, borrower)?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::Cancelled,
+            escrow: *escrow.key,
+            party: escrow_data.party_a,
+            mint: loan_data.x_mint,
+            amount: escrow_data.size_a.into(),
+            old_state: Escrow::LOAN_REQUESTED,
+            new_state: 0,
+        }.emit();
+
+        Ok(())
+    }
+
+    /// Loads a loan escrow and its loan, checking that the loan sits at its PDA.
+    fn load_loan(
+        escrow: &AccountInfo,
+        loan: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<(Escrow, Loan), ProgramError> {
+        if escrow.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let escrow_data = *Escrow::load(&escrow.data.borrow())?;
+        if loan.owner != program_id {
+            return Err(EscrowError::InvalidLoan.into());
+        }
+        let loan_data = Loan::load(&loan.data.borrow())?;
+        let loan_key =
+            Pubkey::create_program_address(&[LOAN_SEED, escrow.key.as_ref(), &[loan_data.bump]], program_id)?;
+        if loan_key != *loan.key || loan_data.escrow != *escrow.key {
+            return Err(EscrowError::InvalidLoan.into());
+        }
+        Ok((escrow_data, loan_data))
+    }
+
+    /// Ends a loan: the escrow goes back to empty, where it can be closed or traded again, and
+    /// the loan's rent goes to the borrower.
+    fn close_loan(escrow: &AccountInfo, loan: &AccountInfo, borrower: &AccountInfo) -> ProgramResult {
+        let mut data = escrow.data.borrow_mut();
+        let escrow_data = Escrow::load_mut(&mut data)?;
+        escrow_data.state = 0;
+        Self::return_status(escrow_data)?;
+        Self::close_program_account(loan, borrower)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_write_option(
+        accounts: &[AccountInfo],
+        amount: u64,
+        premium: u64,
+        strike: u64,
+        expiry: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // writer, also pays rent
+        let writer = next_account_info(account_info_iter)?;
+        // x mint
+        let x_mint = next_account_info(account_info_iter)?;
+        // y mint
+        let y_mint = next_account_info(account_info_iter)?;
+        // option
+        let option = next_account_info(account_info_iter)?;
+        // vault
+        let vault = next_account_info(account_info_iter)?;
+        // writer's x source
+        let writer_x = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // system program
+        let system_program = next_account_info(account_info_iter)?;
+        // rent program
+        let rent_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+        // x mint rule
+        let x_mint_rule = next_account_info(account_info_iter)?;
+        // y mint rule
+        let y_mint_rule = next_account_info(account_info_iter)?;
+
+        if !writer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if amount == 0 || strike == 0 || expiry <= Clock::get()?.unix_timestamp {
+            return Err(EscrowError::InvalidOption.into());
+        }
+        if x_mint.key == y_mint.key {
+            return Err(EscrowError::IdenticalMints.into());
+        }
+        if *token_program.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_mint(x_mint)?;
+        Self::check_mint(y_mint)?;
+        let config_data = Self::load_config_or_default(config, program_id)?;
+        if config_data.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        Self::check_mint_rule(x_mint_rule, x_mint.key, amount, &config_data, program_id)?;
+        Self::check_mint_rule(y_mint_rule, y_mint.key, strike, &config_data, program_id)?;
+        Self::check_token_account(writer_x, writer.key, x_mint.key)?;
+
+        let (option_key, bump) = find_option_address(program_id, writer.key, x_mint.key, y_mint.key);
+        let (vault_key, bump_vault) = find_option_vault_address(program_id, &option_key);
+        if *option.key != option_key || *vault.key != vault_key {
+            return Err(EscrowError::InvalidOption.into());
+        }
+        if option.data_len() != 0 {
+            return Err(EscrowError::AlreadyInitialized.into());
+        }
+
+        // rent and space
+        let rent = &Rent::from_account_info(rent_program)?;
+        let seeds: &[&[u8]] = &[OPTION_SEED, writer.key.as_ref(), x_mint.key.as_ref(), y_mint.key.as_ref(), &[bump]];
+        Self::create_pda_account(writer, option, system_program, rent, CoveredOption::LEN, program_id, seeds)?;
+        let seeds: &[&[u8]] = &[OPTION_VAULT_SEED, option.key.as_ref(), &[bump_vault]];
+        Self::create_token_vault(
+            writer, vault, x_mint, option, token_program, system_program, rent_program, seeds,
+        )?;
+
+        invoke(
+            &transfer(token_program.key, writer_x.key, vault.key, writer.key, &[], amount)?,
+            &[writer_x.clone(), vault.clone(), writer.clone(), token_program.clone()],
+        )?;
+
+        let option_data = CoveredOption {
+            discriminator: CoveredOption::DISCRIMINATOR,
+            writer: *writer.key,
+            owner: Pubkey::default(),
+            x_mint: *x_mint.key,
+            y_mint: *y_mint.key,
+            vault: *vault.key,
+            amount,
+            premium,
+            strike,
+            expiry,
+            bump,
+            bump_vault,
+        };
+        option_data.serialize(&mut &mut option.data.borrow_mut()[..])?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::EscrowCreated,
+            escrow: *option.key,
+            party: *writer.key,
+            mint: *x_mint.key,
+            amount,
+            old_state: 0,
+            new_state: 0,
+        }.emit();
+
+        Ok(())
+    }
+
+    fn process_buy_option(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // buyer
+        let buyer = next_account_info(account_info_iter)?;
+        // option
+        let option = next_account_info(account_info_iter)?;
+        // buyer's y source
+        let buyer_y = next_account_info(account_info_iter)?;
+        // writer's y destination
+        let writer_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+
+        if !buyer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let mut option_data = Self::load_option(option, program_id)?;
+        if option_data.is_sold() {
+            return Err(EscrowError::OptionAlreadySold.into());
+        }
+        if Clock::get()?.unix_timestamp >= option_data.expiry {
+            return Err(EscrowError::OptionExpired.into());
+        }
+        if *buyer.key == option_data.writer {
+            return Err(EscrowError::IdenticalParties.into());
+        }
+        if *token_program.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_token_account(writer_y, &option_data.writer, &option_data.y_mint)?;
+
+        if option_data.premium != 0 {
+            invoke(
+                &transfer(token_program.key, buyer_y.key, writer_y.key, buyer.key, &[], option_data.premium)?,
+                &[buyer_y.clone(), writer_y.clone(), buyer.clone(), token_program.clone()],
+            )?;
+        }
+        option_data.owner = *buyer.key;
+        option_data.serialize(&mut &mut option.data.borrow_mut()[..])?;
+
+        EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: *option.key,
+            party: *buyer.key,
+            mint: option_data.y_mint,
+            amount: option_data.premium,
+            old_state: 0,
+            new_state: 0,
+        }.emit();
+
+        Ok(())
+    }
+
+    fn process_transfer_option(
+        accounts: &[AccountInfo],
+        new_owner: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // owner
+        let owner = next_account_info(account_info_iter)?;
+        // option
+        let option = next_account_info(account_info_iter)?;
+
+        if !owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut option_data = Self::load_option(option, program_id)?;
+        if !option_data.is_sold() || *owner.key != option_data.owner {
+            return Err(EscrowError::Unauthorized.into());
+        }
+        // the default key marks an unsold option
+        if new_owner == Pubkey::default() {
+            return Err(EscrowError::InvalidOption.into());
+        }
+        option_data.owner = new_owner;
+        option_data.serialize(&mut &mut option.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    fn process_exercise_option(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // owner
+        let owner = next_account_info(account_info_iter)?;
+        // option
+        let option = next_account_info(account_info_iter)?;
+        // vault
+        let vault = next_account_info(account_info_iter)?;
+        // owner's x destination
+        let owner_x = next_account_info(account_info_iter)?;
+        // owner's y source
+        let owner_y = next_account_info(account_info_iter)?;
+        // writer, receives the rent
+        let writer = next_account_info(account_info_iter)?;
+        // writer's y destination
+        let writer_y = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+        // config
+        let config = next_account_info(account_info_iter)?;
+
+        if !owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Self::load_config_or_default(config, program_id)?.paused {
+            return Err(EscrowError::Paused.into());
+        }
+        let option_data = Self::load_option(option, program_id)?;
+        if *vault.key != option_data.vault
+            || *writer.key != option_data.writer
+            || *token_program.key != spl_token::id()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !option_data.is_sold() || *owner.key != option_data.owner {
+            return Err(EscrowError::Unauthorized.into());
+        }
+        if Clock::get()?.unix_timestamp >= option_data.expiry {
+            return Err(EscrowError::OptionExpired.into());
+        }
+        Self::check_token_account(owner_x, owner.key, &option_data.x_mint)?;
+        Self::check_token_account(writer_y, writer.key, &option_data.y_mint)?;
+
+        invoke(
+            &transfer(token_program.key, owner_y.key, writer_y.key, owner.key, &[], option_data.strike)?,
+            &[owner_y.clone(), writer_y.clone(), owner.clone(), token_program.clone()],
+        )?;
+        let seeds: &[&[u8]] = &[
+            OPTION_SEED,
+            option_data.writer.as_ref(),
+            option_data.x_mint.as_ref(),
+            option_data.y_mint.as_ref(),
+            &[option_data.bump],
+        ];
+        Self::close_auction_vault(vault, owner_x, writer, option, token_program, seeds)?;
+        Self::close_program_account(option, writer)?;
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Settled,
+            escrow: *option.key,
+            party: *owner.key,
+            mint: option_data.x_mint,
+            amount: option_data.amount,
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+        event.kind = EscrowEventKind::Closed;
+        event.party = option_data.writer;
+        event.mint = Pubkey::default();
+        event.amount = 0;
+        event.emit();
+
+        Ok(())
+    }
+
+    fn process_reclaim_option(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        // writer, receives the rent
+        let writer = next_account_info(account_info_iter)?;
+        // option
+        let option = next_account_info(account_info_iter)?;
+        // vault
+        let vault = next_account_info(account_info_iter)?;
+        // writer's x destination
+        let writer_x = next_account_info(account_info_iter)?;
+        // token program
+        let token_program = next_account_info(account_info_iter)?;
+
+        if !writer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let option_data = Self::load_option(option, program_id)?;
+        if *vault.key != option_data.vault || *token_program.key != spl_token::id() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *writer.key != option_data.writer {
+            return Err(EscrowError::Unauthorized.into());
+        }
+        // a sold option belongs to its owner until it expires; reclaims stay open while paused
+        if option_data.is_sold() && Clock::get()?.unix_timestamp < option_data.expiry {
+            return Err(EscrowError::DeadlineNotReached.into());
+        }
+        Self::check_token_account(writer_x, writer.key, &option_data.x_mint)?;
+
+        let seeds: &[&[u8]] = &[
+            OPTION_SEED,
+            option_data.writer.as_ref(),
+            option_data.x_mint.as_ref(),
+            option_data.y_mint.as_ref(),
+            &[option_data.bump],
+        ];
+        Self::close_auction_vault(vault, writer_x, writer, option, token_program, seeds)?;
+        Self::close_program_account(option, writer)?;
+
+        let mut event = EscrowEvent {
+            kind: EscrowEventKind::Cancelled,
+            escrow: *option.key,
+            party: option_data.writer,
+            mint: option_data.x_mint,
+            amount: option_data.amount,
+            old_state: 0,
+            new_state: 0,
+        };
+        event.emit();
+        event.kind = EscrowEventKind::Closed;
+        event.mint = Pubkey::default();
+        event.amount = 0;
+        event.emit();
+
+        Ok(())
+    }
+
+    /// Loads a covered option and checks that it sits at its PDA.
+    fn load_option(option: &AccountInfo, program_id: &Pubkey) -> Result<CoveredOption, ProgramError> {
+        if option.owner != program_id {
+            return Err(EscrowError::InvalidOption.into());
+        }
+        let option_data = CoveredOption::load(&option.data.borrow())?;
+        let option_key = Pubkey::create_program_address(
+            &[
+                OPTION_SEED,
+                option_data.writer.as_ref(),
+                option_data.x_mint.as_ref(),
+                option_data.y_mint.as_ref(),
+                &[option_data.bump],
+            ],
+            program_id,
+        )?;
+        if option_key != *option.key {
+            return Err(EscrowError::InvalidOption.into());
+        }
+        Ok(option_data)
+    }
+
+    /// Checks that the instruction before this one is an ed25519 program instruction verifying a
+    /// single signature by the maker over `order.message(program_id)`, with all of its data in that
+    /// instruction.
+    fn check_order_signature(instructions: &AccountInfo, order: &Order, program_id: &Pubkey) -> ProgramResult {
+        let current = load_current_index_checked(instructions)?;
+        if current == 0 {
+            return Err(EscrowError::InvalidOrderSignature.into());
+        }
+        let verify = load_instruction_at_checked(current as usize - 1, instructions)?;
+        if verify.program_id != ed25519_program::id() || verify.data.len() < 2 + 14 || verify.data[0] != 1 {
+            return Err(EscrowError::InvalidOrderSignature.into());
+        }
+        let offset = |i: usize| u16::from_le_bytes([verify.data[2 + 2 * i], verify.data[3 + 2 * i]]) as usize;
+        let (public_key_offset, message_offset, message_size) = (offset(2), offset(4), offset(5));
+        // u16::MAX points the signature, public key and message at the ed25519 instruction itself
+        if [offset(1), offset(3), offset(6)].iter().any(|&index| index != u16::MAX as usize) {
+            return Err(EscrowError::InvalidOrderSignature.into());
+        }
+        let public_key = verify.data.get(public_key_offset..public_key_offset + 32);
+        let message = verify.data.get(message_offset..message_offset + message_size);
+        if public_key != Some(order.maker.as_ref()) || message != Some(&order.message(program_id)?[..]) {
+            return Err(EscrowError::InvalidOrderSignature.into());
+        }
+        Ok(())
+    }
+
+    /// Checks that `source` is `owner`'s account of `mint` and delegates exactly `amount` to
+    /// `delegate`, which it still holds.
+    fn check_delegated_source(
+        source: &AccountInfo,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        delegate: &Pubkey,
+        amount: u64,
+    ) -> ProgramResult {
+        let source_data = Self::check_token_account(source, owner, mint)?;
+        if source_data.delegate != COption::Some(*delegate) || source_data.delegated_amount != amount {
+            return Err(EscrowError::InvalidDelegation.into());
+        }
+        if source_data.amount < amount {
+            return Err(EscrowError::InsufficientDelegatedFunds.into());
+        }
+        Ok(())
+    }
+
+    /// Checks that `account` is a token account of `mint` owned by `owner`.
+    fn check_token_account(account: &AccountInfo, owner: &Pubkey, mint: &Pubkey) -> Result<Account, ProgramError> {
+        if *account.owner != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let account_data = Account::unpack(&account.data.borrow())?;
+        if account_data.owner != *owner || account_data.mint != *mint {
+            return Err(EscrowError::InvalidParty.into());
+        }
+        Ok(account_data)
+    }
+}
@@ -1,23 +1,1561 @@
 use solana_program::{
+    entrypoint::ProgramResult,
+    hash::hashv,
     program_error::ProgramError,
     pubkey::Pubkey,
 };
+use crate::error::EscrowError;
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use std::{
+    cmp::Ordering,
+    convert::{TryFrom, TryInto},
+};
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+/// Checks the leading 8-byte discriminator that every account owned by this program starts
+/// with, so that one account type can never be read as another.
+pub fn check_account_type(data: &[u8], discriminator: &[u8; 8]) -> Result<(), ProgramError> {
+    if data.len() < 8 || data[..8] != discriminator[..] {
+        return Err(EscrowError::InvalidAccountType.into());
+    }
+    Ok(())
+}
+
+/// Little-endian `u64` with an alignment of 1, so that `Escrow` can be cast straight from
+/// account data.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable)]
+pub struct PodU64([u8; 8]);
+
+impl From<u64> for PodU64 {
+    fn from(value: u64) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodU64> for u64 {
+    fn from(value: PodU64) -> Self {
+        Self::from_le_bytes(value.0)
+    }
+}
+
+/// Little-endian `u32` with an alignment of 1, encoded by borsh like a `u32`.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable, BorshSerialize, BorshDeserialize)]
+pub struct PodU32([u8; 4]);
+
+impl From<u32> for PodU32 {
+    fn from(value: u32) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodU32> for u32 {
+    fn from(value: PodU32) -> Self {
+        Self::from_le_bytes(value.0)
+    }
+}
+
+/// Little-endian `i64` with an alignment of 1, encoded by borsh like an `i64`.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable, BorshSerialize, BorshDeserialize)]
+pub struct PodI64([u8; 8]);
+
+impl From<i64> for PodI64 {
+    fn from(value: i64) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodI64> for i64 {
+    fn from(value: PodI64) -> Self {
+        Self::from_le_bytes(value.0)
+    }
+}
+
+/// Linear release of an escrow leg to the counterparty between `start` and `end`, with nothing
+/// released before `cliff`. All three are unix timestamps, and the all-zero schedule releases
+/// the whole leg at once.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable, BorshSerialize, BorshDeserialize)]
+pub struct VestingSchedule {
+    pub cliff: PodI64,
+    pub start: PodI64,
+    pub end: PodI64,
+}
+
+impl VestingSchedule {
+    pub fn new(cliff: i64, start: i64, end: i64) -> Self {
+        Self { cliff: cliff.into(), start: start.into(), end: end.into() }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let (cliff, start, end) = (i64::from(self.cliff), i64::from(self.start), i64::from(self.end));
+        start <= cliff && cliff <= end
+    }
+
+    pub fn is_immediate(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Part of `total` released at `now`, rounded down.
+    pub fn vested(&self, total: u64, now: i64) -> u64 {
+        let (cliff, start, end) = (i64::from(self.cliff), i64::from(self.start), i64::from(self.end));
+        if now >= end {
+            return total;
+        }
+        if now < cliff {
+            return 0;
+        }
+        // start <= cliff <= now < end
+        let elapsed = (now as i128 - start as i128) as u128;
+        let duration = (end as i128 - start as i128) as u128;
+        (total as u128 * elapsed / duration) as u64
+    }
+}
+
+/// How a `Condition` compares the value it reads with its threshold, as `value <op> threshold`.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum Comparator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparator {
+    fn from_u8(value: u8) -> Option<Self> {
+        [Self::Eq, Self::Ne, Self::Lt, Self::Le, Self::Gt, Self::Ge].get(usize::from(value)).copied()
+    }
+
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering == Ordering::Equal,
+            Self::Ne => ordering != Ordering::Equal,
+            Self::Lt => ordering == Ordering::Less,
+            Self::Le => ordering != Ordering::Greater,
+            Self::Gt => ordering == Ordering::Greater,
+            Self::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// Typed value read by a `Condition`, stored little-endian like borsh does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConditionValue {
+    U64(u64),
+    I64(i64),
+    Bool(bool),
+    Pubkey(Pubkey),
+}
+
+/// Gate on the release of an escrow: a value of `value_type` read at `offset` in the data of
+/// `account`, which has to be owned by `owner`, compared with `threshold`. The all-zero
+/// condition never gates anything.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable, BorshSerialize, BorshDeserialize)]
+pub struct Condition {
+    pub account: Pubkey,
+    pub owner: Pubkey,
+    pub offset: PodU32,
+    pub value_type: u8,
+    pub comparator: u8,
+    /// the threshold encoded like the value, zero padded
+    pub threshold: [u8; 32],
+}
+
+impl Condition {
+    pub const U64: u8 = 1;
+    pub const I64: u8 = 2;
+    pub const BOOL: u8 = 3;
+    pub const PUBKEY: u8 = 4;
+
+    pub fn new(account: Pubkey, owner: Pubkey, offset: u32, comparator: Comparator, threshold: ConditionValue) -> Self {
+        let mut encoded = [0; 32];
+        let value_type = match threshold {
+            ConditionValue::U64(value) => {
+                encoded[..8].copy_from_slice(&value.to_le_bytes());
+                Self::U64
+            }
+            ConditionValue::I64(value) => {
+                encoded[..8].copy_from_slice(&value.to_le_bytes());
+                Self::I64
+            }
+            ConditionValue::Bool(value) => {
+                encoded[0] = u8::from(value);
+                Self::BOOL
+            }
+            ConditionValue::Pubkey(value) => {
+                encoded.copy_from_slice(value.as_ref());
+                Self::PUBKEY
+            }
+        };
+        Self { account, owner, offset: offset.into(), value_type, comparator: comparator as u8, threshold: encoded }
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the condition reads a known type and compares it in a way that type supports.
+    /// Booleans and pubkeys only compare for (in)equality.
+    pub fn is_valid(&self) -> bool {
+        matches!(
+            (self.decode(&self.threshold), Comparator::from_u8(self.comparator)),
+            (Some(ConditionValue::U64(_) | ConditionValue::I64(_)), Some(_))
+                | (Some(_), Some(Comparator::Eq | Comparator::Ne))
+        )
+    }
+
+    /// Whether `data` of the condition account satisfies the condition. Data too short to hold
+    /// the value, or a boolean that is neither 0 nor 1, does not.
+    pub fn is_met(&self, data: &[u8]) -> bool {
+        let value = data.get(u32::from(self.offset) as usize..).and_then(|bytes| self.decode(bytes));
+        let (value, threshold, comparator) =
+            match (value, self.decode(&self.threshold), Comparator::from_u8(self.comparator)) {
+                (Some(value), Some(threshold), Some(comparator)) => (value, threshold, comparator),
+                _ => return false,
+            };
+        let ordering = match (value, threshold) {
+            (ConditionValue::U64(value), ConditionValue::U64(threshold)) => value.cmp(&threshold),
+            (ConditionValue::I64(value), ConditionValue::I64(threshold)) => value.cmp(&threshold),
+            (ConditionValue::Bool(value), ConditionValue::Bool(threshold)) => value.cmp(&threshold),
+            (ConditionValue::Pubkey(value), ConditionValue::Pubkey(threshold)) => value.cmp(&threshold),
+            _ => return false,
+        };
+        comparator.accepts(ordering)
+    }
+
+    /// Reads a value of the condition's type from the start of `bytes`.
+    fn decode(&self, bytes: &[u8]) -> Option<ConditionValue> {
+        let word = || <[u8; 8]>::try_from(bytes.get(..8)?).ok();
+        match self.value_type {
+            Self::U64 => Some(ConditionValue::U64(u64::from_le_bytes(word()?))),
+            Self::I64 => Some(ConditionValue::I64(i64::from_le_bytes(word()?))),
+            Self::BOOL => match bytes.first()? {
+                0 => Some(ConditionValue::Bool(false)),
+                1 => Some(ConditionValue::Bool(true)),
+                _ => None,
+            },
+            Self::PUBKEY => Some(ConditionValue::Pubkey(Pubkey::new_from_array(bytes.get(..32)?.try_into().ok()?))),
+            _ => None,
+        }
+    }
+}
+
+/// Optional terms of an escrow on top of its sizes. The default terms release both legs at
+/// once and without a condition, as `InitEscrow` does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct EscrowTerms {
+    pub vesting_a: VestingSchedule,
+    pub vesting_b: VestingSchedule,
+    pub condition: Condition,
+}
+
+/// Read in place from the account data, laid out without padding so that it matches the
+/// borsh encoding of the same fields.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
 pub struct Escrow {
+    pub discriminator: [u8; 8],
+    pub version: u8,
     pub party_a: Pubkey,
     pub party_b: Pubkey,
-    pub size_a: u64,
-    pub size_b: u64, 
+    pub size_a: PodU64,
+    pub size_b: PodU64,
     pub vault_x: Pubkey,
     pub vault_y: Pubkey,
     pub state: u8,
     pub bump: u8,
     pub bump_vault_x: u8,
     pub bump_vault_y: u8,
+    /// paid the rent of the escrow and its vaults, and gets it back on close
+    pub rent_payer: Pubkey,
+    /// release of alice's x to bob once both legs are deposited
+    pub vesting_a: VestingSchedule,
+    /// release of bob's y to alice once both legs are deposited
+    pub vesting_b: VestingSchedule,
+    /// x bob has withdrawn so far
+    pub claimed_a: PodU64,
+    /// y alice has withdrawn so far
+    pub claimed_b: PodU64,
+    /// has to hold for either leg to be released to the counterparty
+    pub condition: Condition,
+    /// 1 when the escrow was created without bob, for the first taker to fill. The escrow's
+    /// addresses and signer seeds keep `OPEN_ESCROW_SEED` in his place after his deposit records
+    /// him in `party_b`.
+    pub open_b: u8,
 }
 
 impl Escrow {
+    pub const DISCRIMINATOR: [u8; 8] = *b"escrow\0\0";
+    pub const VERSION: u8 = 4;
+    pub const LEN: usize = std::mem::size_of::<Self>();
+    /// State of a loan escrow holding the borrower's collateral until the lender accepts.
+    pub const LOAN_REQUESTED: u8 = 6;
+    /// State of a loan escrow whose principal has been lent out.
+    pub const LOAN_ACTIVE: u8 = 7;
+
+    fn check(data: &[u8]) -> ProgramResult {
+        if [EscrowV0::LEN, EscrowV1::LEN, EscrowV2::LEN, EscrowV3::LEN].contains(&data.len()) {
+            return Err(EscrowError::AccountNeedsMigration.into());
+        }
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if data[8] != Self::VERSION {
+            return Err(EscrowError::UnsupportedAccountVersion.into());
+        }
+        Ok(())
+    }
+
+    /// Borrows an escrow from account data, checking its discriminator and version.
+    pub fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        Self::check(data)?;
+        Ok(bytemuck::from_bytes(data))
+    }
+
+    /// Mutable counterpart of `load`.
+    pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        Self::check(data)?;
+        Ok(bytemuck::from_bytes_mut(data))
+    }
+
+    /// Upgrades a v0 escrow to the current layout.
+    pub fn from_v0(v0: EscrowV0) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            version: Self::VERSION,
+            party_a: v0.party_a,
+            party_b: v0.party_b,
+            size_a: v0.size_a.into(),
+            size_b: v0.size_b.into(),
+            vault_x: v0.vault_x,
+            vault_y: v0.vault_y,
+            state: v0.state,
+            bump: v0.bump,
+            bump_vault_x: v0.bump_vault_x,
+            bump_vault_y: v0.bump_vault_y,
+            // alice always paid rent before v2
+            rent_payer: v0.party_a,
+            vesting_a: VestingSchedule::default(),
+            vesting_b: VestingSchedule::default(),
+            claimed_a: 0.into(),
+            claimed_b: 0.into(),
+            condition: Condition::default(),
+            open_b: 0,
+        }
+    }
+
+    /// Upgrades a v1 escrow to the current layout.
+    pub fn from_v1(v1: EscrowV1) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            version: Self::VERSION,
+            party_a: v1.party_a,
+            party_b: v1.party_b,
+            size_a: v1.size_a,
+            size_b: v1.size_b,
+            vault_x: v1.vault_x,
+            vault_y: v1.vault_y,
+            state: v1.state,
+            bump: v1.bump,
+            bump_vault_x: v1.bump_vault_x,
+            bump_vault_y: v1.bump_vault_y,
+            rent_payer: v1.party_a,
+            vesting_a: VestingSchedule::default(),
+            vesting_b: VestingSchedule::default(),
+            claimed_a: 0.into(),
+            claimed_b: 0.into(),
+            condition: Condition::default(),
+            open_b: 0,
+        }
+    }
+
+    /// Upgrades a v2 escrow to the current layout. Escrows before v3 released both legs at once.
+    pub fn from_v2(v2: EscrowV2) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            version: Self::VERSION,
+            party_a: v2.party_a,
+            party_b: v2.party_b,
+            size_a: v2.size_a,
+            size_b: v2.size_b,
+            vault_x: v2.vault_x,
+            vault_y: v2.vault_y,
+            state: v2.state,
+            bump: v2.bump,
+            bump_vault_x: v2.bump_vault_x,
+            bump_vault_y: v2.bump_vault_y,
+            rent_payer: v2.rent_payer,
+            vesting_a: VestingSchedule::default(),
+            vesting_b: VestingSchedule::default(),
+            claimed_a: 0.into(),
+            claimed_b: 0.into(),
+            condition: Condition::default(),
+            open_b: 0,
+        }
+    }
+
+    /// Upgrades a v3 escrow to the current layout. Escrows before v4 were unconditional.
+    pub fn from_v3(v3: EscrowV3) -> Self {
+        Self {
+            discriminator: Self::DISCRIMINATOR,
+            version: Self::VERSION,
+            party_a: v3.party_a,
+            party_b: v3.party_b,
+            size_a: v3.size_a,
+            size_b: v3.size_b,
+            vault_x: v3.vault_x,
+            vault_y: v3.vault_y,
+            state: v3.state,
+            bump: v3.bump,
+            bump_vault_x: v3.bump_vault_x,
+            bump_vault_y: v3.bump_vault_y,
+            rent_payer: v3.rent_payer,
+            vesting_a: v3.vesting_a,
+            vesting_b: v3.vesting_b,
+            claimed_a: v3.claimed_a,
+            claimed_b: v3.claimed_b,
+            condition: Condition::default(),
+            open_b: 0,
+        }
+    }
+
+    /// Bob's seed in the escrow's addresses and signer seeds.
+    pub fn seed_b(&self) -> &[u8] {
+        if self.open_b != 0 {
+            OPEN_ESCROW_SEED
+        }
+        else {
+            self.party_b.as_ref()
+        }
+    }
+
+    /// Whether the escrow holds a loan, which only the loan instructions move out of.
+    pub fn is_loan(&self) -> bool {
+        self.state == Self::LOAN_REQUESTED || self.state == Self::LOAN_ACTIVE
+    }
+
+    /// Summary of the escrow derived from its state.
+    pub fn status(&self) -> EscrowStatus {
+        // what is left of each leg after the counterparty's vested withdrawals
+        let size_a = u64::from(self.size_a).saturating_sub(self.claimed_a.into());
+        let size_b = u64::from(self.size_b).saturating_sub(self.claimed_b.into());
+        let (vault_x_amount, vault_y_amount) = match self.state {
+            1 | 4 | Self::LOAN_REQUESTED | Self::LOAN_ACTIVE => (size_a, 0),
+            2 | 5 => (0, size_b),
+            3 => (size_a, size_b),
+            _ => (0, 0),
+        };
+        EscrowStatus {
+            state: self.state,
+            vault_x_amount,
+            vault_y_amount,
+            can_settle: (3..=5).contains(&self.state),
+        }
+    }
+}
+
+/// Escrow layout before accounts carried a discriminator and version. Only read by `Migrate`.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct EscrowV0 {
+    pub party_a: Pubkey,
+    pub party_b: Pubkey,
+    pub size_a: u64,
+    pub size_b: u64,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub state: u8,
+    pub bump: u8,
+    pub bump_vault_x: u8,
+    pub bump_vault_y: u8,
+}
+
+impl EscrowV0 {
     pub const LEN: usize = 148;
-}
\ No newline at end of file
+}
+
+/// Escrow layout before the rent payer was stored. Only read by `Migrate`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct EscrowV1 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub party_a: Pubkey,
+    pub party_b: Pubkey,
+    pub size_a: PodU64,
+    pub size_b: PodU64,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub state: u8,
+    pub bump: u8,
+    pub bump_vault_x: u8,
+    pub bump_vault_y: u8,
+}
+
+impl EscrowV1 {
+    pub const VERSION: u8 = 1;
+    pub const LEN: usize = std::mem::size_of::<Self>();
+
+    pub fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        check_account_type(data, &Escrow::DISCRIMINATOR)?;
+        if data.len() != Self::LEN || data[8] != Self::VERSION {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(bytemuck::from_bytes(data))
+    }
+}
+
+/// Escrow layout before legs could vest. Only read by `Migrate`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct EscrowV2 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub party_a: Pubkey,
+    pub party_b: Pubkey,
+    pub size_a: PodU64,
+    pub size_b: PodU64,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub state: u8,
+    pub bump: u8,
+    pub bump_vault_x: u8,
+    pub bump_vault_y: u8,
+    pub rent_payer: Pubkey,
+}
+
+impl EscrowV2 {
+    pub const VERSION: u8 = 2;
+    pub const LEN: usize = std::mem::size_of::<Self>();
+
+    pub fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        check_account_type(data, &Escrow::DISCRIMINATOR)?;
+        if data.len() != Self::LEN || data[8] != Self::VERSION {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(bytemuck::from_bytes(data))
+    }
+}
+
+/// Escrow layout before releases could be conditional. Only read by `Migrate`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct EscrowV3 {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub party_a: Pubkey,
+    pub party_b: Pubkey,
+    pub size_a: PodU64,
+    pub size_b: PodU64,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub state: u8,
+    pub bump: u8,
+    pub bump_vault_x: u8,
+    pub bump_vault_y: u8,
+    pub rent_payer: Pubkey,
+    pub vesting_a: VestingSchedule,
+    pub vesting_b: VestingSchedule,
+    pub claimed_a: PodU64,
+    pub claimed_b: PodU64,
+}
+
+impl EscrowV3 {
+    pub const VERSION: u8 = 3;
+    pub const LEN: usize = std::mem::size_of::<Self>();
+
+    pub fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        check_account_type(data, &Escrow::DISCRIMINATOR)?;
+        if data.len() != Self::LEN || data[8] != Self::VERSION {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(bytemuck::from_bytes(data))
+    }
+}
+
+// v1 only prepends the discriminator and version to the v0 fields, v2 appends the rent payer,
+// v3 the vesting schedules and claimed amounts and v4 the condition and the open taker flag
+const _: () = assert!(EscrowV1::LEN == EscrowV0::LEN + 8 + 1);
+const _: () = assert!(EscrowV2::LEN == EscrowV1::LEN + 32);
+const _: () = assert!(EscrowV3::LEN == EscrowV2::LEN + 2 * 24 + 2 * 8);
+const _: () = assert!(Escrow::LEN == EscrowV3::LEN + 2 * 32 + 4 + 2 + 32 + 1);
+const _: () = assert!(std::mem::align_of::<Escrow>() == 1);
+
+/// Returned through `set_return_data` by every escrow instruction.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct EscrowStatus {
+    pub state: u8,
+    pub vault_x_amount: u64,
+    pub vault_y_amount: u64,
+    /// both legs are deposited and can be withdrawn by the counterparty
+    pub can_settle: bool,
+}
+
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Takes bob's place in the addresses of an escrow that any taker can fill. It is shorter than
+/// a key, so these addresses cannot be those of an escrow with a bob.
+pub const OPEN_ESCROW_SEED: &[u8] = b"open";
+
+/// Vault of `mint` for the escrow between `alice` and `bob`.
+pub fn find_vault_address(program_id: &Pubkey, mint: &Pubkey, alice: &Pubkey, bob: &Pubkey) -> (Pubkey, u8) {
+    find_vault_address_with_seed(program_id, mint, alice, bob.as_ref())
+}
+
+/// Vault of `mint` for `alice`'s escrow that any taker can fill.
+pub fn find_open_vault_address(program_id: &Pubkey, mint: &Pubkey, alice: &Pubkey) -> (Pubkey, u8) {
+    find_vault_address_with_seed(program_id, mint, alice, OPEN_ESCROW_SEED)
+}
+
+pub(crate) fn find_vault_address_with_seed(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    alice: &Pubkey,
+    bob_seed: &[u8],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[mint.as_ref(), alice.as_ref(), bob_seed], program_id)
+}
+
+pub fn find_escrow_address(
+    program_id: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    alice: &Pubkey,
+    bob: &Pubkey,
+) -> (Pubkey, u8) {
+    find_escrow_address_with_seed(program_id, x_vault, y_vault, alice, bob.as_ref())
+}
+
+/// Escrow between the vaults of `find_open_vault_address`, for any taker to fill.
+pub fn find_open_escrow_address(
+    program_id: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    alice: &Pubkey,
+) -> (Pubkey, u8) {
+    find_escrow_address_with_seed(program_id, x_vault, y_vault, alice, OPEN_ESCROW_SEED)
+}
+
+pub(crate) fn find_escrow_address_with_seed(
+    program_id: &Pubkey,
+    x_vault: &Pubkey,
+    y_vault: &Pubkey,
+    alice: &Pubkey,
+    bob_seed: &[u8],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[x_vault.as_ref(), y_vault.as_ref(), alice.as_ref(), bob_seed], program_id)
+}
+
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+pub struct Config {
+    pub discriminator: [u8; 8],
+    pub admin: Pubkey,
+    pub guardian: Pubkey,
+    pub paused: bool,
+    /// When set, only mints with an allowing `MintRule` can be escrowed
+    pub allowlist_only: bool,
+    pub bump: u8,
+}
+
+impl Config {
+    pub const DISCRIMINATOR: [u8; 8] = *b"config\0\0";
+    pub const LEN: usize = 75;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+}
+
+pub const MINT_RULE_SEED: &[u8] = b"mint_rule";
+
+pub fn find_mint_rule_address(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MINT_RULE_SEED, mint.as_ref()], program_id)
+}
+
+/// Registry entry for a single mint, stored at the PDA of `[MINT_RULE_SEED, mint]`.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct MintRule {
+    pub discriminator: [u8; 8],
+    pub mint: Pubkey,
+    pub allowed: bool,
+    pub min_size: u64,
+    /// 0 means no maximum
+    pub max_size: u64,
+    pub bump: u8,
+}
+
+impl MintRule {
+    pub const DISCRIMINATOR: [u8; 8] = *b"mintrule";
+    pub const LEN: usize = 58;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+}
+
+/// Escrow terms signed off-chain by the maker, who gives `size_a` of `x_mint` for `size_b` of
+/// `y_mint`. The maker signs `Order::message`, and anyone holding the signature can fill it
+/// with `FillOrder`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Order {
+    pub maker: Pubkey,
+    pub x_mint: Pubkey,
+    pub y_mint: Pubkey,
+    pub size_a: u64,
+    pub size_b: u64,
+    /// unix timestamp after which the order can no longer be filled
+    pub expiry: i64,
+    pub nonce: u64,
+}
+
+/// Starts every signed order, so that a maker's signature over one cannot pass for any other
+/// message they sign with the same key.
+pub const ORDER_MESSAGE_PREFIX: &[u8] = b"solana-escrow order";
+
+impl Order {
+    /// The bytes the maker signs: `ORDER_MESSAGE_PREFIX`, the ID of the program that fills the
+    /// order, then its borsh encoding.
+    pub fn message(&self, program_id: &Pubkey) -> Result<Vec<u8>, ProgramError> {
+        let mut message = [ORDER_MESSAGE_PREFIX, program_id.as_ref()].concat();
+        self.serialize(&mut message)?;
+        Ok(message)
+    }
+}
+
+/// Makers approve this PDA as delegate of their x accounts, and `FillOrder` signs with it.
+pub const ORDER_AUTHORITY_SEED: &[u8] = b"order_authority";
+
+pub fn find_order_authority_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ORDER_AUTHORITY_SEED], program_id)
+}
+
+pub const ORDER_NONCES_SEED: &[u8] = b"order_nonces";
+
+/// Nonce bitmap of `maker` covering `nonce`, stored at the PDA of
+/// `[ORDER_NONCES_SEED, maker, nonce / OrderNonces::NONCES]`.
+pub fn find_order_nonces_address(program_id: &Pubkey, maker: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    let page = nonce / OrderNonces::NONCES;
+    Pubkey::find_program_address(&[ORDER_NONCES_SEED, maker.as_ref(), &page.to_le_bytes()], program_id)
+}
+
+/// Marks the filled orders of a maker, one bit per nonce, for a range of `NONCES` nonces.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct OrderNonces {
+    pub discriminator: [u8; 8],
+    pub maker: Pubkey,
+    /// nonce / NONCES of every nonce in this account
+    pub page: PodU64,
+    pub bump: u8,
+    pub used: [u8; 256],
+}
+
+impl OrderNonces {
+    pub const DISCRIMINATOR: [u8; 8] = *b"nonces\0\0";
+    pub const LEN: usize = std::mem::size_of::<Self>();
+    pub const NONCES: u64 = 256 * 8;
+
+    fn check(data: &[u8]) -> ProgramResult {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        if data.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+
+    pub fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        Self::check(data)?;
+        Ok(bytemuck::from_bytes(data))
+    }
+
+    pub fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        Self::check(data)?;
+        Ok(bytemuck::from_bytes_mut(data))
+    }
+
+    fn bit(nonce: u64) -> (usize, u8) {
+        let index = (nonce % Self::NONCES) as usize;
+        (index / 8, 1 << (index % 8))
+    }
+
+    pub fn is_used(&self, nonce: u64) -> bool {
+        let (byte, mask) = Self::bit(nonce);
+        self.used[byte] & mask != 0
+    }
+
+    pub fn set_used(&mut self, nonce: u64) {
+        let (byte, mask) = Self::bit(nonce);
+        self.used[byte] |= mask;
+    }
+}
+
+pub const MAKER_STATE_SEED: &[u8] = b"maker";
+
+pub fn find_maker_state_address(program_id: &Pubkey, maker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MAKER_STATE_SEED, maker.as_ref()], program_id)
+}
+
+/// Order settings of a maker, stored at the PDA of `[MAKER_STATE_SEED, maker]`. A maker
+/// without one has never invalidated any orders.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+pub struct MakerState {
+    pub discriminator: [u8; 8],
+    pub maker: Pubkey,
+    /// orders with a lower nonce can no longer be filled
+    pub min_valid_nonce: u64,
+    pub bump: u8,
+}
+
+impl MakerState {
+    pub const DISCRIMINATOR: [u8; 8] = *b"maker\0\0\0";
+    pub const LEN: usize = 49;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+}
+
+pub const DUTCH_AUCTION_SEED: &[u8] = b"dutch_auction";
+
+pub fn find_dutch_auction_address(program_id: &Pubkey, escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DUTCH_AUCTION_SEED, escrow.as_ref()], program_id)
+}
+
+pub const LOAN_SEED: &[u8] = b"loan";
+
+pub fn find_loan_address(program_id: &Pubkey, escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LOAN_SEED, escrow.as_ref()], program_id)
+}
+
+/// Terms of a loan escrow created with `InitLoan`, stored at the PDA of `[LOAN_SEED, escrow]`
+/// while the escrow is in a loan state. The escrow's `size_a` is the collateral in x and its
+/// `size_b` the principal in y.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Loan {
+    pub discriminator: [u8; 8],
+    pub escrow: Pubkey,
+    pub x_mint: Pubkey,
+    pub y_mint: Pubkey,
+    /// owed on top of the principal
+    pub interest: u64,
+    /// unix timestamp by which the borrower has to repay
+    pub deadline: i64,
+    pub bump: u8,
+}
+
+impl Loan {
+    pub const DISCRIMINATOR: [u8; 8] = *b"loan\0\0\0\0";
+    pub const LEN: usize = 8 + 3 * 32 + 2 * 8 + 1;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+}
+
+pub const OPTION_SEED: &[u8] = b"option";
+pub const OPTION_VAULT_SEED: &[u8] = b"option_vault";
+
+pub fn find_option_address(program_id: &Pubkey, writer: &Pubkey, x_mint: &Pubkey, y_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OPTION_SEED, writer.as_ref(), x_mint.as_ref(), y_mint.as_ref()], program_id)
+}
+
+/// Vault of a covered option, holding the underlying and owned by the option account.
+pub fn find_option_vault_address(program_id: &Pubkey, option: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OPTION_VAULT_SEED, option.as_ref()], program_id)
+}
+
+/// Covered call on `amount` of the writer's x, bought for `premium` in y and exercised for
+/// `strike` in y until `expiry`. Stored at the PDA of `[OPTION_SEED, writer, x_mint, y_mint]`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct CoveredOption {
+    pub discriminator: [u8; 8],
+    pub writer: Pubkey,
+    /// holder of the right to exercise, the default key until the option is bought
+    pub owner: Pubkey,
+    pub x_mint: Pubkey,
+    pub y_mint: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub premium: u64,
+    pub strike: u64,
+    /// unix timestamp from which the option can no longer be exercised
+    pub expiry: i64,
+    pub bump: u8,
+    pub bump_vault: u8,
+}
+
+impl CoveredOption {
+    pub const DISCRIMINATOR: [u8; 8] = *b"option\0\0";
+    pub const LEN: usize = 8 + 5 * 32 + 4 * 8 + 2;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+
+    pub fn is_sold(&self) -> bool {
+        self.owner != Pubkey::default()
+    }
+}
+
+/// Descending price of y for an escrow created with `InitDutchEscrow`, stored at the PDA of
+/// `[DUTCH_AUCTION_SEED, escrow]`. The escrow keeps a `size_b` of 0 until the first taker's
+/// deposit fixes it at the price of the moment, which also closes this account.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct DutchAuction {
+    pub discriminator: [u8; 8],
+    pub escrow: Pubkey,
+    pub start_amount_b: u64,
+    pub end_amount_b: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub bump: u8,
+}
+
+impl DutchAuction {
+    pub const DISCRIMINATOR: [u8; 8] = *b"dutch\0\0\0";
+    pub const LEN: usize = 73;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+
+    /// Amount of y asked at unix timestamp `now`: `start_amount_b` up to `start_time`,
+    /// `end_amount_b` from `end_time`, and linear in between, rounded up in favour of alice.
+    /// Expects `start_time < end_time` and `start_amount_b >= end_amount_b`.
+    pub fn price_at(&self, now: i64) -> u64 {
+        if now <= self.start_time {
+            return self.start_amount_b;
+        }
+        if now >= self.end_time {
+            return self.end_amount_b;
+        }
+        // rounded up, written out since `div_ceil` is newer than the BPF toolchain. Both factors
+        // fit in 64 bits, so neither the product nor the rounding overflows 128, and the start
+        // price would be kept if they did
+        let range = u128::from(self.start_amount_b - self.end_amount_b);
+        let remaining = (i128::from(self.end_time) - i128::from(now)) as u128;
+        let duration = (i128::from(self.end_time) - i128::from(self.start_time)) as u128;
+        let above_end = range
+            .checked_mul(remaining)
+            .and_then(|product| product.checked_add(duration - 1))
+            .map_or(range, |product| product / duration);
+        // above_end <= range, so the sum is at most start_amount_b
+        self.end_amount_b + above_end as u64
+    }
+}
+
+/// Vault of `mint` for an auction, owned by the auction account.
+pub const AUCTION_VAULT_SEED: &[u8] = b"auction_vault";
+
+pub fn find_auction_vault_address(program_id: &Pubkey, auction: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUCTION_VAULT_SEED, auction.as_ref(), mint.as_ref()], program_id)
+}
+
+pub const ENGLISH_AUCTION_SEED: &[u8] = b"english_auction";
+
+pub fn find_english_auction_address(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ENGLISH_AUCTION_SEED, seller.as_ref(), x_mint.as_ref(), y_mint.as_ref()],
+        program_id,
+    )
+}
+
+/// Open ascending auction of `size_a` x for bids in y, stored at the PDA of
+/// `[ENGLISH_AUCTION_SEED, seller, x_mint, y_mint]`. The x vault holds the lot and the y vault
+/// the highest bid.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct EnglishAuction {
+    pub discriminator: [u8; 8],
+    pub seller: Pubkey,
+    pub x_mint: Pubkey,
+    pub y_mint: Pubkey,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub size_a: u64,
+    /// every bid after the first beats the highest by at least this much
+    pub min_increment: u64,
+    /// lowest first bid, 0 means no reserve
+    pub reserve_price: u64,
+    /// unix timestamp after which bids are closed and the auction can be settled
+    pub deadline: i64,
+    pub highest_bidder: Pubkey,
+    /// 0 until the first bid
+    pub highest_bid: u64,
+    pub bump: u8,
+}
+
+impl EnglishAuction {
+    pub const DISCRIMINATOR: [u8; 8] = *b"english\0";
+    pub const LEN: usize = 241;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+
+    /// Smallest bid that is accepted next.
+    pub fn min_bid(&self) -> Option<u64> {
+        if self.highest_bid == 0 {
+            return Some(self.reserve_price.max(1));
+        }
+        self.highest_bid.checked_add(self.min_increment)
+    }
+}
+
+pub const SEALED_AUCTION_SEED: &[u8] = b"sealed_auction";
+
+pub fn find_sealed_auction_address(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    x_mint: &Pubkey,
+    y_mint: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SEALED_AUCTION_SEED, seller.as_ref(), x_mint.as_ref(), y_mint.as_ref()],
+        program_id,
+    )
+}
+
+/// Sealed-bid auction of `size_a` x for bids in y, stored at the PDA of
+/// `[SEALED_AUCTION_SEED, seller, x_mint, y_mint]`. Bidders commit until `commit_deadline` and
+/// reveal until `reveal_deadline`. The y vault holds every bidder's collateral.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct SealedAuction {
+    pub discriminator: [u8; 8],
+    pub seller: Pubkey,
+    pub x_mint: Pubkey,
+    pub y_mint: Pubkey,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub size_a: u64,
+    /// lowest winning bid, 0 means no reserve
+    pub reserve_price: u64,
+    /// collateral a bidder forfeits to the seller by not revealing
+    pub penalty: u64,
+    pub commit_deadline: i64,
+    pub reveal_deadline: i64,
+    pub highest_bidder: Pubkey,
+    /// 0 until a valid bid is revealed
+    pub highest_bid: u64,
+    /// bids whose collateral has not been withdrawn yet
+    pub open_bids: u32,
+    pub settled: bool,
+    pub bump: u8,
+}
+
+impl SealedAuction {
+    pub const DISCRIMINATOR: [u8; 8] = *b"sealed\0\0";
+    pub const LEN: usize = 254;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+}
+
+pub const SEALED_BID_SEED: &[u8] = b"sealed_bid";
+
+pub fn find_sealed_bid_address(program_id: &Pubkey, auction: &Pubkey, bidder: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SEALED_BID_SEED, auction.as_ref(), bidder.as_ref()], program_id)
+}
+
+/// Commitment to `bid`. The bidder is part of it so that nobody can replay another bidder's
+/// reveal as their own.
+pub fn sealed_bid_commitment(bidder: &Pubkey, bid: u64, salt: &[u8; 32]) -> [u8; 32] {
+    hashv(&[bidder.as_ref(), &bid.to_le_bytes(), salt]).to_bytes()
+}
+
+/// One bidder's commitment and collateral in a sealed-bid auction, stored at the PDA of
+/// `[SEALED_BID_SEED, auction, bidder]`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct SealedBid {
+    pub discriminator: [u8; 8],
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    /// `sealed_bid_commitment` of the bid
+    pub commitment: [u8; 32],
+    pub collateral: u64,
+    /// 0 until revealed
+    pub bid: u64,
+    pub revealed: bool,
+    pub bump: u8,
+}
+
+impl SealedBid {
+    pub const DISCRIMINATOR: [u8; 8] = *b"sealbid\0";
+    pub const LEN: usize = 122;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+}
+
+pub const MILESTONE_ESCROW_SEED: &[u8] = b"milestone";
+pub const MILESTONE_VAULT_SEED: &[u8] = b"milestone_vault";
+
+pub fn find_milestone_escrow_address(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    payee: &Pubkey,
+    mint: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[MILESTONE_ESCROW_SEED, payer.as_ref(), payee.as_ref(), mint.as_ref()],
+        program_id,
+    )
+}
+
+/// Vault of a milestone escrow, owned by the escrow account.
+pub fn find_milestone_vault_address(program_id: &Pubkey, milestone_escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MILESTONE_VAULT_SEED, milestone_escrow.as_ref()], program_id)
+}
+
+/// Payment of the payer's x to the payee in tranches, one per approved milestone, stored at the
+/// PDA of `[MILESTONE_ESCROW_SEED, payer, payee, mint]`. Its size depends on the number of
+/// milestones, see `space`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct MilestoneEscrow {
+    pub discriminator: [u8; 8],
+    pub payer: Pubkey,
+    pub payee: Pubkey,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    /// may approve milestones besides the payer, the default key when there is none
+    pub arbiter: Pubkey,
+    /// unix timestamp after which the payer can take back unapproved tranches
+    pub deadline: i64,
+    /// milestones approved so far, always the first ones
+    pub approved: u8,
+    pub bump: u8,
+    pub bump_vault: u8,
+    /// gates every approval, the default condition when there is none
+    pub condition: Condition,
+    /// tranche paid out by each milestone, in order
+    pub milestones: Vec<u64>,
+}
+
+impl MilestoneEscrow {
+    pub const DISCRIMINATOR: [u8; 8] = *b"milestne";
+    pub const MAX_MILESTONES: usize = 32;
+
+    /// Account size for `milestones` tranches.
+    pub fn space(milestones: usize) -> usize {
+        8 + 5 * 32 + 8 + 3 + std::mem::size_of::<Condition>() + 4 + 8 * milestones
+    }
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+
+    pub fn has_arbiter(&self) -> bool {
+        self.arbiter != Pubkey::default()
+    }
+
+    /// Tranche released by the next approval, if any is left.
+    pub fn next_tranche(&self) -> Option<u64> {
+        self.milestones.get(self.approved as usize).copied()
+    }
+
+    /// Sum of the tranches not approved yet, which is what the vault holds.
+    pub fn remaining(&self) -> u64 {
+        self.milestones[self.approved as usize..].iter().sum()
+    }
+}
+
+pub const STREAM_SEED: &[u8] = b"stream";
+pub const STREAM_VAULT_SEED: &[u8] = b"stream_vault";
+
+pub fn find_stream_address(program_id: &Pubkey, sender: &Pubkey, recipient: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STREAM_SEED, sender.as_ref(), recipient.as_ref(), mint.as_ref()], program_id)
+}
+
+/// Vault of a stream, owned by the stream account.
+pub fn find_stream_vault_address(program_id: &Pubkey, stream: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STREAM_VAULT_SEED, stream.as_ref()], program_id)
+}
+
+/// Payment of `rate` of the sender's tokens per second to the recipient from `start` to `end`,
+/// stored at the PDA of `[STREAM_SEED, sender, recipient, mint]`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Stream {
+    pub discriminator: [u8; 8],
+    pub sender: Pubkey,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub rate: u64,
+    pub start: i64,
+    pub end: i64,
+    /// accrued amount the recipient has taken out so far
+    pub withdrawn: u64,
+    pub bump: u8,
+    pub bump_vault: u8,
+}
+
+impl Stream {
+    pub const DISCRIMINATOR: [u8; 8] = *b"stream\0\0";
+    pub const LEN: usize = 8 + 4 * 32 + 4 * 8 + 2;
+
+    pub fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        check_account_type(data, &Self::DISCRIMINATOR)?;
+        Ok(Self::try_from_slice(data)?)
+    }
+
+    /// Whole amount streamed from start to end, `None` if it does not fit a `u64`.
+    pub fn total(rate: u64, start: i64, end: i64) -> Option<u64> {
+        let duration = u64::try_from(i128::from(end) - i128::from(start)).ok()?;
+        rate.checked_mul(duration)
+    }
+
+    /// Amount accrued to the recipient at `now`, withdrawn or not.
+    pub fn accrued(&self, now: i64) -> u64 {
+        let elapsed = i128::from(now.clamp(self.start, self.end)) - i128::from(self.start);
+        // at most the total, which fit a u64 at init
+        (i128::from(self.rate) * elapsed) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auction(start_amount_b: u64, end_amount_b: u64, start_time: i64, end_time: i64) -> DutchAuction {
+        DutchAuction {
+            discriminator: DutchAuction::DISCRIMINATOR,
+            escrow: Pubkey::default(),
+            start_amount_b,
+            end_amount_b,
+            start_time,
+            end_time,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn test_price_at_boundaries() {
+        let dutch = auction(1000, 100, 50, 150);
+        assert_eq!(dutch.price_at(i64::MIN), 1000);
+        assert_eq!(dutch.price_at(50), 1000);
+        assert_eq!(dutch.price_at(51), 991);
+        assert_eq!(dutch.price_at(100), 550);
+        assert_eq!(dutch.price_at(149), 109);
+        assert_eq!(dutch.price_at(150), 100);
+        assert_eq!(dutch.price_at(i64::MAX), 100);
+    }
+
+    #[test]
+    fn test_price_at_rounds_up() {
+        // 10 over 3 seconds drops by 3.33 per second
+        let dutch = auction(10, 0, 0, 3);
+        assert_eq!(dutch.price_at(1), 7);
+        assert_eq!(dutch.price_at(2), 4);
+        assert_eq!(dutch.price_at(3), 0);
+    }
+
+    #[test]
+    fn test_price_at_flat() {
+        let dutch = auction(5, 5, 0, 2);
+        assert_eq!(dutch.price_at(0), 5);
+        assert_eq!(dutch.price_at(1), 5);
+        assert_eq!(dutch.price_at(2), 5);
+    }
+
+    #[test]
+    fn test_price_at_extremes() {
+        let dutch = auction(u64::MAX, 0, i64::MIN, i64::MAX);
+        assert_eq!(dutch.price_at(i64::MIN), u64::MAX);
+        assert_eq!(dutch.price_at(i64::MIN + 1), u64::MAX - 1);
+        assert_eq!(dutch.price_at(0), u64::MAX / 2);
+        assert_eq!(dutch.price_at(i64::MAX - 1), 1);
+        assert_eq!(dutch.price_at(i64::MAX), 0);
+    }
+
+    #[test]
+    fn test_vested_linear_after_cliff() {
+        let vesting = VestingSchedule::new(150, 100, 200);
+        assert!(vesting.is_valid());
+        assert_eq!(vesting.vested(1000, 0), 0);
+        assert_eq!(vesting.vested(1000, 149), 0);
+        // the cliff releases everything vested since the start
+        assert_eq!(vesting.vested(1000, 150), 500);
+        assert_eq!(vesting.vested(1000, 175), 750);
+        assert_eq!(vesting.vested(1000, 199), 990);
+        assert_eq!(vesting.vested(1000, 200), 1000);
+        assert_eq!(vesting.vested(1000, i64::MAX), 1000);
+    }
+
+    #[test]
+    fn test_vested_rounds_down() {
+        let vesting = VestingSchedule::new(0, 0, 3);
+        assert_eq!(vesting.vested(10, 1), 3);
+        assert_eq!(vesting.vested(10, 2), 6);
+    }
+
+    #[test]
+    fn test_vested_immediate() {
+        let vesting = VestingSchedule::default();
+        assert!(vesting.is_valid() && vesting.is_immediate());
+        assert_eq!(vesting.vested(10, 0), 10);
+        assert_eq!(vesting.vested(u64::MAX, 1), u64::MAX);
+    }
+
+    #[test]
+    fn test_vested_extremes() {
+        let vesting = VestingSchedule::new(i64::MIN, i64::MIN, i64::MAX);
+        assert_eq!(vesting.vested(u64::MAX, i64::MIN), 0);
+        assert_eq!(vesting.vested(u64::MAX, i64::MAX - 1), u64::MAX - 1);
+        assert!(!VestingSchedule::new(99, 100, 200).is_valid());
+        assert!(!VestingSchedule::new(201, 100, 200).is_valid());
+    }
+
+    fn stream(rate: u64, start: i64, end: i64) -> Stream {
+        Stream {
+            discriminator: Stream::DISCRIMINATOR,
+            sender: Pubkey::default(),
+            recipient: Pubkey::default(),
+            mint: Pubkey::default(),
+            vault: Pubkey::default(),
+            rate,
+            start,
+            end,
+            withdrawn: 0,
+            bump: 0,
+            bump_vault: 0,
+        }
+    }
+
+    #[test]
+    fn test_stream_accrued() {
+        let stream = stream(3, 100, 200);
+        assert_eq!(stream.accrued(0), 0);
+        assert_eq!(stream.accrued(100), 0);
+        assert_eq!(stream.accrued(101), 3);
+        assert_eq!(stream.accrued(150), 150);
+        assert_eq!(stream.accrued(200), 300);
+        assert_eq!(stream.accrued(i64::MAX), 300);
+    }
+
+    #[test]
+    fn test_stream_total() {
+        assert_eq!(Stream::total(3, 100, 200), Some(300));
+        assert_eq!(Stream::total(1, i64::MIN, i64::MAX), Some(u64::MAX));
+        assert_eq!(Stream::total(2, i64::MIN, i64::MAX), None);
+        assert_eq!(Stream::total(1, 200, 100), None);
+        assert_eq!(stream(1, i64::MIN, i64::MAX).accrued(i64::MAX), u64::MAX);
+    }
+
+    fn condition(offset: u32, comparator: Comparator, threshold: ConditionValue) -> Condition {
+        Condition::new(Pubkey::new_unique(), Pubkey::new_unique(), offset, comparator, threshold)
+    }
+
+    #[test]
+    fn test_condition_compares_numbers() {
+        let mut data = [0; 16];
+        data[4..12].copy_from_slice(&100u64.to_le_bytes());
+        assert!(condition(4, Comparator::Ge, ConditionValue::U64(100)).is_met(&data));
+        assert!(!condition(4, Comparator::Gt, ConditionValue::U64(100)).is_met(&data));
+        assert!(condition(4, Comparator::Lt, ConditionValue::U64(101)).is_met(&data));
+        assert!(condition(4, Comparator::Ne, ConditionValue::U64(99)).is_met(&data));
+        data[4..12].copy_from_slice(&(-5i64).to_le_bytes());
+        assert!(condition(4, Comparator::Le, ConditionValue::I64(-5)).is_met(&data));
+        assert!(condition(4, Comparator::Gt, ConditionValue::I64(-6)).is_met(&data));
+        // the threshold decides how the bytes are read, and -5 read as a u64 is far from small
+        assert!(!condition(4, Comparator::Lt, ConditionValue::U64(5)).is_met(&data));
+    }
+
+    #[test]
+    fn test_condition_compares_flags_and_keys() {
+        let key = Pubkey::new_unique();
+        let mut data = vec![1];
+        data.extend_from_slice(key.as_ref());
+        assert!(condition(0, Comparator::Eq, ConditionValue::Bool(true)).is_met(&data));
+        assert!(!condition(0, Comparator::Eq, ConditionValue::Bool(false)).is_met(&data));
+        assert!(condition(1, Comparator::Eq, ConditionValue::Pubkey(key)).is_met(&data));
+        assert!(condition(1, Comparator::Ne, ConditionValue::Pubkey(Pubkey::default())).is_met(&data));
+        // anything but 0 and 1 is not a boolean
+        data[0] = 2;
+        assert!(!condition(0, Comparator::Ne, ConditionValue::Bool(false)).is_met(&data));
+    }
+
+    #[test]
+    fn test_condition_out_of_bounds() {
+        let data = [0; 8];
+        assert!(condition(0, Comparator::Eq, ConditionValue::U64(0)).is_met(&data));
+        assert!(!condition(1, Comparator::Eq, ConditionValue::U64(0)).is_met(&data));
+        assert!(!condition(u32::MAX, Comparator::Eq, ConditionValue::Bool(false)).is_met(&data));
+    }
+
+    #[test]
+    fn test_condition_valid() {
+        assert!(condition(0, Comparator::Lt, ConditionValue::I64(0)).is_valid());
+        assert!(condition(0, Comparator::Ne, ConditionValue::Bool(true)).is_valid());
+        assert!(!condition(0, Comparator::Gt, ConditionValue::Bool(true)).is_valid());
+        assert!(!condition(0, Comparator::Ge, ConditionValue::Pubkey(Pubkey::default())).is_valid());
+        let mut unknown = condition(0, Comparator::Eq, ConditionValue::U64(0));
+        unknown.comparator = 6;
+        assert!(!unknown.is_valid());
+        unknown.comparator = 0;
+        unknown.value_type = 5;
+        assert!(!unknown.is_valid());
+        assert!(Condition::default().is_none() && !Condition::default().is_valid());
+    }
+
+    /// Checks that `account`, filled to the widest value of each field, takes up `len` bytes.
+    fn assert_len<T: BorshSerialize>(account: &T, len: usize) {
+        assert_eq!(account.try_to_vec().unwrap().len(), len, "{}", std::any::type_name::<T>());
+    }
+
+    #[test]
+    fn test_account_lens() {
+        let key = Pubkey::new_unique;
+        let escrow_v0 = EscrowV0 {
+            party_a: key(),
+            party_b: key(),
+            size_a: u64::MAX,
+            size_b: u64::MAX,
+            vault_x: key(),
+            vault_y: key(),
+            state: u8::MAX,
+            bump: u8::MAX,
+            bump_vault_x: u8::MAX,
+            bump_vault_y: u8::MAX,
+        };
+        assert_len(&escrow_v0, EscrowV0::LEN);
+        let config = Config {
+            discriminator: Config::DISCRIMINATOR,
+            admin: key(),
+            guardian: key(),
+            paused: true,
+            allowlist_only: true,
+            bump: u8::MAX,
+        };
+        assert_len(&config, Config::LEN);
+        let mint_rule = MintRule {
+            discriminator: MintRule::DISCRIMINATOR,
+            mint: key(),
+            allowed: true,
+            min_size: u64::MAX,
+            max_size: u64::MAX,
+            bump: u8::MAX,
+        };
+        assert_len(&mint_rule, MintRule::LEN);
+        let maker_state = MakerState {
+            discriminator: MakerState::DISCRIMINATOR,
+            maker: key(),
+            min_valid_nonce: u64::MAX,
+            bump: u8::MAX,
+        };
+        assert_len(&maker_state, MakerState::LEN);
+        let loan = Loan {
+            discriminator: Loan::DISCRIMINATOR,
+            escrow: key(),
+            x_mint: key(),
+            y_mint: key(),
+            interest: u64::MAX,
+            deadline: i64::MAX,
+            bump: u8::MAX,
+        };
+        assert_len(&loan, Loan::LEN);
+        let option = CoveredOption {
+            discriminator: CoveredOption::DISCRIMINATOR,
+            writer: key(),
+            owner: key(),
+            x_mint: key(),
+            y_mint: key(),
+            vault: key(),
+            amount: u64::MAX,
+            premium: u64::MAX,
+            strike: u64::MAX,
+            expiry: i64::MAX,
+            bump: u8::MAX,
+            bump_vault: u8::MAX,
+        };
+        assert_len(&option, CoveredOption::LEN);
+        assert_len(&auction(u64::MAX, u64::MAX, i64::MIN, i64::MAX), DutchAuction::LEN);
+        let english_auction = EnglishAuction {
+            discriminator: EnglishAuction::DISCRIMINATOR,
+            seller: key(),
+            x_mint: key(),
+            y_mint: key(),
+            vault_x: key(),
+            vault_y: key(),
+            size_a: u64::MAX,
+            min_increment: u64::MAX,
+            reserve_price: u64::MAX,
+            deadline: i64::MAX,
+            highest_bidder: key(),
+            highest_bid: u64::MAX,
+            bump: u8::MAX,
+        };
+        assert_len(&english_auction, EnglishAuction::LEN);
+        let sealed_auction = SealedAuction {
+            discriminator: SealedAuction::DISCRIMINATOR,
+            seller: key(),
+            x_mint: key(),
+            y_mint: key(),
+            vault_x: key(),
+            vault_y: key(),
+            size_a: u64::MAX,
+            reserve_price: u64::MAX,
+            penalty: u64::MAX,
+            commit_deadline: i64::MAX,
+            reveal_deadline: i64::MAX,
+            highest_bidder: key(),
+            highest_bid: u64::MAX,
+            open_bids: u32::MAX,
+            settled: true,
+            bump: u8::MAX,
+        };
+        assert_len(&sealed_auction, SealedAuction::LEN);
+        let sealed_bid = SealedBid {
+            discriminator: SealedBid::DISCRIMINATOR,
+            auction: key(),
+            bidder: key(),
+            commitment: [u8::MAX; 32],
+            collateral: u64::MAX,
+            bid: u64::MAX,
+            revealed: true,
+            bump: u8::MAX,
+        };
+        assert_len(&sealed_bid, SealedBid::LEN);
+        let milestone_escrow = MilestoneEscrow {
+            discriminator: MilestoneEscrow::DISCRIMINATOR,
+            payer: key(),
+            payee: key(),
+            mint: key(),
+            vault: key(),
+            arbiter: key(),
+            deadline: i64::MAX,
+            approved: u8::MAX,
+            bump: u8::MAX,
+            bump_vault: u8::MAX,
+            condition: condition(8, Comparator::Ge, ConditionValue::U64(1)),
+            milestones: vec![u64::MAX; MilestoneEscrow::MAX_MILESTONES],
+        };
+        assert_len(&milestone_escrow, MilestoneEscrow::space(MilestoneEscrow::MAX_MILESTONES));
+        assert_len(&stream(u64::MAX, i64::MIN, i64::MAX), Stream::LEN);
+    }
+}
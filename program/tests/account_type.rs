@@ -0,0 +1,33 @@
+mod common;
+
+use common::*;
+use solana_escrow::{error::EscrowError, instruction};
+use solana_program_test::*;
+use solana_sdk::{pubkey::Pubkey, signature::{Keypair, Signer}};
+
+#[tokio::test]
+async fn test_config_rejected_as_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &parties.config, &Keypair::new().pubkey()).unwrap();
+    send(&mut context, &[init_config], &[]).await.unwrap();
+
+    let status = instruction::get_escrow_status(&program_id, &parties.config).unwrap();
+    let result = send(&mut context, &[status], &[]).await;
+    assert_custom_error(result, EscrowError::InvalidAccountType as u32);
+}
+
+#[tokio::test]
+async fn test_escrow_rejected_as_config() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+
+    let mut deposit = parties.alice_deposit(10);
+    deposit.accounts[6].pubkey = parties.escrow;
+    let result = send(&mut context, &[deposit], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidAccountType as u32);
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::*;
+use solana_escrow::{error::EscrowError, instruction};
+use solana_program_test::*;
+use solana_sdk::{pubkey::Pubkey, signature::Signer};
+
+#[tokio::test]
+async fn test_atomic_swap() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    send(&mut context, &[parties.atomic_swap(10, 20)], &[&parties.alice, &parties.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 90);
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &parties.bob_y).await, 80);
+    for address in [parties.escrow, parties.x_vault, parties.y_vault] {
+        assert!(context.banks_client.get_account(address).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_atomic_swap_requires_both_signatures() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    let mut swap = parties.atomic_swap(10, 20);
+    swap.accounts[1].is_signer = false;
+    assert!(send(&mut context, &[swap], &[&parties.alice]).await.is_err());
+    assert_eq!(token_balance(&mut context, &parties.bob_y).await, 100);
+}
+
+#[tokio::test]
+async fn test_atomic_swap_validates_like_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let signers = [&parties.alice, &parties.bob];
+
+    let result = send(&mut context, &[parties.atomic_swap(0, 20)], &signers).await;
+    assert_custom_error(result, EscrowError::ZeroEscrowSize as u32);
+
+    // bob's y account in place of bob's x account
+    let mut swap = parties.atomic_swap(10, 20);
+    swap.accounts[6].pubkey = parties.bob_y;
+    let result = send(&mut context, &[swap], &signers).await;
+    assert_custom_error(result, EscrowError::InvalidParty as u32);
+
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &parties.config, &payer).unwrap();
+    let set_paused = instruction::set_paused(&program_id, &payer, &parties.config, true).unwrap();
+    send(&mut context, &[init_config, set_paused], &[]).await.unwrap();
+    let result = send(&mut context, &[parties.atomic_swap(10, 20)], &signers).await;
+    assert_custom_error(result, EscrowError::Paused as u32);
+}
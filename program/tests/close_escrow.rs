@@ -0,0 +1,121 @@
+mod common;
+
+use common::*;
+use solana_escrow::{error::EscrowError, instruction, state::Escrow};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+async fn lamports(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    context.banks_client.get_balance(*address).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_sponsored_escrow_returns_rent_to_sponsor() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let sponsor = Keypair::new();
+    airdrop(&mut context, &sponsor.pubkey(), 1_000_000_000).await;
+    let alice_lamports = lamports(&mut context, &parties.alice.pubkey()).await;
+
+    let init = instruction::init_escrow_sponsored(
+        &program_id, &sponsor.pubkey(), &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint,
+        &parties.y_mint, &parties.x_vault, &parties.y_vault, &parties.escrow, &parties.config, &parties.x_mint_rule,
+        &parties.y_mint_rule, 10, 20,
+    )
+    .unwrap();
+    send(&mut context, &[init], &[&parties.alice, &sponsor]).await.unwrap();
+    assert_eq!(lamports(&mut context, &parties.alice.pubkey()).await, alice_lamports);
+    assert!(lamports(&mut context, &sponsor.pubkey()).await < 1_000_000_000);
+    let account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    assert_eq!(Escrow::load(&account.data).unwrap().rent_payer, sponsor.pubkey());
+
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    send(&mut context, &[parties.alice_withdraw(20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_withdraw(10)], &[&parties.bob]).await.unwrap();
+
+    // bob closes the settled escrow, the rent goes back to the sponsor
+    let close = parties.close_escrow(&parties.bob.pubkey(), &sponsor.pubkey());
+    send(&mut context, &[close], &[&parties.bob]).await.unwrap();
+    assert_eq!(lamports(&mut context, &sponsor.pubkey()).await, 1_000_000_000);
+    for address in [parties.escrow, parties.x_vault, parties.y_vault] {
+        assert!(context.banks_client.get_account(address).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_cancelled_escrow_returns_rent_to_alice() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let alice_lamports = lamports(&mut context, &parties.alice.pubkey()).await;
+
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    let refund = instruction::withdraw(
+        &program_id, &parties.alice.pubkey(), &parties.x_vault, &parties.escrow, &parties.alice_x, &parties.config, 10,
+    )
+    .unwrap();
+    send(&mut context, &[refund], &[&parties.alice]).await.unwrap();
+
+    let close = parties.close_escrow(&parties.alice.pubkey(), &parties.alice.pubkey());
+    send(&mut context, &[close], &[&parties.alice]).await.unwrap();
+    assert_eq!(lamports(&mut context, &parties.alice.pubkey()).await, alice_lamports);
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 100);
+
+    // the pair can open a new escrow afterwards
+    send(&mut context, &[parties.init_escrow(5, 5)], &[&parties.alice]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_close_rejected_while_funded() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+
+    let close = parties.close_escrow(&parties.bob.pubkey(), &parties.alice.pubkey());
+    let result = send(&mut context, &[close], &[&parties.bob]).await;
+    assert_custom_error(result, EscrowError::EscrowNotEmpty as u32);
+}
+
+#[tokio::test]
+async fn test_close_rejects_outsider_and_wrong_rent_payer() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+
+    let outsider = Keypair::new();
+    let close = parties.close_escrow(&outsider.pubkey(), &parties.alice.pubkey());
+    let result = send(&mut context, &[close], &[&outsider]).await;
+    assert_custom_error(result, EscrowError::Unauthorized as u32);
+
+    let close = parties.close_escrow(&parties.bob.pubkey(), &parties.bob.pubkey());
+    assert!(send(&mut context, &[close], &[&parties.bob]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_close_rejects_other_token_program() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+
+    // the escrow signs the vault closes, so they only go to the real token program
+    let mut close = parties.close_escrow(&parties.alice.pubkey(), &parties.alice.pubkey());
+    close.accounts[5].pubkey = Pubkey::new_unique();
+    let result = send(&mut context, &[close], &[&parties.alice]).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+    );
+    assert!(context.banks_client.get_account(parties.escrow).await.unwrap().is_some());
+}
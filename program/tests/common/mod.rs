@@ -0,0 +1,297 @@
+#![allow(dead_code)]
+
+use solana_escrow::{
+    instruction,
+    processor::Processor,
+    state::{
+        find_config_address, find_escrow_address, find_mint_rule_address, find_open_escrow_address,
+        find_open_vault_address, find_order_authority_address, find_vault_address, Order,
+    },
+};
+use std::convert::TryInto;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+
+pub fn program_test(program_id: &Pubkey) -> ProgramTest {
+    ProgramTest::new("solana_escrow", *program_id, processor!(Processor::process))
+}
+
+pub async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransportError> {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+/// Waits for a blockhash newer than the one `send` last used, so that resending the same
+/// instructions makes a new transaction instead of hitting the status cache.
+pub async fn refresh_blockhash(context: &mut ProgramTestContext) {
+    context.last_blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    context.get_new_latest_blockhash().await.unwrap();
+}
+
+/// Asserts that `result` failed in its first instruction with custom error `code`.
+pub fn assert_custom_error(result: Result<(), TransportError>, code: u32) {
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(code))
+    );
+}
+
+pub async fn airdrop(context: &mut ProgramTestContext, to: &Pubkey, lamports: u64) {
+    let transfer = system_instruction::transfer(&context.payer.pubkey(), to, lamports);
+    send(context, &[transfer], &[]).await.unwrap();
+}
+
+pub async fn create_mint(context: &mut ProgramTestContext) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), &context.payer.pubkey(), None, 0)
+            .unwrap(),
+    ];
+    send(context, &instructions, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(
+    context: &mut ProgramTestContext,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &account.pubkey(),
+            rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(&spl_token::id(), &account.pubkey(), mint, owner).unwrap(),
+        spl_token::instruction::mint_to(&spl_token::id(), mint, &account.pubkey(), &context.payer.pubkey(), &[], amount)
+            .unwrap(),
+    ];
+    send(context, &instructions, &[&account]).await.unwrap();
+    account.pubkey()
+}
+
+pub async fn token_balance(context: &mut ProgramTestContext, account: &Pubkey) -> u64 {
+    let account = context.banks_client.get_account(*account).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data).unwrap().amount
+}
+
+/// Alice holding 100 x and Bob holding 100 y, with the escrow addresses derived for them.
+pub struct Parties {
+    pub program_id: Pubkey,
+    pub alice: Keypair,
+    pub bob: Keypair,
+    pub x_mint: Pubkey,
+    pub y_mint: Pubkey,
+    pub alice_x: Pubkey,
+    pub alice_y: Pubkey,
+    pub bob_x: Pubkey,
+    pub bob_y: Pubkey,
+    pub x_vault: Pubkey,
+    pub y_vault: Pubkey,
+    pub escrow: Pubkey,
+    pub config: Pubkey,
+    pub x_mint_rule: Pubkey,
+    pub y_mint_rule: Pubkey,
+}
+
+impl Parties {
+    pub async fn new(context: &mut ProgramTestContext, program_id: &Pubkey) -> Self {
+        let alice = Keypair::new();
+        let bob = Keypair::new();
+        airdrop(context, &alice.pubkey(), 1_000_000_000).await;
+        airdrop(context, &bob.pubkey(), 1_000_000_000).await;
+
+        let x_mint = create_mint(context).await;
+        let y_mint = create_mint(context).await;
+        let alice_x = create_token_account(context, &x_mint, &alice.pubkey(), 100).await;
+        let alice_y = create_token_account(context, &y_mint, &alice.pubkey(), 0).await;
+        let bob_x = create_token_account(context, &x_mint, &bob.pubkey(), 0).await;
+        let bob_y = create_token_account(context, &y_mint, &bob.pubkey(), 100).await;
+
+        let (x_vault, _) = find_vault_address(program_id, &x_mint, &alice.pubkey(), &bob.pubkey());
+        let (y_vault, _) = find_vault_address(program_id, &y_mint, &alice.pubkey(), &bob.pubkey());
+        let (escrow, _) = find_escrow_address(program_id, &x_vault, &y_vault, &alice.pubkey(), &bob.pubkey());
+        let (config, _) = find_config_address(program_id);
+        let (x_mint_rule, _) = find_mint_rule_address(program_id, &x_mint);
+        let (y_mint_rule, _) = find_mint_rule_address(program_id, &y_mint);
+
+        Self {
+            program_id: *program_id,
+            alice,
+            bob,
+            x_mint,
+            y_mint,
+            alice_x,
+            alice_y,
+            bob_x,
+            bob_y,
+            x_vault,
+            y_vault,
+            escrow,
+            config,
+            x_mint_rule,
+            y_mint_rule,
+        }
+    }
+
+    /// Rederives the vaults and the escrow without bob, for an escrow the first taker fills.
+    pub fn open(mut self) -> Self {
+        let alice = self.alice.pubkey();
+        self.x_vault = find_open_vault_address(&self.program_id, &self.x_mint, &alice).0;
+        self.y_vault = find_open_vault_address(&self.program_id, &self.y_mint, &alice).0;
+        self.escrow = find_open_escrow_address(&self.program_id, &self.x_vault, &self.y_vault, &alice).0;
+        self
+    }
+
+    pub fn init_escrow(&self, amount_a: u64, amount_b: u64) -> Instruction {
+        instruction::init_escrow(
+            &self.program_id,
+            &self.alice.pubkey(),
+            &self.bob.pubkey(),
+            &self.x_mint,
+            &self.y_mint,
+            &self.x_vault,
+            &self.y_vault,
+            &self.escrow,
+            &self.config,
+            &self.x_mint_rule,
+            &self.y_mint_rule,
+            amount_a,
+            amount_b,
+        )
+        .unwrap()
+    }
+
+    pub fn alice_deposit(&self, amount: u64) -> Instruction {
+        instruction::deposit(&self.program_id, &self.alice.pubkey(), &self.x_vault, &self.escrow, &self.alice_x, &self.config, amount)
+            .unwrap()
+    }
+
+    pub fn bob_deposit(&self, amount: u64) -> Instruction {
+        instruction::deposit(&self.program_id, &self.bob.pubkey(), &self.y_vault, &self.escrow, &self.bob_y, &self.config, amount)
+            .unwrap()
+    }
+
+    pub fn alice_withdraw(&self, amount: u64) -> Instruction {
+        instruction::withdraw(&self.program_id, &self.alice.pubkey(), &self.y_vault, &self.escrow, &self.alice_y, &self.config, amount)
+            .unwrap()
+    }
+
+    pub fn bob_withdraw(&self, amount: u64) -> Instruction {
+        instruction::withdraw(&self.program_id, &self.bob.pubkey(), &self.x_vault, &self.escrow, &self.bob_x, &self.config, amount)
+            .unwrap()
+    }
+
+    pub fn atomic_swap(&self, amount_a: u64, amount_b: u64) -> Instruction {
+        instruction::atomic_swap(
+            &self.program_id,
+            &self.alice.pubkey(),
+            &self.bob.pubkey(),
+            &self.x_mint,
+            &self.y_mint,
+            &self.alice_x,
+            &self.alice_y,
+            &self.bob_x,
+            &self.bob_y,
+            &self.config,
+            &self.x_mint_rule,
+            &self.y_mint_rule,
+            amount_a,
+            amount_b,
+        )
+        .unwrap()
+    }
+
+    pub fn settle_delegated(&self) -> Instruction {
+        instruction::settle_delegated(
+            &self.program_id,
+            &self.escrow,
+            &self.x_vault,
+            &self.y_vault,
+            &self.alice_x,
+            &self.alice_y,
+            &self.bob_x,
+            &self.bob_y,
+            &self.config,
+        )
+        .unwrap()
+    }
+
+    pub fn close_escrow(&self, authority: &Pubkey, rent_payer: &Pubkey) -> Instruction {
+        instruction::close_escrow(&self.program_id, authority, &self.escrow, &self.x_vault, &self.y_vault, rent_payer).unwrap()
+    }
+
+    /// Alice's order of `size_a` x for `size_b` y, without expiry.
+    pub fn order(&self, size_a: u64, size_b: u64, nonce: u64) -> Order {
+        Order {
+            maker: self.alice.pubkey(),
+            x_mint: self.x_mint,
+            y_mint: self.y_mint,
+            size_a,
+            size_b,
+            expiry: i64::MAX,
+            nonce,
+        }
+    }
+
+    /// Alice approving the order authority for `amount` of her x.
+    pub fn approve_orders(&self, amount: u64) -> Instruction {
+        let (order_authority, _) = find_order_authority_address(&self.program_id);
+        spl_token::instruction::approve(&spl_token::id(), &self.alice_x, &order_authority, &self.alice.pubkey(), &[], amount)
+            .unwrap()
+    }
+
+    /// Bob filling `order` as signed by alice.
+    pub fn fill_order(&self, order: &Order) -> [Instruction; 2] {
+        let signature = self.alice.sign_message(&order.message(&self.program_id).unwrap());
+        [
+            instruction::verify_order(&self.program_id, order, signature.as_ref().try_into().unwrap()).unwrap(),
+            instruction::fill_order(
+                &self.program_id,
+                &self.bob.pubkey(),
+                order,
+                &self.alice_x,
+                &self.alice_y,
+                &self.bob_x,
+                &self.bob_y,
+                &self.config,
+                &self.x_mint_rule,
+                &self.y_mint_rule,
+            )
+            .unwrap(),
+        ]
+    }
+}
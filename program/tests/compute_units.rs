@@ -0,0 +1,410 @@
+//! Compute units consumed per instruction by the BPF build, checked against the budgets in
+//! `compute_budgets.txt`.
+//!
+//! Run with `cargo test-bpf --test compute_units`. Setting `UPDATE_COMPUTE_BUDGETS=1`
+//! rewrites the budgets file with the measured numbers instead of checking them.
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_escrow::{
+    instruction,
+    state::{
+        find_english_auction_address, find_milestone_escrow_address, find_option_address,
+        find_sealed_auction_address, find_stream_address, find_stream_vault_address, sealed_bid_commitment, Comparator,
+        Condition, ConditionValue, EscrowTerms, EscrowV0, VestingSchedule,
+    },
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar::clock::Clock,
+    transaction::Transaction,
+};
+use std::{collections::BTreeMap, fs};
+
+/// How far above its budget an instruction may go before the test fails.
+const MARGIN_PERCENT: u64 = 5;
+
+const BUDGETS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/compute_budgets.txt");
+
+fn load_budgets() -> BTreeMap<String, u64> {
+    let contents = fs::read_to_string(BUDGETS_PATH).unwrap();
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, units) = line.split_once(char::is_whitespace).unwrap();
+            (name.to_string(), units.trim().parse().unwrap())
+        })
+        .collect()
+}
+
+fn write_budgets(measured: &[(String, u64)]) {
+    let mut contents = String::from(
+        "# Compute units per instruction, written by `UPDATE_COMPUTE_BUDGETS=1 cargo test-bpf --test compute_units`\n",
+    );
+    for (name, units) in measured {
+        contents.push_str(&format!("{} {}\n", name, units));
+    }
+    fs::write(BUDGETS_PATH, contents).unwrap();
+}
+
+/// Runs every measured instruction as its own transaction, simulating it first to read the
+/// units it consumes. Precompiles sent along with an instruction are not metered.
+struct Bench {
+    measured: Vec<(String, u64)>,
+}
+
+impl Bench {
+    async fn run(
+        &mut self,
+        context: &mut ProgramTestContext,
+        name: &str,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) {
+        let mut all_signers = vec![&context.payer];
+        all_signers.extend_from_slice(signers);
+        let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+        let transaction =
+            Transaction::new_signed_with_payer(instructions, Some(&context.payer.pubkey()), &all_signers, blockhash);
+
+        let simulation = context.banks_client.simulate_transaction(transaction.clone()).await.unwrap();
+        simulation.result.unwrap().unwrap();
+        let units = simulation.simulation_details.unwrap().units_consumed;
+        println!("{:<24} {}", name, units);
+        self.measured.push((name.to_string(), units));
+
+        context.banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    fn check(self) {
+        if std::env::var("UPDATE_COMPUTE_BUDGETS").is_ok() {
+            write_budgets(&self.measured);
+            return;
+        }
+
+        let budgets = load_budgets();
+        let mut missing = vec![];
+        let mut regressions = vec![];
+        for (name, units) in &self.measured {
+            match budgets.get(name) {
+                Some(&budget) if *units > budget + budget * MARGIN_PERCENT / 100 => {
+                    regressions.push(format!("{}: {} units, budget {}", name, units, budget));
+                }
+                Some(&budget) if *units + budget * MARGIN_PERCENT / 100 < budget => {
+                    println!("{} is well under its budget of {}, consider updating it", name, budget);
+                }
+                Some(_) => {}
+                None => missing.push(name.as_str()),
+            }
+        }
+        assert!(missing.is_empty(), "no compute budget for {}, add them with UPDATE_COMPUTE_BUDGETS=1", missing.join(", "));
+        assert!(
+            regressions.is_empty(),
+            "compute budgets exceeded by more than {}%:\n{}",
+            MARGIN_PERCENT,
+            regressions.join("\n")
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_compute_budgets() {
+    let program_id = Pubkey::new_unique();
+    let mut context = ProgramTest::new("solana_escrow", program_id, None).start_with_context().await;
+    let mut bench = Bench { measured: vec![] };
+    let payer = context.payer.pubkey();
+
+    // a full trade and a refund, without a config or mint rules
+    let parties = Parties::new(&mut context, &program_id).await;
+    bench.run(&mut context, "init_escrow", &[parties.init_escrow(10, 20)], &[&parties.alice]).await;
+    bench.run(&mut context, "deposit", &[parties.alice_deposit(10)], &[&parties.alice]).await;
+    bench.run(&mut context, "deposit_second", &[parties.bob_deposit(20)], &[&parties.bob]).await;
+    let status = instruction::get_escrow_status(&program_id, &parties.escrow).unwrap();
+    bench.run(&mut context, "get_escrow_status", &[status], &[]).await;
+    bench.run(&mut context, "withdraw_settle", &[parties.alice_withdraw(20)], &[&parties.alice]).await;
+    bench.run(&mut context, "withdraw_close", &[parties.bob_withdraw(10)], &[&parties.bob]).await;
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    let refund = instruction::withdraw(
+        &program_id, &parties.alice.pubkey(), &parties.x_vault, &parties.escrow, &parties.alice_x, &parties.config, 10,
+    )
+    .unwrap();
+    bench.run(&mut context, "withdraw_refund", &[refund], &[&parties.alice]).await;
+
+    // a non-custodial trade on the now empty escrow
+    let approve = |source: &Pubkey, owner: &Keypair, amount: u64| {
+        spl_token::instruction::approve(&spl_token::id(), source, &parties.escrow, &owner.pubkey(), &[], amount).unwrap()
+    };
+    send(&mut context, &[approve(&parties.alice_x, &parties.alice, 10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[approve(&parties.bob_y, &parties.bob, 20)], &[&parties.bob]).await.unwrap();
+    bench.run(&mut context, "settle_delegated", &[parties.settle_delegated()], &[]).await;
+    bench.run(&mut context, "atomic_swap", &[parties.atomic_swap(10, 20)], &[&parties.alice, &parties.bob]).await;
+    send(&mut context, &[parties.approve_orders(10)], &[&parties.alice]).await.unwrap();
+    bench.run(&mut context, "fill_order", &parties.fill_order(&parties.order(10, 20, 0)), &[&parties.bob]).await;
+    let invalidate = instruction::invalidate_orders(&program_id, &parties.alice.pubkey(), 0).unwrap();
+    bench.run(&mut context, "invalidate_orders", &[invalidate], &[&parties.alice]).await;
+
+    // a Dutch auction priced by bob's deposit
+    let parties = Parties::new(&mut context, &program_id).await.open();
+    let now = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    let init_dutch = instruction::init_dutch_escrow(
+        &program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint, &parties.x_vault, &parties.y_vault,
+        &parties.escrow, &parties.config, &parties.x_mint_rule, &parties.y_mint_rule, 10, 100, 20, now - 10_000,
+        now + 10_000,
+    )
+    .unwrap();
+    bench.run(&mut context, "init_dutch_escrow", &[init_dutch], &[&parties.alice]).await;
+    let deposit_dutch = instruction::deposit_dutch(
+        &program_id, &parties.bob.pubkey(), &parties.y_vault, &parties.escrow, &parties.bob_y, &parties.config, 100,
+    )
+    .unwrap();
+    bench.run(&mut context, "deposit_dutch", &[deposit_dutch], &[&parties.bob]).await;
+
+    // an escrow whose y leg vests to alice, claimed halfway through
+    let parties = Parties::new(&mut context, &program_id).await;
+    let now = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    let terms = EscrowTerms {
+        vesting_b: VestingSchedule::new(now - 100, now - 100, now + 100),
+        ..EscrowTerms::default()
+    };
+    let init_vesting = instruction::init_escrow_with_terms(
+        &program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &parties.y_mint,
+        &parties.x_vault, &parties.y_vault, &parties.escrow, &parties.config, &parties.x_mint_rule,
+        &parties.y_mint_rule, 10, 20, terms,
+    )
+    .unwrap();
+    bench.run(&mut context, "init_escrow_with_terms", &[init_vesting], &[&parties.alice]).await;
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    bench.run(&mut context, "withdraw_vested", &[parties.alice_withdraw(5)], &[&parties.alice]).await;
+
+    // an escrow released once the supply of y, at offset 36 of its mint, reaches 100
+    let parties = Parties::new(&mut context, &program_id).await;
+    let condition = Condition::new(parties.y_mint, spl_token::id(), 36, Comparator::Ge, ConditionValue::U64(100));
+    let terms = EscrowTerms { condition, ..EscrowTerms::default() };
+    let init_conditional = instruction::init_escrow_with_terms(
+        &program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &parties.y_mint,
+        &parties.x_vault, &parties.y_vault, &parties.escrow, &parties.config, &parties.x_mint_rule,
+        &parties.y_mint_rule, 10, 20, terms,
+    )
+    .unwrap();
+    bench.run(&mut context, "init_escrow_with_condition", &[init_conditional], &[&parties.alice]).await;
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    let withdraw_conditional = instruction::withdraw_conditional(
+        &program_id, &parties.alice.pubkey(), &parties.y_vault, &parties.escrow, &parties.alice_y, &parties.config,
+        &parties.y_mint, 20,
+    )
+    .unwrap();
+    bench.run(&mut context, "withdraw_conditional", &[withdraw_conditional], &[&parties.alice]).await;
+
+    // an English auction outbid once and settled after its deadline
+    let parties = Parties::new(&mut context, &program_id).await;
+    let (auction, _) =
+        find_english_auction_address(&program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint);
+    let deadline = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+    let init_english = instruction::init_english_auction(
+        &program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint, &parties.alice_x, &parties.config,
+        &parties.x_mint_rule, 10, 1, 0, deadline,
+    )
+    .unwrap();
+    bench.run(&mut context, "init_english_auction", &[init_english], &[&parties.alice]).await;
+    let bid = |amount: u64, previous_bidder_y: Option<&Pubkey>| {
+        instruction::place_bid(
+            &program_id, &parties.bob.pubkey(), &auction, &parties.y_mint, &parties.bob_y, &parties.config,
+            &parties.y_mint_rule, previous_bidder_y, amount,
+        )
+        .unwrap()
+    };
+    bench.run(&mut context, "place_bid", &[bid(20, None)], &[&parties.bob]).await;
+    bench.run(&mut context, "place_bid_outbid", &[bid(30, Some(&parties.bob_y))], &[&parties.bob]).await;
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = deadline;
+    context.set_sysvar(&clock);
+    let settle_english = instruction::settle_english_auction(
+        &program_id, &auction, &parties.x_mint, &parties.y_mint, &parties.alice.pubkey(), &parties.alice_y,
+        &parties.bob_x, &parties.config,
+    )
+    .unwrap();
+    bench.run(&mut context, "settle_english_auction", &[settle_english], &[]).await;
+
+    // a sealed-bid auction with one bid, withdrawn after settlement
+    let parties = Parties::new(&mut context, &program_id).await;
+    let (auction, _) =
+        find_sealed_auction_address(&program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint);
+    let commit_deadline = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+    let init_sealed = instruction::init_sealed_auction(
+        &program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint, &parties.alice_x, &parties.config,
+        &parties.x_mint_rule, 10, 0, 0, commit_deadline, commit_deadline + 1_000,
+    )
+    .unwrap();
+    bench.run(&mut context, "init_sealed_auction", &[init_sealed], &[&parties.alice]).await;
+    let salt = [7; 32];
+    let commitment = sealed_bid_commitment(&parties.bob.pubkey(), 20, &salt);
+    let commit = instruction::commit_bid(
+        &program_id, &parties.bob.pubkey(), &auction, &parties.y_mint, &parties.bob_y, &parties.config, commitment, 30,
+    )
+    .unwrap();
+    bench.run(&mut context, "commit_bid", &[commit], &[&parties.bob]).await;
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = commit_deadline;
+    context.set_sysvar(&clock);
+    let reveal = instruction::reveal_bid(
+        &program_id, &parties.bob.pubkey(), &auction, &parties.config, &parties.y_mint_rule, 20, salt,
+    )
+    .unwrap();
+    bench.run(&mut context, "reveal_bid", &[reveal], &[&parties.bob]).await;
+    clock.unix_timestamp = commit_deadline + 1_000;
+    context.set_sysvar(&clock);
+    let settle_sealed = instruction::settle_sealed_auction(
+        &program_id, &auction, &parties.x_mint, &parties.y_mint, &parties.alice.pubkey(), &parties.alice_y,
+        &parties.bob_x, &parties.config,
+    )
+    .unwrap();
+    bench.run(&mut context, "settle_sealed_auction", &[settle_sealed], &[]).await;
+    let withdraw_collateral = instruction::withdraw_collateral(
+        &program_id, &parties.bob.pubkey(), &auction, &parties.y_mint, &parties.bob_y, &parties.alice.pubkey(),
+        &parties.alice_y,
+    )
+    .unwrap();
+    bench.run(&mut context, "withdraw_collateral", &[withdraw_collateral], &[&parties.bob]).await;
+
+    // a milestone escrow with one tranche approved and the other refunded after the deadline
+    let parties = Parties::new(&mut context, &program_id).await;
+    let (milestone_escrow, _) =
+        find_milestone_escrow_address(&program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint);
+    let deadline = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+    let init_milestones = instruction::init_milestone_escrow(
+        &program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &parties.alice_x,
+        &parties.config, &parties.x_mint_rule, None, vec![10, 20], deadline, Condition::default(),
+    )
+    .unwrap();
+    bench.run(&mut context, "init_milestone_escrow", &[init_milestones], &[&parties.alice]).await;
+    let approve_milestone = instruction::approve_milestone(
+        &program_id, &parties.alice.pubkey(), &milestone_escrow, &parties.alice.pubkey(), &parties.bob_x,
+        &parties.config,
+    )
+    .unwrap();
+    bench.run(&mut context, "approve_milestone", &[approve_milestone], &[&parties.alice]).await;
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = deadline;
+    context.set_sysvar(&clock);
+    let refund_milestones =
+        instruction::refund_milestones(&program_id, &parties.alice.pubkey(), &milestone_escrow, &parties.alice_x)
+            .unwrap();
+    bench.run(&mut context, "refund_milestones", &[refund_milestones], &[&parties.alice]).await;
+
+    // a stream withdrawn from halfway through and then cancelled
+    let parties = Parties::new(&mut context, &program_id).await;
+    let (stream, _) =
+        find_stream_address(&program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint);
+    let (stream_vault, _) = find_stream_vault_address(&program_id, &stream);
+    let now = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    let init_stream = instruction::init_stream(
+        &program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &parties.alice_x,
+        &parties.config, &parties.x_mint_rule, 1, now - 50, now + 50,
+    )
+    .unwrap();
+    bench.run(&mut context, "init_stream", &[init_stream], &[&parties.alice]).await;
+    let withdraw_stream = instruction::withdraw(
+        &program_id, &parties.bob.pubkey(), &stream_vault, &stream, &parties.bob_x, &parties.config, 10,
+    )
+    .unwrap();
+    bench.run(&mut context, "withdraw_stream", &[withdraw_stream], &[&parties.bob]).await;
+    let cancel_stream =
+        instruction::cancel_stream(&program_id, &parties.alice.pubkey(), &stream, &parties.alice_x, &parties.bob_x)
+            .unwrap();
+    bench.run(&mut context, "cancel_stream", &[cancel_stream], &[&parties.alice]).await;
+
+    // a loan accepted and repaid without interest
+    let parties = Parties::new(&mut context, &program_id).await;
+    let deadline = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+    let init_loan = instruction::init_loan(
+        &program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &parties.y_mint,
+        &parties.x_vault, &parties.y_vault, &parties.escrow, &parties.config, &parties.x_mint_rule,
+        &parties.y_mint_rule, &parties.alice_x, 10, 40, 0, deadline,
+    )
+    .unwrap();
+    bench.run(&mut context, "init_loan", &[init_loan], &[&parties.alice]).await;
+    let accept_loan = instruction::accept_loan(
+        &program_id, &parties.bob.pubkey(), &parties.escrow, &parties.bob_y, &parties.alice_y, &parties.config,
+    )
+    .unwrap();
+    bench.run(&mut context, "accept_loan", &[accept_loan], &[&parties.bob]).await;
+    let repay_loan = instruction::repay_loan(
+        &program_id, &parties.alice.pubkey(), &parties.escrow, &parties.x_vault, &parties.alice_x, &parties.alice_y,
+        &parties.bob_y,
+    )
+    .unwrap();
+    bench.run(&mut context, "repay_loan", &[repay_loan], &[&parties.alice]).await;
+
+    // a covered option bought and exercised
+    let parties = Parties::new(&mut context, &program_id).await;
+    let expiry = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+    let write_option = instruction::write_option(
+        &program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint, &parties.alice_x, &parties.config,
+        &parties.x_mint_rule, &parties.y_mint_rule, 10, 5, 30, expiry,
+    )
+    .unwrap();
+    bench.run(&mut context, "write_option", &[write_option], &[&parties.alice]).await;
+    let (option, _) = find_option_address(&program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint);
+    let buy_option = instruction::buy_option(
+        &program_id, &parties.bob.pubkey(), &option, &parties.bob_y, &parties.alice_y, &parties.config,
+    )
+    .unwrap();
+    bench.run(&mut context, "buy_option", &[buy_option], &[&parties.bob]).await;
+    let exercise_option = instruction::exercise_option(
+        &program_id, &parties.bob.pubkey(), &option, &parties.bob_x, &parties.bob_y, &parties.alice.pubkey(),
+        &parties.alice_y, &parties.config,
+    )
+    .unwrap();
+    bench.run(&mut context, "exercise_option", &[exercise_option], &[&parties.bob]).await;
+
+    // admin instructions
+    let init_config = instruction::init_config(&program_id, &payer, &parties.config, &payer).unwrap();
+    bench.run(&mut context, "init_config", &[init_config], &[]).await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let set_mint_rule =
+        instruction::set_mint_rule(&program_id, &payer, &parties.config, &parties.x_mint, &parties.x_mint_rule, true, 1, 0)
+            .unwrap();
+    bench.run(&mut context, "set_mint_rule_create", &[set_mint_rule], &[]).await;
+    let set_mint_rule =
+        instruction::set_mint_rule(&program_id, &payer, &parties.config, &parties.x_mint, &parties.x_mint_rule, true, 1, 1000)
+            .unwrap();
+    bench.run(&mut context, "set_mint_rule_update", &[set_mint_rule], &[]).await;
+    let set_mint_rule =
+        instruction::set_mint_rule(&program_id, &payer, &parties.config, &parties.y_mint, &parties.y_mint_rule, true, 1, 0)
+            .unwrap();
+    send(&mut context, &[set_mint_rule], &[]).await.unwrap();
+    let set_allowlist_only = instruction::set_allowlist_only(&program_id, &payer, &parties.config, true).unwrap();
+    bench.run(&mut context, "set_allowlist_only", &[set_allowlist_only], &[]).await;
+
+    // an escrow under a config with both mints registered, unwound while paused
+    bench.run(&mut context, "init_escrow_with_rules", &[parties.init_escrow(10, 20)], &[&parties.alice]).await;
+    bench.run(&mut context, "deposit_with_config", &[parties.alice_deposit(10)], &[&parties.alice]).await;
+    let set_paused = instruction::set_paused(&program_id, &payer, &parties.config, true).unwrap();
+    bench.run(&mut context, "set_paused", &[set_paused], &[]).await;
+    let unwind = instruction::emergency_unwind(
+        &program_id, &payer, &parties.config, &parties.escrow, &parties.x_vault, &parties.y_vault, &parties.alice_x,
+        &parties.bob_y,
+    )
+    .unwrap();
+    bench.run(&mut context, "emergency_unwind", &[unwind], &[]).await;
+
+    // the same escrow rewritten in the v0 layout
+    let mut account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    account.data = account.data[9..9 + EscrowV0::LEN].to_vec();
+    context.set_account(&parties.escrow, &account.into());
+    let migrate = instruction::migrate(&program_id, &payer, &parties.escrow).unwrap();
+    bench.run(&mut context, "migrate", &[migrate], &[]).await;
+    let close = parties.close_escrow(&parties.alice.pubkey(), &parties.alice.pubkey());
+    bench.run(&mut context, "close_escrow", &[close], &[&parties.alice]).await;
+
+    bench.check();
+}
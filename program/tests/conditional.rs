@@ -0,0 +1,236 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{Comparator, Condition, ConditionValue, Escrow, EscrowTerms},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Byte offsets in the stand-in oracle's feed: a price, a halted flag and the key of the
+/// feed's publisher.
+const PRICE: u32 = 8;
+const HALTED: u32 = 16;
+const PUBLISHER: u32 = 17;
+
+/// Stand-in oracle program. Writes everything after the first byte of the instruction data
+/// into the feed at the offset given by that byte.
+fn process_oracle(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let (offset, value) = data.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+    let offset = usize::from(*offset);
+    let feed = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let mut feed_data = feed.data.borrow_mut();
+    feed_data
+        .get_mut(offset..offset + value.len())
+        .ok_or(ProgramError::InvalidInstructionData)?
+        .copy_from_slice(value);
+    Ok(())
+}
+
+/// Alice trading 10 x for 20 y once the oracle's feed allows it.
+struct Conditional {
+    parties: Parties,
+    oracle: Pubkey,
+    feed: Pubkey,
+}
+
+impl Conditional {
+    /// Starts the escrow program next to the oracle, with a zeroed feed owned by the oracle
+    /// and a lookalike feed owned by another program.
+    async fn start(program_id: &Pubkey) -> (ProgramTestContext, Self, Pubkey) {
+        let oracle = Pubkey::new_unique();
+        let feed = Pubkey::new_unique();
+        let lookalike = Pubkey::new_unique();
+        let mut program_test = program_test(program_id);
+        program_test.add_program("stand_in_oracle", oracle, processor!(process_oracle));
+        for (address, owner) in [(feed, oracle), (lookalike, Pubkey::new_unique())] {
+            let account = Account { lamports: 1_000_000_000, data: vec![0; 64], owner, ..Account::default() };
+            program_test.add_account(address, account);
+        }
+        let mut context = program_test.start_with_context().await;
+        let parties = Parties::new(&mut context, program_id).await;
+        (context, Self { parties, oracle, feed }, lookalike)
+    }
+
+    fn condition(&self, offset: u32, comparator: Comparator, threshold: ConditionValue) -> Condition {
+        Condition::new(self.feed, self.oracle, offset, comparator, threshold)
+    }
+
+    fn init(&self, condition: Condition) -> Instruction {
+        let p = &self.parties;
+        let terms = EscrowTerms { condition, ..EscrowTerms::default() };
+        instruction::init_escrow_with_terms(
+            &p.program_id, &p.alice.pubkey(), &p.bob.pubkey(), &p.x_mint, &p.y_mint, &p.x_vault, &p.y_vault,
+            &p.escrow, &p.config, &p.x_mint_rule, &p.y_mint_rule, 10, 20, terms,
+        )
+        .unwrap()
+    }
+
+    fn alice_withdraw(&self, condition_account: &Pubkey) -> Instruction {
+        let p = &self.parties;
+        instruction::withdraw_conditional(
+            &p.program_id, &p.alice.pubkey(), &p.y_vault, &p.escrow, &p.alice_y, &p.config, condition_account, 20,
+        )
+        .unwrap()
+    }
+
+    fn bob_withdraw(&self) -> Instruction {
+        let p = &self.parties;
+        instruction::withdraw_conditional(
+            &p.program_id, &p.bob.pubkey(), &p.x_vault, &p.escrow, &p.bob_x, &p.config, &self.feed, 10,
+        )
+        .unwrap()
+    }
+
+    /// Publishes `value` at `offset` in the feed.
+    fn post(&self, offset: u32, value: &[u8]) -> Instruction {
+        let mut data = vec![offset as u8];
+        data.extend_from_slice(value);
+        Instruction::new_with_bytes(self.oracle, &data, vec![AccountMeta::new(self.feed, false)])
+    }
+
+    async fn deposit_both(&self, context: &mut ProgramTestContext) {
+        let p = &self.parties;
+        send(context, &[p.alice_deposit(10)], &[&p.alice]).await.unwrap();
+        send(context, &[p.bob_deposit(20)], &[&p.bob]).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_conditional_released_once_price_is_met() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, c, _) = Conditional::start(&program_id).await;
+    let p = &c.parties;
+
+    let condition = c.condition(PRICE, Comparator::Ge, ConditionValue::U64(100));
+    send(&mut context, &[c.init(condition)], &[&p.alice]).await.unwrap();
+    let account = context.banks_client.get_account(p.escrow).await.unwrap().unwrap();
+    assert_eq!(Escrow::load(&account.data).unwrap().condition, condition);
+    c.deposit_both(&mut context).await;
+
+    let result = send(&mut context, &[c.alice_withdraw(&c.feed)], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::ConditionNotMet as u32);
+    send(&mut context, &[c.post(PRICE, &99u64.to_le_bytes())], &[]).await.unwrap();
+    refresh_blockhash(&mut context).await;
+    let result = send(&mut context, &[c.alice_withdraw(&c.feed)], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::ConditionNotMet as u32);
+
+    send(&mut context, &[c.post(PRICE, &150u64.to_le_bytes())], &[]).await.unwrap();
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[c.alice_withdraw(&c.feed)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[c.bob_withdraw()], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+}
+
+#[tokio::test]
+async fn test_conditional_checks_the_account() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, c, lookalike) = Conditional::start(&program_id).await;
+    let p = &c.parties;
+    let condition = c.condition(PRICE, Comparator::Eq, ConditionValue::U64(0));
+    send(&mut context, &[c.init(condition)], &[&p.alice]).await.unwrap();
+    c.deposit_both(&mut context).await;
+
+    // the condition holds on the lookalike's zeroed data, but only the feed counts
+    let result = send(&mut context, &[c.alice_withdraw(&lookalike)], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidConditionAccount as u32);
+    let result = send(&mut context, &[p.alice_withdraw(20)], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidConditionAccount as u32);
+    assert_eq!(token_balance(&mut context, &p.y_vault).await, 20);
+}
+
+#[tokio::test]
+async fn test_conditional_checks_the_owner() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, c, _) = Conditional::start(&program_id).await;
+    let p = &c.parties;
+
+    // a feed that is not owned by the program named in the condition is not trusted
+    let condition = Condition::new(c.feed, Pubkey::new_unique(), PRICE, Comparator::Eq, ConditionValue::U64(0));
+    send(&mut context, &[c.init(condition)], &[&p.alice]).await.unwrap();
+    c.deposit_both(&mut context).await;
+    let result = send(&mut context, &[c.alice_withdraw(&c.feed)], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidConditionAccount as u32);
+}
+
+#[tokio::test]
+async fn test_conditional_refunds_not_gated() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, c, _) = Conditional::start(&program_id).await;
+    let p = &c.parties;
+    send(&mut context, &[c.post(HALTED, &[1])], &[]).await.unwrap();
+    send(&mut context, &[c.init(c.condition(HALTED, Comparator::Eq, ConditionValue::Bool(false)))], &[&p.alice])
+        .await
+        .unwrap();
+
+    // alice takes back her unmatched deposit while the feed is halted, without the feed
+    send(&mut context, &[p.alice_deposit(10)], &[&p.alice]).await.unwrap();
+    let refund = instruction::withdraw(&program_id, &p.alice.pubkey(), &p.x_vault, &p.escrow, &p.alice_x, &p.config, 10)
+        .unwrap();
+    send(&mut context, &[refund], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 100);
+
+    refresh_blockhash(&mut context).await;
+    c.deposit_both(&mut context).await;
+    let result = send(&mut context, &[c.bob_withdraw()], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::ConditionNotMet as u32);
+    send(&mut context, &[c.post(HALTED, &[0])], &[]).await.unwrap();
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[c.bob_withdraw()], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+}
+
+#[tokio::test]
+async fn test_conditional_settle_delegated() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, c, _) = Conditional::start(&program_id).await;
+    let p = &c.parties;
+    let publisher = Keypair::new().pubkey();
+    let condition = c.condition(PUBLISHER, Comparator::Eq, ConditionValue::Pubkey(publisher));
+    send(&mut context, &[c.init(condition)], &[&p.alice]).await.unwrap();
+    for (source, owner, amount) in [(&p.alice_x, &p.alice, 10), (&p.bob_y, &p.bob, 20)] {
+        let approve =
+            spl_token::instruction::approve(&spl_token::id(), source, &p.escrow, &owner.pubkey(), &[], amount)
+                .unwrap();
+        send(&mut context, &[approve], &[owner]).await.unwrap();
+    }
+
+    let mut settle = p.settle_delegated();
+    settle.accounts.push(AccountMeta::new_readonly(c.feed, false));
+    let result = send(&mut context, &[settle.clone()], &[]).await;
+    assert_custom_error(result, EscrowError::ConditionNotMet as u32);
+    send(&mut context, &[c.post(PUBLISHER, publisher.as_ref())], &[]).await.unwrap();
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[settle], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+}
+
+#[tokio::test]
+async fn test_conditional_invalid_terms() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, c, _) = Conditional::start(&program_id).await;
+    let alice = &c.parties.alice;
+
+    let unordered = c.condition(HALTED, Comparator::Gt, ConditionValue::Bool(false));
+    let mut no_account = c.condition(PRICE, Comparator::Ge, ConditionValue::U64(100));
+    no_account.account = Pubkey::default();
+    let mut unknown_type = no_account;
+    unknown_type.account = c.feed;
+    unknown_type.value_type = 0;
+    for condition in [unordered, no_account, unknown_type] {
+        let result = send(&mut context, &[c.init(condition)], &[alice]).await;
+        assert_custom_error(result, EscrowError::InvalidCondition as u32);
+    }
+}
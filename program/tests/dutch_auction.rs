@@ -0,0 +1,181 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{find_dutch_auction_address, Escrow},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar::clock::Clock,
+};
+
+/// Alice's 10 x against y falling from `start_amount_b` to `end_amount_b`, with times relative
+/// to the current clock.
+async fn init_dutch(
+    context: &mut ProgramTestContext,
+    parties: &Parties,
+    start_amount_b: u64,
+    end_amount_b: u64,
+    start_offset: i64,
+    end_offset: i64,
+) -> Instruction {
+    let now = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    instruction::init_dutch_escrow(
+        &parties.program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint, &parties.x_vault,
+        &parties.y_vault, &parties.escrow, &parties.config, &parties.x_mint_rule, &parties.y_mint_rule, 10,
+        start_amount_b, end_amount_b, now + start_offset, now + end_offset,
+    )
+    .unwrap()
+}
+
+fn bob_deposit_dutch(parties: &Parties, max_amount: u64) -> Instruction {
+    instruction::deposit_dutch(
+        &parties.program_id, &parties.bob.pubkey(), &parties.y_vault, &parties.escrow, &parties.bob_y,
+        &parties.config, max_amount,
+    )
+    .unwrap()
+}
+
+async fn escrow_size_b(context: &mut ProgramTestContext, parties: &Parties) -> u64 {
+    let account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    Escrow::load(&account.data).unwrap().size_b.into()
+}
+
+#[tokio::test]
+async fn test_dutch_trade_at_current_price() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await.open();
+    let (dutch_auction, _) = find_dutch_auction_address(&program_id, &parties.escrow);
+
+    // halfway through a slow auction from 100 down to 20, the price is close to 60
+    let init = init_dutch(&mut context, &parties, 100, 20, -10_000, 10_000).await;
+    send(&mut context, &[init], &[&parties.alice]).await.unwrap();
+    assert_eq!(escrow_size_b(&mut context, &parties).await, 0);
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[bob_deposit_dutch(&parties, 70)], &[&parties.bob]).await.unwrap();
+
+    let price = escrow_size_b(&mut context, &parties).await;
+    assert!((55..=65).contains(&price), "price {}", price);
+    assert_eq!(token_balance(&mut context, &parties.y_vault).await, price);
+    assert!(context.banks_client.get_account(dutch_auction).await.unwrap().is_none());
+
+    // from here on the escrow trades at the price bob paid
+    send(&mut context, &[parties.alice_withdraw(price)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_withdraw(10)], &[&parties.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, price);
+    assert_eq!(token_balance(&mut context, &parties.bob_y).await, 100 - price);
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+}
+
+#[tokio::test]
+async fn test_dutch_price_above_limit() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await.open();
+
+    // not started yet, so the price is the start amount
+    let init = init_dutch(&mut context, &parties, 50, 20, 10_000, 20_000).await;
+    send(&mut context, &[init], &[&parties.alice]).await.unwrap();
+    let result = send(&mut context, &[bob_deposit_dutch(&parties, 49)], &[&parties.bob]).await;
+    assert_custom_error(result, EscrowError::PriceAboveLimit as u32);
+
+    // a fixed size deposit or one without the auction account is not a bid
+    let result = send(&mut context, &[parties.bob_deposit(50)], &[&parties.bob]).await;
+    assert_custom_error(result, EscrowError::InvalidDutchAuction as u32);
+
+    send(&mut context, &[bob_deposit_dutch(&parties, 80)], &[&parties.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.bob_y).await, 50);
+}
+
+#[tokio::test]
+async fn test_dutch_price_after_end() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await.open();
+
+    let init = init_dutch(&mut context, &parties, 50, 20, -20_000, -10_000).await;
+    send(&mut context, &[init], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[bob_deposit_dutch(&parties, 20)], &[&parties.bob]).await.unwrap();
+    assert_eq!(escrow_size_b(&mut context, &parties).await, 20);
+}
+
+#[tokio::test]
+async fn test_dutch_invalid_terms() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await.open();
+
+    for (start_amount_b, end_amount_b, start_offset, end_offset) in [(50, 20, 10, 10), (50, 0, 0, 10), (20, 50, 0, 10)] {
+        let init = init_dutch(&mut context, &parties, start_amount_b, end_amount_b, start_offset, end_offset).await;
+        let result = send(&mut context, &[init], &[&parties.alice]).await;
+        assert_custom_error(result, EscrowError::InvalidDutchAuction as u32);
+    }
+}
+
+#[tokio::test]
+async fn test_close_unpriced_dutch_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await.open();
+    let (dutch_auction, _) = find_dutch_auction_address(&program_id, &parties.escrow);
+    let alice = parties.alice.pubkey();
+    let alice_lamports = context.banks_client.get_balance(alice).await.unwrap();
+
+    let init = init_dutch(&mut context, &parties, 50, 20, 0, 10_000).await;
+    send(&mut context, &[init], &[&parties.alice]).await.unwrap();
+    let result = send(&mut context, &[parties.settle_delegated()], &[]).await;
+    assert_custom_error(result, EscrowError::UnpricedDutchAuction as u32);
+
+    let close = instruction::close_dutch_escrow(
+        &program_id, &alice, &parties.escrow, &parties.x_vault, &parties.y_vault, &alice,
+    )
+    .unwrap();
+    send(&mut context, &[close], &[&parties.alice]).await.unwrap();
+    assert_eq!(context.banks_client.get_balance(alice).await.unwrap(), alice_lamports);
+    for address in [parties.escrow, dutch_auction] {
+        assert!(context.banks_client.get_account(address).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_dutch_first_taker_fills() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await.open();
+    let carol = Keypair::new();
+    airdrop(&mut context, &carol.pubkey(), 1_000_000_000).await;
+    let carol_x = create_token_account(&mut context, &parties.x_mint, &carol.pubkey(), 0).await;
+    let carol_y = create_token_account(&mut context, &parties.y_mint, &carol.pubkey(), 100).await;
+
+    let init = init_dutch(&mut context, &parties, 50, 20, -20_000, -10_000).await;
+    send(&mut context, &[init], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+
+    // carol bids first and takes bob's place
+    let carol_deposit = instruction::deposit_dutch(
+        &program_id, &carol.pubkey(), &parties.y_vault, &parties.escrow, &carol_y, &parties.config, 20,
+    )
+    .unwrap();
+    send(&mut context, &[carol_deposit], &[&carol]).await.unwrap();
+    let account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    assert_eq!(Escrow::load(&account.data).unwrap().party_b, carol.pubkey());
+
+    // the auction is taken
+    let result = send(&mut context, &[bob_deposit_dutch(&parties, 50)], &[&parties.bob]).await;
+    assert_custom_error(result, EscrowError::InvalidParty as u32);
+
+    // the escrow still signs with the seeds it was created under
+    let carol_withdraw =
+        instruction::withdraw(&program_id, &carol.pubkey(), &parties.x_vault, &parties.escrow, &carol_x, &parties.config, 10)
+            .unwrap();
+    send(&mut context, &[parties.alice_withdraw(20), carol_withdraw], &[&parties.alice, &carol]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &carol_x).await, 10);
+    assert_eq!(token_balance(&mut context, &carol_y).await, 80);
+}
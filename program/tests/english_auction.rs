@@ -0,0 +1,216 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{find_auction_vault_address, find_english_auction_address, EnglishAuction},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar::clock::Clock,
+};
+
+/// Alice auctioning 10 x for y, with bob and carol holding 100 y each.
+struct Auction {
+    parties: Parties,
+    carol: Keypair,
+    carol_x: Pubkey,
+    carol_y: Pubkey,
+    auction: Pubkey,
+    deadline: i64,
+}
+
+impl Auction {
+    async fn new(context: &mut ProgramTestContext, program_id: &Pubkey) -> Self {
+        let parties = Parties::new(context, program_id).await;
+        let carol = Keypair::new();
+        airdrop(context, &carol.pubkey(), 1_000_000_000).await;
+        let carol_x = create_token_account(context, &parties.x_mint, &carol.pubkey(), 0).await;
+        let carol_y = create_token_account(context, &parties.y_mint, &carol.pubkey(), 100).await;
+        let (auction, _) =
+            find_english_auction_address(program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint);
+        let deadline = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+        Self { parties, carol, carol_x, carol_y, auction, deadline }
+    }
+
+    fn init(&self, min_increment: u64, reserve_price: u64) -> Instruction {
+        let parties = &self.parties;
+        instruction::init_english_auction(
+            &parties.program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint, &parties.alice_x,
+            &parties.config, &parties.x_mint_rule, 10, min_increment, reserve_price, self.deadline,
+        )
+        .unwrap()
+    }
+
+    fn bid(&self, bidder: &Keypair, bidder_y: &Pubkey, previous_bidder_y: Option<&Pubkey>, amount: u64) -> Instruction {
+        let parties = &self.parties;
+        instruction::place_bid(
+            &parties.program_id, &bidder.pubkey(), &self.auction, &parties.y_mint, bidder_y, &parties.config,
+            &parties.y_mint_rule, previous_bidder_y, amount,
+        )
+        .unwrap()
+    }
+
+    fn settle(&self, x_destination: &Pubkey) -> Instruction {
+        let parties = &self.parties;
+        instruction::settle_english_auction(
+            &parties.program_id, &self.auction, &parties.x_mint, &parties.y_mint, &parties.alice.pubkey(),
+            &parties.alice_y, x_destination, &parties.config,
+        )
+        .unwrap()
+    }
+
+    /// Moves the clock to the deadline.
+    async fn end(&self, context: &mut ProgramTestContext) {
+        let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = self.deadline;
+        context.set_sysvar(&clock);
+    }
+}
+
+#[tokio::test]
+async fn test_english_auction() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+    let (x_vault, _) = find_auction_vault_address(&program_id, &a.auction, &p.x_mint);
+    let (y_vault, _) = find_auction_vault_address(&program_id, &a.auction, &p.y_mint);
+
+    send(&mut context, &[a.init(5, 0)], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &x_vault).await, 10);
+
+    // each bid refunds the one it beats
+    send(&mut context, &[a.bid(&p.bob, &p.bob_y, None, 20)], &[&p.bob]).await.unwrap();
+    send(&mut context, &[a.bid(&a.carol, &a.carol_y, Some(&p.bob_y), 25)], &[&a.carol]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 100);
+    send(&mut context, &[a.bid(&p.bob, &p.bob_y, Some(&a.carol_y), 40)], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &a.carol_y).await, 100);
+    assert_eq!(token_balance(&mut context, &y_vault).await, 40);
+
+    let account = context.banks_client.get_account(a.auction).await.unwrap().unwrap();
+    let auction = EnglishAuction::load(&account.data).unwrap();
+    assert_eq!((auction.highest_bidder, auction.highest_bid), (p.bob.pubkey(), 40));
+
+    a.end(&mut context).await;
+    send(&mut context, &[a.settle(&p.bob_x)], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 60);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 40);
+    for account in [a.auction, x_vault, y_vault] {
+        assert!(context.banks_client.get_account(account).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_english_auction_bid_too_low() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+    send(&mut context, &[a.init(5, 20)], &[&p.alice]).await.unwrap();
+
+    // below the reserve
+    let result = send(&mut context, &[a.bid(&p.bob, &p.bob_y, None, 19)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::BidTooLow as u32);
+    send(&mut context, &[a.bid(&p.bob, &p.bob_y, None, 20)], &[&p.bob]).await.unwrap();
+
+    // below the minimum increment
+    let result = send(&mut context, &[a.bid(&a.carol, &a.carol_y, Some(&p.bob_y), 24)], &[&a.carol]).await;
+    assert_custom_error(result, EscrowError::BidTooLow as u32);
+
+    // the refund has to go to the highest bidder
+    let result = send(&mut context, &[a.bid(&a.carol, &a.carol_y, Some(&a.carol_y), 25)], &[&a.carol]).await;
+    assert_custom_error(result, EscrowError::InvalidParty as u32);
+
+    // the seller cannot bid on their own lot
+    let result = send(&mut context, &[a.bid(&p.alice, &p.alice_y, Some(&p.bob_y), 25)], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::IdenticalParties as u32);
+}
+
+#[tokio::test]
+async fn test_english_auction_deadline() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+    send(&mut context, &[a.init(1, 0)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[a.bid(&p.bob, &p.bob_y, None, 20)], &[&p.bob]).await.unwrap();
+
+    let result = send(&mut context, &[a.settle(&p.bob_x)], &[]).await;
+    assert_custom_error(result, EscrowError::AuctionNotEnded as u32);
+
+    a.end(&mut context).await;
+    let result = send(&mut context, &[a.bid(&a.carol, &a.carol_y, Some(&p.bob_y), 30)], &[&a.carol]).await;
+    assert_custom_error(result, EscrowError::AuctionEnded as u32);
+
+    // the lot only goes to the highest bidder
+    let result = send(&mut context, &[a.settle(&a.carol_x)], &[]).await;
+    assert_custom_error(result, EscrowError::InvalidParty as u32);
+    send(&mut context, &[a.settle(&p.bob_x)], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 20);
+}
+
+#[tokio::test]
+async fn test_english_auction_settle_while_paused() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+    send(&mut context, &[a.init(1, 0)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[a.bid(&p.bob, &p.bob_y, None, 20)], &[&p.bob]).await.unwrap();
+    a.end(&mut context).await;
+
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &p.config, &payer).unwrap();
+    let set_paused = instruction::set_paused(&program_id, &payer, &p.config, true).unwrap();
+    send(&mut context, &[init_config, set_paused], &[]).await.unwrap();
+    let result = send(&mut context, &[a.settle(&p.bob_x)], &[]).await;
+    assert_custom_error(result, EscrowError::Paused as u32);
+
+    refresh_blockhash(&mut context).await;
+    let set_unpaused = instruction::set_paused(&program_id, &payer, &p.config, false).unwrap();
+    send(&mut context, &[set_unpaused], &[]).await.unwrap();
+    send(&mut context, &[a.settle(&p.bob_x)], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+}
+
+#[tokio::test]
+async fn test_english_auction_without_bids() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+    send(&mut context, &[a.init(1, 50)], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 90);
+
+    a.end(&mut context).await;
+    send(&mut context, &[a.settle(&p.alice_x)], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 100);
+    assert!(context.banks_client.get_account(a.auction).await.unwrap().is_none());
+
+    // the same lot can be auctioned again once settled
+    refresh_blockhash(&mut context).await;
+    let mut a = a;
+    a.deadline = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+    send(&mut context, &[a.init(1, 50)], &[&a.parties.alice]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_english_auction_invalid_terms() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let mut a = Auction::new(&mut context, &program_id).await;
+
+    let result = send(&mut context, &[a.init(0, 0)], &[&a.parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidAuction as u32);
+
+    a.deadline -= 2_000;
+    let result = send(&mut context, &[a.init(1, 0)], &[&a.parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidAuction as u32);
+}
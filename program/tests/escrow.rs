@@ -0,0 +1,191 @@
+mod common;
+
+use common::*;
+use solana_escrow::{error::EscrowError, instruction};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::Signer,
+    transaction::TransactionError,
+};
+
+#[tokio::test]
+async fn test_trade() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    send(&mut context, &[parties.alice_withdraw(20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_withdraw(10)], &[&parties.bob]).await.unwrap();
+
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 90);
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &parties.bob_y).await, 80);
+}
+
+#[tokio::test]
+async fn test_deposit_into_other_account_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+
+    // alice "deposits" into a second account of her own instead of the x vault
+    let decoy = create_token_account(&mut context, &parties.x_mint, &parties.alice.pubkey(), 0).await;
+    let deposit =
+        instruction::deposit(&program_id, &parties.alice.pubkey(), &decoy, &parties.escrow, &parties.alice_x, &parties.config, 10)
+            .unwrap();
+    let result = send(&mut context, &[deposit], &[&parties.alice]).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+    );
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 100);
+}
+
+#[tokio::test]
+async fn test_init_escrow_with_prefunded_pdas() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    // the least a system account can hold, and more than the vault needs
+    let rent = context.banks_client.get_rent().await.unwrap();
+    airdrop(&mut context, &parties.x_vault, rent.minimum_balance(0)).await;
+    airdrop(&mut context, &parties.y_vault, 10_000_000).await;
+    airdrop(&mut context, &parties.escrow, rent.minimum_balance(0)).await;
+
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    send(&mut context, &[parties.alice_withdraw(20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_withdraw(10)], &[&parties.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+
+    let y_vault = context.banks_client.get_account(parties.y_vault).await.unwrap().unwrap();
+    assert_eq!(y_vault.owner, spl_token::id());
+    assert_eq!(y_vault.lamports, 10_000_000);
+    let escrow = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    assert_eq!(escrow.owner, program_id);
+    assert!(escrow.lamports >= rent.minimum_balance(escrow.data.len()));
+}
+
+#[tokio::test]
+async fn test_init_config_with_prefunded_pda() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let rent = context.banks_client.get_rent().await.unwrap();
+    airdrop(&mut context, &parties.config, rent.minimum_balance(0)).await;
+
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &parties.config, &payer).unwrap();
+    send(&mut context, &[init_config], &[]).await.unwrap();
+    let set_paused = instruction::set_paused(&program_id, &payer, &parties.config, true).unwrap();
+    send(&mut context, &[set_paused], &[]).await.unwrap();
+
+    let result = send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::Paused as u32);
+}
+
+#[tokio::test]
+async fn test_emergency_unwind_rejects_other_token_program() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &parties.config, &payer).unwrap();
+    send(&mut context, &[init_config], &[]).await.unwrap();
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    let set_paused = instruction::set_paused(&program_id, &payer, &parties.config, true).unwrap();
+    send(&mut context, &[set_paused], &[]).await.unwrap();
+
+    // the escrow would sign the refund for whatever program is passed as the token program
+    let unwind = instruction::emergency_unwind(
+        &program_id, &payer, &parties.config, &parties.escrow, &parties.x_vault, &parties.y_vault, &parties.alice_x,
+        &parties.bob_y,
+    )
+    .unwrap();
+    let mut bogus = unwind.clone();
+    bogus.accounts[7].pubkey = Pubkey::new_unique();
+    let result = send(&mut context, &[bogus], &[]).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+    );
+    assert_eq!(token_balance(&mut context, &parties.x_vault).await, 10);
+
+    send(&mut context, &[unwind], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 100);
+}
+
+#[tokio::test]
+async fn test_init_escrow_with_denied_mint() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &parties.config, &payer).unwrap();
+    let deny_x = instruction::set_mint_rule(
+        &program_id, &payer, &parties.config, &parties.x_mint, &parties.x_mint_rule, false, 0, 0,
+    )
+    .unwrap();
+    send(&mut context, &[init_config, deny_x], &[]).await.unwrap();
+
+    let result = send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::MintNotAllowed as u32);
+}
+
+#[tokio::test]
+async fn test_init_escrow_allowlist_only() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &parties.config, &payer).unwrap();
+    let allowlist_only = instruction::set_allowlist_only(&program_id, &payer, &parties.config, true).unwrap();
+    send(&mut context, &[init_config, allowlist_only], &[]).await.unwrap();
+
+    // neither mint has a rule yet
+    let result = send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::MintNotAllowed as u32);
+
+    let allow = |mint, mint_rule| {
+        instruction::set_mint_rule(&program_id, &payer, &parties.config, mint, mint_rule, true, 0, 0).unwrap()
+    };
+    let allow_x = allow(&parties.x_mint, &parties.x_mint_rule);
+    let allow_y = allow(&parties.y_mint, &parties.y_mint_rule);
+    send(&mut context, &[allow_x], &[]).await.unwrap();
+    let result = send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::MintNotAllowed as u32);
+
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[allow_y], &[]).await.unwrap();
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_set_mint_rule_requires_admin() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &parties.config, &payer).unwrap();
+    send(&mut context, &[init_config], &[]).await.unwrap();
+
+    let alice = parties.alice.pubkey();
+    let deny_x = instruction::set_mint_rule(
+        &program_id, &alice, &parties.config, &parties.x_mint, &parties.x_mint_rule, false, 0, 0,
+    )
+    .unwrap();
+    let result = send(&mut context, &[deny_x], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::Unauthorized as u32);
+    assert!(context.banks_client.get_account(parties.x_mint_rule).await.unwrap().is_none());
+}
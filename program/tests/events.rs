@@ -0,0 +1,91 @@
+//! Events decoded from the logs of the BPF build. The native `processor!` used by most tests
+//! prints `sol_log_data` to stdout instead of the transaction's logs.
+//!
+//! Run with `cargo test-bpf --test events`.
+#![cfg(feature = "test-bpf")]
+
+mod common;
+
+use common::*;
+use solana_escrow::{
+    event::{EscrowEvent, EscrowEventKind},
+    instruction,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+/// Sends `instructions` and returns the events the escrow program logged, read from a
+/// simulation of the same transaction.
+async fn send_for_events(
+    context: &mut ProgramTestContext,
+    program_id: &Pubkey,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Vec<EscrowEvent> {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    let transaction =
+        Transaction::new_signed_with_payer(instructions, Some(&context.payer.pubkey()), &all_signers, blockhash);
+
+    let simulation = context.banks_client.simulate_transaction(transaction.clone()).await.unwrap();
+    simulation.result.unwrap().unwrap();
+    let logs = simulation.simulation_details.unwrap().logs;
+    context.banks_client.process_transaction(transaction).await.unwrap();
+    EscrowEvent::from_logs(program_id, &logs)
+}
+
+#[tokio::test]
+async fn test_events_decoded_from_logs() {
+    let program_id = Pubkey::new_unique();
+    let mut context = ProgramTest::new("solana_escrow", program_id, None).start_with_context().await;
+    let p = Parties::new(&mut context, &program_id).await;
+    let (alice, bob) = (p.alice.pubkey(), p.bob.pubkey());
+    let event = |kind, party, mint, amount, old_state, new_state| EscrowEvent {
+        kind,
+        escrow: p.escrow,
+        party,
+        mint,
+        amount,
+        old_state,
+        new_state,
+    };
+
+    // a full trade
+    let events = send_for_events(&mut context, &program_id, &[p.init_escrow(10, 20)], &[&p.alice]).await;
+    assert_eq!(events, vec![event(EscrowEventKind::EscrowCreated, alice, p.x_mint, 10, 0, 0)]);
+    let events = send_for_events(&mut context, &program_id, &[p.alice_deposit(10)], &[&p.alice]).await;
+    assert_eq!(events, vec![event(EscrowEventKind::Deposited, alice, p.x_mint, 10, 0, 1)]);
+    let events = send_for_events(&mut context, &program_id, &[p.bob_deposit(20)], &[&p.bob]).await;
+    assert_eq!(events, vec![event(EscrowEventKind::Deposited, bob, p.y_mint, 20, 1, 3)]);
+    let events = send_for_events(&mut context, &program_id, &[p.alice_withdraw(20)], &[&p.alice]).await;
+    assert_eq!(events, vec![event(EscrowEventKind::Withdrawn, alice, p.y_mint, 20, 3, 4)]);
+    let events = send_for_events(&mut context, &program_id, &[p.bob_withdraw(10)], &[&p.bob]).await;
+    assert_eq!(
+        events,
+        vec![
+            event(EscrowEventKind::Withdrawn, bob, p.x_mint, 10, 4, 0),
+            event(EscrowEventKind::Settled, bob, p.x_mint, 10, 4, 0),
+        ]
+    );
+
+    // a refunded deposit, then the empty escrow is closed
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[p.alice_deposit(10)], &[&p.alice]).await.unwrap();
+    let refund = instruction::withdraw(&program_id, &alice, &p.x_vault, &p.escrow, &p.alice_x, &p.config, 10).unwrap();
+    let events = send_for_events(&mut context, &program_id, &[refund], &[&p.alice]).await;
+    assert_eq!(
+        events,
+        vec![
+            event(EscrowEventKind::Withdrawn, alice, p.x_mint, 10, 1, 0),
+            event(EscrowEventKind::Cancelled, alice, p.x_mint, 10, 1, 0),
+        ]
+    );
+    let events = send_for_events(&mut context, &program_id, &[p.close_escrow(&alice, &alice)], &[&p.alice]).await;
+    assert_eq!(events, vec![event(EscrowEventKind::Closed, alice, Pubkey::default(), 0, 0, 0)]);
+}
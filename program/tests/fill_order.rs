@@ -0,0 +1,128 @@
+mod common;
+
+use common::*;
+use solana_escrow::{error::EscrowError, instruction, state::{find_order_nonces_address, OrderNonces}};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::Signer,
+    transaction::TransactionError,
+    transport::TransportError,
+};
+use std::convert::TryInto;
+
+/// Asserts that `result` failed in `FillOrder`, after the signature verification, with `error`.
+fn assert_fill_error(result: Result<(), TransportError>, error: EscrowError) {
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(1, InstructionError::Custom(error as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_fill_order() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.approve_orders(25)], &[&parties.alice]).await.unwrap();
+
+    // one approval backs two orders, the second one on the same nonces account
+    let order = parties.order(10, 20, 7);
+    send(&mut context, &parties.fill_order(&order), &[&parties.bob]).await.unwrap();
+    send(&mut context, &parties.fill_order(&parties.order(15, 30, 8)), &[&parties.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 75);
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 50);
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 25);
+    assert_eq!(token_balance(&mut context, &parties.bob_y).await, 50);
+
+    let (order_nonces, _) = find_order_nonces_address(&program_id, &parties.alice.pubkey(), 7);
+    let account = context.banks_client.get_account(order_nonces).await.unwrap().unwrap();
+    let nonces = OrderNonces::load(&account.data).unwrap();
+    assert!(nonces.is_used(7) && nonces.is_used(8) && !nonces.is_used(9));
+}
+
+#[tokio::test]
+async fn test_fill_order_replay_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.approve_orders(100)], &[&parties.alice]).await.unwrap();
+
+    let order = parties.order(10, 20, OrderNonces::NONCES + 3);
+    send(&mut context, &parties.fill_order(&order), &[&parties.bob]).await.unwrap();
+    refresh_blockhash(&mut context).await;
+    let result = send(&mut context, &parties.fill_order(&order), &[&parties.bob]).await;
+    assert_fill_error(result, EscrowError::OrderNonceUsed);
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 90);
+}
+
+#[tokio::test]
+async fn test_fill_order_signature_checked() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.approve_orders(100)], &[&parties.alice]).await.unwrap();
+
+    // no ed25519 instruction at all
+    let [_, fill] = parties.fill_order(&parties.order(10, 20, 0));
+    let result = send(&mut context, &[fill], &[&parties.bob]).await;
+    assert_custom_error(result, EscrowError::InvalidOrderSignature as u32);
+
+    // alice's signature over different terms
+    let [verify, _] = parties.fill_order(&parties.order(10, 20, 0));
+    let [_, fill] = parties.fill_order(&parties.order(50, 1, 0));
+    let result = send(&mut context, &[verify, fill], &[&parties.bob]).await;
+    assert_fill_error(result, EscrowError::InvalidOrderSignature);
+
+    // bob's signature over an order made out in alice's name
+    let order = parties.order(10, 20, 0);
+    let signature = parties.bob.sign_message(&order.message(&program_id).unwrap());
+    let mut verify = instruction::verify_order(&program_id, &order, signature.as_ref().try_into().unwrap()).unwrap();
+    verify.data[16..48].copy_from_slice(parties.bob.pubkey().as_ref());
+    let [_, fill] = parties.fill_order(&order);
+    let result = send(&mut context, &[verify, fill], &[&parties.bob]).await;
+    assert_fill_error(result, EscrowError::InvalidOrderSignature);
+
+    // alice's signature over the bare order, and over the order for another program
+    let other_program_id = Pubkey::new_unique();
+    let messages = [borsh::BorshSerialize::try_to_vec(&order).unwrap(), order.message(&other_program_id).unwrap()];
+    for message in messages.iter() {
+        let signature: [u8; 64] = parties.alice.sign_message(message).as_ref().try_into().unwrap();
+        let mut verify = instruction::verify_order(&program_id, &order, &signature).unwrap();
+        verify.data.truncate(verify.data.len() - order.message(&program_id).unwrap().len());
+        verify.data.extend_from_slice(message);
+        verify.data[12..14].copy_from_slice(&(message.len() as u16).to_le_bytes());
+        let [_, fill] = parties.fill_order(&order);
+        let result = send(&mut context, &[verify, fill], &[&parties.bob]).await;
+        assert_fill_error(result, EscrowError::InvalidOrderSignature);
+    }
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 100);
+}
+
+#[tokio::test]
+async fn test_fill_order_expired() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.approve_orders(100)], &[&parties.alice]).await.unwrap();
+
+    let mut order = parties.order(10, 20, 0);
+    order.expiry = 0;
+    let result = send(&mut context, &parties.fill_order(&order), &[&parties.bob]).await;
+    assert_fill_error(result, EscrowError::OrderExpired);
+}
+
+#[tokio::test]
+async fn test_fill_order_needs_approval() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    let result = send(&mut context, &parties.fill_order(&parties.order(10, 20, 0)), &[&parties.bob]).await;
+    assert_fill_error(result, EscrowError::InvalidDelegation);
+
+    send(&mut context, &[parties.approve_orders(5)], &[&parties.alice]).await.unwrap();
+    let result = send(&mut context, &parties.fill_order(&parties.order(10, 20, 1)), &[&parties.bob]).await;
+    assert_fill_error(result, EscrowError::InvalidDelegation);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::*;
+use solana_escrow::{error::EscrowError, instruction, state::{find_escrow_address, find_vault_address}};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+/// `InitEscrow` between `alice` and `bob` for arbitrary mints, with the PDAs derived for them.
+fn init_escrow(parties: &Parties, alice: &Pubkey, bob: &Pubkey, x_mint: &Pubkey, y_mint: &Pubkey) -> Instruction {
+    let program_id = &parties.program_id;
+    let (x_vault, _) = find_vault_address(program_id, x_mint, alice, bob);
+    let (y_vault, _) = find_vault_address(program_id, y_mint, alice, bob);
+    let (escrow, _) = find_escrow_address(program_id, &x_vault, &y_vault, alice, bob);
+    instruction::init_escrow(
+        program_id, alice, bob, x_mint, y_mint, &x_vault, &y_vault, &escrow, &parties.config,
+        &parties.x_mint_rule, &parties.y_mint_rule, 10, 20,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_reinit_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+
+    let result = send(&mut context, &[parties.init_escrow(5, 5)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::AlreadyInitialized as u32);
+    assert_eq!(token_balance(&mut context, &parties.x_vault).await, 10);
+}
+
+#[tokio::test]
+async fn test_zero_size_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    let result = send(&mut context, &[parties.init_escrow(0, 20)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::ZeroEscrowSize as u32);
+    let result = send(&mut context, &[parties.init_escrow(10, 0)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::ZeroEscrowSize as u32);
+}
+
+#[tokio::test]
+async fn test_identical_parties_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let alice = parties.alice.pubkey();
+
+    let init = init_escrow(&parties, &alice, &alice, &parties.x_mint, &parties.y_mint);
+    let result = send(&mut context, &[init], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::IdenticalParties as u32);
+}
+
+#[tokio::test]
+async fn test_identical_mints_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    let init = init_escrow(&parties, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &parties.x_mint);
+    let result = send(&mut context, &[init], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::IdenticalMints as u32);
+}
+
+#[tokio::test]
+async fn test_non_mint_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let (alice, bob) = (parties.alice.pubkey(), parties.bob.pubkey());
+
+    // a token account is owned by the token program but is not a mint
+    let init = init_escrow(&parties, &alice, &bob, &parties.alice_x, &parties.y_mint);
+    let result = send(&mut context, &[init], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidMint as u32);
+
+    let init = init_escrow(&parties, &alice, &bob, &parties.x_mint, &Pubkey::new_unique());
+    let result = send(&mut context, &[init], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidMint as u32);
+}
+
+#[tokio::test]
+async fn test_uninitialized_mint_rejected() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    let mint = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let create = system_instruction::create_account(
+        &context.payer.pubkey(),
+        &mint.pubkey(),
+        rent.minimum_balance(spl_token::state::Mint::LEN),
+        spl_token::state::Mint::LEN as u64,
+        &spl_token::id(),
+    );
+    send(&mut context, &[create], &[&mint]).await.unwrap();
+
+    let init = init_escrow(&parties, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &mint.pubkey());
+    let result = send(&mut context, &[init], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::UninitializedMint as u32);
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{find_maker_state_address, MakerState},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::Signer,
+    transaction::TransactionError,
+};
+
+#[tokio::test]
+async fn test_invalidate_orders() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.approve_orders(100)], &[&parties.alice]).await.unwrap();
+    let alice = parties.alice.pubkey();
+
+    let invalidate = instruction::invalidate_orders(&program_id, &alice, 5).unwrap();
+    send(&mut context, &[invalidate], &[&parties.alice]).await.unwrap();
+    let (maker_state, _) = find_maker_state_address(&program_id, &alice);
+    let account = context.banks_client.get_account(maker_state).await.unwrap().unwrap();
+    assert_eq!(MakerState::load(&account.data).unwrap().min_valid_nonce, 6);
+
+    for nonce in [0, 5] {
+        let result = send(&mut context, &parties.fill_order(&parties.order(10, 20, nonce)), &[&parties.bob]).await;
+        assert_eq!(
+            result.unwrap_err().unwrap(),
+            TransactionError::InstructionError(1, InstructionError::Custom(EscrowError::OrderInvalidated as u32))
+        );
+    }
+    send(&mut context, &parties.fill_order(&parties.order(10, 20, 6)), &[&parties.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+
+    // the next invalidation also covers the order just filled
+    let invalidate = instruction::invalidate_orders(&program_id, &alice, 9).unwrap();
+    send(&mut context, &[invalidate], &[&parties.alice]).await.unwrap();
+    let account = context.banks_client.get_account(maker_state).await.unwrap().unwrap();
+    assert_eq!(MakerState::load(&account.data).unwrap().min_valid_nonce, 10);
+}
+
+#[tokio::test]
+async fn test_invalidate_orders_must_increase() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    let alice = parties.alice.pubkey();
+
+    let invalidate = instruction::invalidate_orders(&program_id, &alice, 5).unwrap();
+    send(&mut context, &[invalidate], &[&parties.alice]).await.unwrap();
+    refresh_blockhash(&mut context).await;
+    // u64::MAX would leave no valid nonce to raise the minimum to
+    for nonce in [4, 5, u64::MAX] {
+        let invalidate = instruction::invalidate_orders(&program_id, &alice, nonce).unwrap();
+        let result = send(&mut context, &[invalidate], &[&parties.alice]).await;
+        assert_custom_error(result, EscrowError::MinNonceNotIncreased as u32);
+    }
+}
@@ -0,0 +1,189 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{find_loan_address, Escrow},
+};
+use solana_program_test::*;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Signer, sysvar::clock::Clock};
+
+/// Alice borrowing 40 of bob's y against 10 x, owing 5 y of interest.
+struct Lending {
+    parties: Parties,
+    loan: Pubkey,
+    deadline: i64,
+}
+
+impl Lending {
+    async fn new(context: &mut ProgramTestContext, program_id: &Pubkey) -> Self {
+        let parties = Parties::new(context, program_id).await;
+        let (loan, _) = find_loan_address(program_id, &parties.escrow);
+        let deadline = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+        Self { parties, loan, deadline }
+    }
+
+    fn init(&self, interest: u64) -> Instruction {
+        let p = &self.parties;
+        instruction::init_loan(
+            &p.program_id, &p.alice.pubkey(), &p.bob.pubkey(), &p.x_mint, &p.y_mint, &p.x_vault, &p.y_vault, &p.escrow,
+            &p.config, &p.x_mint_rule, &p.y_mint_rule, &p.alice_x, 10, 40, interest, self.deadline,
+        )
+        .unwrap()
+    }
+
+    fn accept(&self) -> Instruction {
+        let p = &self.parties;
+        instruction::accept_loan(&p.program_id, &p.bob.pubkey(), &p.escrow, &p.bob_y, &p.alice_y, &p.config).unwrap()
+    }
+
+    fn repay(&self) -> Instruction {
+        let p = &self.parties;
+        instruction::repay_loan(
+            &p.program_id, &p.alice.pubkey(), &p.escrow, &p.x_vault, &p.alice_x, &p.alice_y, &p.bob_y,
+        )
+        .unwrap()
+    }
+
+    fn claim(&self) -> Instruction {
+        let p = &self.parties;
+        instruction::claim_collateral(
+            &p.program_id, &p.bob.pubkey(), &p.alice.pubkey(), &p.escrow, &p.x_vault, &p.bob_x, &p.config,
+        )
+        .unwrap()
+    }
+
+    fn cancel(&self) -> Instruction {
+        let p = &self.parties;
+        instruction::cancel_loan(&p.program_id, &p.alice.pubkey(), &p.escrow, &p.x_vault, &p.alice_x).unwrap()
+    }
+
+    async fn state(&self, context: &mut ProgramTestContext) -> u8 {
+        let account = context.banks_client.get_account(self.parties.escrow).await.unwrap().unwrap();
+        Escrow::load(&account.data).unwrap().state
+    }
+
+    /// Moves the clock to the deadline.
+    async fn expire(&self, context: &mut ProgramTestContext) {
+        let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = self.deadline;
+        context.set_sysvar(&clock);
+        refresh_blockhash(context).await;
+    }
+}
+
+#[tokio::test]
+async fn test_loan_repaid() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let l = Lending::new(&mut context, &program_id).await;
+    let p = &l.parties;
+
+    send(&mut context, &[l.init(5)], &[&p.alice]).await.unwrap();
+    assert_eq!(l.state(&mut context).await, Escrow::LOAN_REQUESTED);
+    assert_eq!(token_balance(&mut context, &p.x_vault).await, 10);
+
+    // repaying before the principal is lent does not work
+    let result = send(&mut context, &[l.repay()], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidLoanState as u32);
+
+    send(&mut context, &[l.accept()], &[&p.bob]).await.unwrap();
+    assert_eq!(l.state(&mut context).await, Escrow::LOAN_ACTIVE);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 40);
+    let result = send(&mut context, &[l.claim()], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::DeadlineNotReached as u32);
+
+    // alice comes up with the interest elsewhere
+    let mint_interest =
+        spl_token::instruction::mint_to(&spl_token::id(), &p.y_mint, &p.alice_y, &context.payer.pubkey(), &[], 5)
+            .unwrap();
+    send(&mut context, &[mint_interest], &[]).await.unwrap();
+    send(&mut context, &[l.repay()], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 100);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 0);
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 105);
+    assert_eq!(l.state(&mut context).await, 0);
+    assert!(context.banks_client.get_account(l.loan).await.unwrap().is_none());
+
+    // the emptied escrow closes like any other
+    send(&mut context, &[p.close_escrow(&p.alice.pubkey(), &p.alice.pubkey())], &[&p.alice]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_loan_default() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let l = Lending::new(&mut context, &program_id).await;
+    let p = &l.parties;
+    send(&mut context, &[l.init(0)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[l.accept()], &[&p.bob]).await.unwrap();
+
+    l.expire(&mut context).await;
+    let result = send(&mut context, &[l.repay()], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::LoanExpired as u32);
+    send(&mut context, &[l.claim()], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 40);
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 90);
+    assert_eq!(l.state(&mut context).await, 0);
+    assert!(context.banks_client.get_account(l.loan).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_loan_cancelled() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let l = Lending::new(&mut context, &program_id).await;
+    let p = &l.parties;
+    send(&mut context, &[l.init(5)], &[&p.alice]).await.unwrap();
+
+    // the regular escrow instructions stay away from a loan
+    let result = send(&mut context, &[p.bob_deposit(40)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::LoanInProgress as u32);
+    let refund = instruction::withdraw(
+        &program_id, &p.alice.pubkey(), &p.x_vault, &p.escrow, &p.alice_x, &p.config, 10,
+    )
+    .unwrap();
+    let result = send(&mut context, &[refund], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::LoanInProgress as u32);
+
+    send(&mut context, &[l.cancel()], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 100);
+    assert_eq!(l.state(&mut context).await, 0);
+    let result = send(&mut context, &[l.accept()], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::InvalidLoan as u32);
+}
+
+#[tokio::test]
+async fn test_loan_accept_after_deadline() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let l = Lending::new(&mut context, &program_id).await;
+    let p = &l.parties;
+    send(&mut context, &[l.init(5)], &[&p.alice]).await.unwrap();
+
+    // only the lender accepts, and only before the deadline
+    let accept = instruction::accept_loan(&program_id, &p.alice.pubkey(), &p.escrow, &p.alice_y, &p.alice_y, &p.config)
+        .unwrap();
+    let result = send(&mut context, &[accept], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidParty as u32);
+    l.expire(&mut context).await;
+    let result = send(&mut context, &[l.accept()], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::LoanExpired as u32);
+    send(&mut context, &[l.cancel()], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 100);
+}
+
+#[tokio::test]
+async fn test_loan_invalid_terms() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let mut l = Lending::new(&mut context, &program_id).await;
+
+    let result = send(&mut context, &[l.init(u64::MAX)], &[&l.parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidLoan as u32);
+    l.deadline -= 2_000;
+    let result = send(&mut context, &[l.init(5)], &[&l.parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidLoan as u32);
+}
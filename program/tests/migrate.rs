@@ -0,0 +1,237 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{find_escrow_address, find_vault_address, Escrow, EscrowV0, EscrowV1, EscrowV2, EscrowV3},
+};
+use solana_program_test::*;
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signer};
+
+/// Byte layout of a v0 escrow account, written out field by field.
+#[allow(clippy::too_many_arguments)]
+fn v0_fixture(
+    party_a: &Pubkey,
+    party_b: &Pubkey,
+    size_a: u64,
+    size_b: u64,
+    vault_x: &Pubkey,
+    vault_y: &Pubkey,
+    state: u8,
+    bump: u8,
+    bump_vault_x: u8,
+    bump_vault_y: u8,
+) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(party_a.as_ref());
+    data.extend_from_slice(party_b.as_ref());
+    data.extend_from_slice(&size_a.to_le_bytes());
+    data.extend_from_slice(&size_b.to_le_bytes());
+    data.extend_from_slice(vault_x.as_ref());
+    data.extend_from_slice(vault_y.as_ref());
+    data.extend_from_slice(&[state, bump, bump_vault_x, bump_vault_y]);
+    assert_eq!(data.len(), EscrowV0::LEN);
+    data
+}
+
+/// Creates an escrow and then rewrites it in the v0 layout, as a pre-migration deployment
+/// would have left it.
+async fn setup_v0_escrow(context: &mut ProgramTestContext, parties: &Parties) {
+    send(context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+
+    let alice = parties.alice.pubkey();
+    let bob = parties.bob.pubkey();
+    let (_, bump_vault_x) = find_vault_address(&parties.program_id, &parties.x_mint, &alice, &bob);
+    let (_, bump_vault_y) = find_vault_address(&parties.program_id, &parties.y_mint, &alice, &bob);
+    let (_, bump) = find_escrow_address(&parties.program_id, &parties.x_vault, &parties.y_vault, &alice, &bob);
+    let data = v0_fixture(
+        &alice, &bob, 10, 20, &parties.x_vault, &parties.y_vault, 0, bump, bump_vault_x, bump_vault_y,
+    );
+
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let account = Account {
+        lamports: rent.minimum_balance(EscrowV0::LEN),
+        data,
+        owner: parties.program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    context.set_account(&parties.escrow, &account.into());
+}
+
+#[tokio::test]
+async fn test_migrate_v0_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    setup_v0_escrow(&mut context, &parties).await;
+
+    // v0 accounts are rejected until migrated
+    let result = send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::AccountNeedsMigration as u32);
+
+    let payer = context.payer.pubkey();
+    let migrate = instruction::migrate(&program_id, &payer, &parties.escrow).unwrap();
+    send(&mut context, &[migrate], &[]).await.unwrap();
+
+    let account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    assert_eq!(account.data.len(), Escrow::LEN);
+    assert!(account.lamports >= rent.minimum_balance(Escrow::LEN));
+    let escrow = Escrow::load(&account.data).unwrap();
+    assert_eq!(escrow.discriminator, Escrow::DISCRIMINATOR);
+    assert_eq!(escrow.version, Escrow::VERSION);
+    assert_eq!(escrow.party_a, parties.alice.pubkey());
+    assert_eq!(escrow.party_b, parties.bob.pubkey());
+    assert_eq!((u64::from(escrow.size_a), u64::from(escrow.size_b)), (10, 20));
+    assert_eq!((escrow.vault_x, escrow.vault_y), (parties.x_vault, parties.y_vault));
+    assert_eq!(escrow.state, 0);
+    assert_eq!(escrow.rent_payer, parties.alice.pubkey());
+
+    // the migrated escrow trades as usual
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    send(&mut context, &[parties.alice_withdraw(20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_withdraw(10)], &[&parties.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+}
+
+#[tokio::test]
+async fn test_migrate_keeps_state() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    // alice's deposit is in the vault, rewrite the escrow as v0 in state 1
+    let mut account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    account.data = account.data[9..9 + EscrowV0::LEN].to_vec();
+    context.set_account(&parties.escrow, &account.into());
+
+    let payer = context.payer.pubkey();
+    let migrate = instruction::migrate(&program_id, &payer, &parties.escrow).unwrap();
+    send(&mut context, &[migrate], &[]).await.unwrap();
+
+    let account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    assert_eq!(Escrow::load(&account.data).unwrap().state, 1);
+
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    send(&mut context, &[parties.bob_withdraw(10)], &[&parties.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+}
+
+#[tokio::test]
+async fn test_migrate_v1_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    // v1 is v2 without the trailing rent payer
+    let mut account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    account.data.truncate(EscrowV1::LEN);
+    account.data[8] = EscrowV1::VERSION;
+    context.set_account(&parties.escrow, &account.into());
+
+    let result = send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await;
+    assert_custom_error(result, EscrowError::AccountNeedsMigration as u32);
+
+    let payer = context.payer.pubkey();
+    let migrate = instruction::migrate(&program_id, &payer, &parties.escrow).unwrap();
+    send(&mut context, &[migrate], &[]).await.unwrap();
+
+    let account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    let escrow = Escrow::load(&account.data).unwrap();
+    assert_eq!(escrow.version, Escrow::VERSION);
+    assert_eq!(escrow.state, 1);
+    assert_eq!(escrow.rent_payer, parties.alice.pubkey());
+
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    send(&mut context, &[parties.bob_withdraw(10)], &[&parties.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+}
+
+#[tokio::test]
+async fn test_migrate_v2_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    // v2 is v3 without the vesting schedules and claimed amounts
+    let mut account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    account.data.truncate(EscrowV2::LEN);
+    account.data[8] = EscrowV2::VERSION;
+    context.set_account(&parties.escrow, &account.into());
+
+    let result = send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await;
+    assert_custom_error(result, EscrowError::AccountNeedsMigration as u32);
+
+    let payer = context.payer.pubkey();
+    let migrate = instruction::migrate(&program_id, &payer, &parties.escrow).unwrap();
+    send(&mut context, &[migrate], &[]).await.unwrap();
+
+    let account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    let escrow = Escrow::load(&account.data).unwrap();
+    assert_eq!(escrow.version, Escrow::VERSION);
+    assert_eq!(escrow.state, 1);
+    assert_eq!(escrow.rent_payer, parties.alice.pubkey());
+    assert!(escrow.vesting_a.is_immediate() && escrow.vesting_b.is_immediate());
+
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    send(&mut context, &[parties.alice_withdraw(20)], &[&parties.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+}
+
+#[tokio::test]
+async fn test_migrate_v3_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    // v3 is v4 without the condition
+    let mut account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    account.data.truncate(EscrowV3::LEN);
+    account.data[8] = EscrowV3::VERSION;
+    context.set_account(&parties.escrow, &account.into());
+
+    let result = send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await;
+    assert_custom_error(result, EscrowError::AccountNeedsMigration as u32);
+
+    let payer = context.payer.pubkey();
+    let migrate = instruction::migrate(&program_id, &payer, &parties.escrow).unwrap();
+    send(&mut context, &[migrate], &[]).await.unwrap();
+
+    let account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    let escrow = Escrow::load(&account.data).unwrap();
+    assert_eq!(escrow.version, Escrow::VERSION);
+    assert_eq!(escrow.state, 1);
+    assert!(escrow.condition.is_none());
+
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+    send(&mut context, &[parties.alice_withdraw(20)], &[&parties.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+}
+
+#[tokio::test]
+async fn test_migrate_current_layout_is_noop() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+    send(&mut context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    let before = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+
+    let payer = context.payer.pubkey();
+    let migrate = instruction::migrate(&program_id, &payer, &parties.escrow).unwrap();
+    send(&mut context, &[migrate], &[]).await.unwrap();
+
+    let after = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    assert_eq!(before.data, after.data);
+    assert_eq!(before.lamports, after.lamports);
+}
@@ -0,0 +1,200 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{
+        find_milestone_escrow_address, find_milestone_vault_address, Comparator, Condition, ConditionValue,
+        MilestoneEscrow,
+    },
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar::clock::Clock,
+};
+
+/// Alice paying bob in x as milestones are approved, with carol as the arbiter.
+struct Milestones {
+    parties: Parties,
+    carol: Keypair,
+    milestone_escrow: Pubkey,
+    vault: Pubkey,
+    deadline: i64,
+}
+
+impl Milestones {
+    async fn new(context: &mut ProgramTestContext, program_id: &Pubkey) -> Self {
+        let parties = Parties::new(context, program_id).await;
+        let carol = Keypair::new();
+        let (milestone_escrow, _) =
+            find_milestone_escrow_address(program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint);
+        let (vault, _) = find_milestone_vault_address(program_id, &milestone_escrow);
+        let deadline = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+        Self { parties, carol, milestone_escrow, vault, deadline }
+    }
+
+    fn init(&self, arbiter: Option<&Pubkey>, milestones: Vec<u64>) -> Instruction {
+        self.init_conditional(arbiter, milestones, Condition::default())
+    }
+
+    fn init_conditional(&self, arbiter: Option<&Pubkey>, milestones: Vec<u64>, condition: Condition) -> Instruction {
+        let parties = &self.parties;
+        instruction::init_milestone_escrow(
+            &parties.program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &parties.alice_x,
+            &parties.config, &parties.x_mint_rule, arbiter, milestones, self.deadline, condition,
+        )
+        .unwrap()
+    }
+
+    fn approve(&self, approver: &Keypair) -> Instruction {
+        let parties = &self.parties;
+        instruction::approve_milestone(
+            &parties.program_id, &approver.pubkey(), &self.milestone_escrow, &parties.alice.pubkey(), &parties.bob_x,
+            &parties.config,
+        )
+        .unwrap()
+    }
+
+    fn refund(&self) -> Instruction {
+        let parties = &self.parties;
+        instruction::refund_milestones(
+            &parties.program_id, &parties.alice.pubkey(), &self.milestone_escrow, &parties.alice_x,
+        )
+        .unwrap()
+    }
+
+    async fn load(&self, context: &mut ProgramTestContext) -> MilestoneEscrow {
+        let account = context.banks_client.get_account(self.milestone_escrow).await.unwrap().unwrap();
+        MilestoneEscrow::load(&account.data).unwrap()
+    }
+
+    /// Moves the clock to the deadline.
+    async fn end(&self, context: &mut ProgramTestContext) {
+        let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = self.deadline;
+        context.set_sysvar(&clock);
+        refresh_blockhash(context).await;
+    }
+}
+
+#[tokio::test]
+async fn test_milestones_approved_in_order() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let m = Milestones::new(&mut context, &program_id).await;
+    let p = &m.parties;
+
+    send(&mut context, &[m.init(Some(&m.carol.pubkey()), vec![10, 20, 30])], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &m.vault).await, 60);
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 40);
+
+    // the payer and the arbiter can both approve, each approval pays the next tranche
+    send(&mut context, &[m.approve(&p.alice)], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+    send(&mut context, &[m.approve(&m.carol)], &[&m.carol]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 30);
+    let milestone_data = m.load(&mut context).await;
+    assert_eq!((milestone_data.approved, milestone_data.remaining()), (2, 30));
+
+    // the last approval closes the escrow
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[m.approve(&p.alice)], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 60);
+    for account in [m.milestone_escrow, m.vault] {
+        assert!(context.banks_client.get_account(account).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_milestones_unauthorized_approver() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let m = Milestones::new(&mut context, &program_id).await;
+    let p = &m.parties;
+    send(&mut context, &[m.init(None, vec![10, 20])], &[&p.alice]).await.unwrap();
+
+    // neither the payee nor anyone else, and without an arbiter only the payer
+    let result = send(&mut context, &[m.approve(&p.bob)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::Unauthorized as u32);
+    let result = send(&mut context, &[m.approve(&m.carol)], &[&m.carol]).await;
+    assert_custom_error(result, EscrowError::Unauthorized as u32);
+    assert_eq!(token_balance(&mut context, &m.vault).await, 30);
+}
+
+#[tokio::test]
+async fn test_milestones_refund_after_deadline() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let m = Milestones::new(&mut context, &program_id).await;
+    let p = &m.parties;
+    send(&mut context, &[m.init(Some(&m.carol.pubkey()), vec![10, 20, 30])], &[&p.alice]).await.unwrap();
+    send(&mut context, &[m.approve(&m.carol)], &[&m.carol]).await.unwrap();
+
+    let result = send(&mut context, &[m.refund()], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::DeadlineNotReached as u32);
+
+    // only the unapproved tranches go back
+    m.end(&mut context).await;
+    send(&mut context, &[m.refund()], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 90);
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+    for account in [m.milestone_escrow, m.vault] {
+        assert!(context.banks_client.get_account(account).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_milestones_invalid_terms() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let mut m = Milestones::new(&mut context, &program_id).await;
+    let alice = &m.parties.alice;
+
+    for milestones in [vec![], vec![10, 0], vec![1; MilestoneEscrow::MAX_MILESTONES + 1], vec![u64::MAX, 1]] {
+        let result = send(&mut context, &[m.init(None, milestones)], &[alice]).await;
+        assert_custom_error(result, EscrowError::InvalidMilestoneEscrow as u32);
+    }
+
+    // the payee cannot arbitrate their own milestones
+    let result = send(&mut context, &[m.init(Some(&m.parties.bob.pubkey()), vec![10])], &[alice]).await;
+    assert_custom_error(result, EscrowError::IdenticalParties as u32);
+
+    m.deadline -= 2_000;
+    let result = send(&mut context, &[m.init(None, vec![10])], &[&m.parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidMilestoneEscrow as u32);
+}
+
+#[tokio::test]
+async fn test_milestones_with_condition() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let m = Milestones::new(&mut context, &program_id).await;
+    let p = &m.parties;
+
+    // approvals only pay out once the supply of x, at offset 36 of its mint, reaches 200
+    let condition = Condition::new(p.x_mint, spl_token::id(), 36, Comparator::Ge, ConditionValue::U64(200));
+    send(&mut context, &[m.init_conditional(None, vec![10, 20], condition)], &[&p.alice]).await.unwrap();
+    assert_eq!(m.load(&mut context).await.condition, condition);
+    let approve = instruction::approve_milestone_conditional(
+        &program_id, &p.alice.pubkey(), &m.milestone_escrow, &p.alice.pubkey(), &p.bob_x, &p.config, &p.x_mint,
+    )
+    .unwrap();
+    let result = send(&mut context, std::slice::from_ref(&approve), &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::ConditionNotMet as u32);
+    let result = send(&mut context, &[m.approve(&p.alice)], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidConditionAccount as u32);
+
+    let payer = context.payer.pubkey();
+    create_token_account(&mut context, &p.x_mint, &payer, 100).await;
+    send(&mut context, &[approve], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+
+    // refunds are not gated
+    m.end(&mut context).await;
+    send(&mut context, &[m.refund()], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 90);
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{find_option_address, find_option_vault_address, CoveredOption},
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar::clock::Clock,
+};
+
+/// Alice writing a call on 10 x with a strike of 30 y and a premium of 5 y.
+struct Writing {
+    parties: Parties,
+    option: Pubkey,
+    vault: Pubkey,
+    expiry: i64,
+}
+
+impl Writing {
+    async fn new(context: &mut ProgramTestContext, program_id: &Pubkey) -> Self {
+        let parties = Parties::new(context, program_id).await;
+        let (option, _) =
+            find_option_address(program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint);
+        let (vault, _) = find_option_vault_address(program_id, &option);
+        let expiry = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp + 1_000;
+        Self { parties, option, vault, expiry }
+    }
+
+    fn write(&self, amount: u64, strike: u64) -> Instruction {
+        let p = &self.parties;
+        instruction::write_option(
+            &p.program_id, &p.alice.pubkey(), &p.x_mint, &p.y_mint, &p.alice_x, &p.config, &p.x_mint_rule,
+            &p.y_mint_rule, amount, 5, strike, self.expiry,
+        )
+        .unwrap()
+    }
+
+    fn buy(&self) -> Instruction {
+        let p = &self.parties;
+        instruction::buy_option(&p.program_id, &p.bob.pubkey(), &self.option, &p.bob_y, &p.alice_y, &p.config).unwrap()
+    }
+
+    fn transfer(&self, owner: &Keypair, new_owner: &Pubkey) -> Instruction {
+        instruction::transfer_option(&self.parties.program_id, &owner.pubkey(), &self.option, new_owner).unwrap()
+    }
+
+    fn exercise(&self, owner: &Keypair, owner_x: &Pubkey, owner_y: &Pubkey) -> Instruction {
+        let p = &self.parties;
+        instruction::exercise_option(
+            &p.program_id, &owner.pubkey(), &self.option, owner_x, owner_y, &p.alice.pubkey(), &p.alice_y, &p.config,
+        )
+        .unwrap()
+    }
+
+    fn reclaim(&self) -> Instruction {
+        let p = &self.parties;
+        instruction::reclaim_option(&p.program_id, &p.alice.pubkey(), &self.option, &p.alice_x).unwrap()
+    }
+
+    async fn load(&self, context: &mut ProgramTestContext) -> CoveredOption {
+        let account = context.banks_client.get_account(self.option).await.unwrap().unwrap();
+        CoveredOption::load(&account.data).unwrap()
+    }
+
+    async fn closed(&self, context: &mut ProgramTestContext) -> bool {
+        for account in [self.option, self.vault] {
+            if context.banks_client.get_account(account).await.unwrap().is_some() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Moves the clock to the expiry.
+    async fn expire(&self, context: &mut ProgramTestContext) {
+        let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = self.expiry;
+        context.set_sysvar(&clock);
+        refresh_blockhash(context).await;
+    }
+}
+
+#[tokio::test]
+async fn test_option_bought_and_exercised() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let w = Writing::new(&mut context, &program_id).await;
+    let p = &w.parties;
+
+    send(&mut context, &[w.write(10, 30)], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &w.vault).await, 10);
+    assert!(!w.load(&mut context).await.is_sold());
+
+    // nobody holds the option before it is bought
+    let result = send(&mut context, &[w.exercise(&p.bob, &p.bob_x, &p.bob_y)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::Unauthorized as u32);
+
+    send(&mut context, &[w.buy()], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 5);
+    assert_eq!(w.load(&mut context).await.owner, p.bob.pubkey());
+    refresh_blockhash(&mut context).await;
+    let result = send(&mut context, &[w.buy()], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::OptionAlreadySold as u32);
+
+    send(&mut context, &[w.exercise(&p.bob, &p.bob_x, &p.bob_y)], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 65);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 35);
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 90);
+    assert!(w.closed(&mut context).await);
+}
+
+#[tokio::test]
+async fn test_option_transferred() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let w = Writing::new(&mut context, &program_id).await;
+    let p = &w.parties;
+    let carol = Keypair::new();
+    let carol_x = create_token_account(&mut context, &p.x_mint, &carol.pubkey(), 0).await;
+    let carol_y = create_token_account(&mut context, &p.y_mint, &carol.pubkey(), 30).await;
+    send(&mut context, &[w.write(10, 30)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[w.buy()], &[&p.bob]).await.unwrap();
+
+    // only the owner hands the option on
+    let result = send(&mut context, &[w.transfer(&carol, &carol.pubkey())], &[&carol]).await;
+    assert_custom_error(result, EscrowError::Unauthorized as u32);
+    let result = send(&mut context, &[w.transfer(&p.bob, &Pubkey::default())], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::InvalidOption as u32);
+    send(&mut context, &[w.transfer(&p.bob, &carol.pubkey())], &[&p.bob]).await.unwrap();
+    assert_eq!(w.load(&mut context).await.owner, carol.pubkey());
+
+    // the previous owner no longer exercises, the new one does
+    let result = send(&mut context, &[w.exercise(&p.bob, &p.bob_x, &p.bob_y)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::Unauthorized as u32);
+    send(&mut context, &[w.exercise(&carol, &carol_x, &carol_y)], &[&carol]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &carol_x).await, 10);
+    assert_eq!(token_balance(&mut context, &carol_y).await, 0);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 35);
+    assert!(w.closed(&mut context).await);
+}
+
+#[tokio::test]
+async fn test_option_expired() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let w = Writing::new(&mut context, &program_id).await;
+    let p = &w.parties;
+    send(&mut context, &[w.write(10, 30)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[w.buy()], &[&p.bob]).await.unwrap();
+
+    // the writer waits for the expiry of a sold option
+    let result = send(&mut context, &[w.reclaim()], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::DeadlineNotReached as u32);
+
+    w.expire(&mut context).await;
+    let result = send(&mut context, &[w.exercise(&p.bob, &p.bob_x, &p.bob_y)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::OptionExpired as u32);
+    send(&mut context, &[w.reclaim()], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 100);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 5);
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 0);
+    assert!(w.closed(&mut context).await);
+}
+
+#[tokio::test]
+async fn test_option_unsold() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let w = Writing::new(&mut context, &program_id).await;
+    let p = &w.parties;
+    send(&mut context, &[w.write(10, 30)], &[&p.alice]).await.unwrap();
+
+    // an option nobody bought comes back at any time, and expired ones cannot be bought
+    send(&mut context, &[w.reclaim()], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 100);
+    assert!(w.closed(&mut context).await);
+
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[w.write(10, 30)], &[&p.alice]).await.unwrap();
+    w.expire(&mut context).await;
+    let result = send(&mut context, &[w.buy()], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::OptionExpired as u32);
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 100);
+}
+
+#[tokio::test]
+async fn test_option_invalid_terms() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let mut w = Writing::new(&mut context, &program_id).await;
+
+    for (amount, strike) in [(0, 30), (10, 0)] {
+        let result = send(&mut context, &[w.write(amount, strike)], &[&w.parties.alice]).await;
+        assert_custom_error(result, EscrowError::InvalidOption as u32);
+    }
+    w.expiry -= 2_000;
+    let result = send(&mut context, &[w.write(10, 30)], &[&w.parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidOption as u32);
+}
@@ -0,0 +1,285 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{
+        find_auction_vault_address, find_sealed_auction_address, find_sealed_bid_address, sealed_bid_commitment,
+        SealedAuction, SealedBid,
+    },
+};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    sysvar::clock::Clock,
+};
+
+const SALT: [u8; 32] = [7; 32];
+
+/// Alice auctioning 10 x for sealed bids in y, with bob and carol holding 100 y each.
+struct Auction {
+    parties: Parties,
+    carol: Keypair,
+    carol_x: Pubkey,
+    carol_y: Pubkey,
+    auction: Pubkey,
+    commit_deadline: i64,
+    reveal_deadline: i64,
+}
+
+impl Auction {
+    async fn new(context: &mut ProgramTestContext, program_id: &Pubkey) -> Self {
+        let parties = Parties::new(context, program_id).await;
+        let carol = Keypair::new();
+        airdrop(context, &carol.pubkey(), 1_000_000_000).await;
+        let carol_x = create_token_account(context, &parties.x_mint, &carol.pubkey(), 0).await;
+        let carol_y = create_token_account(context, &parties.y_mint, &carol.pubkey(), 100).await;
+        let (auction, _) =
+            find_sealed_auction_address(program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint);
+        let now = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+        Self { parties, carol, carol_x, carol_y, auction, commit_deadline: now + 1_000, reveal_deadline: now + 2_000 }
+    }
+
+    fn init(&self, reserve_price: u64, penalty: u64) -> Instruction {
+        let parties = &self.parties;
+        instruction::init_sealed_auction(
+            &parties.program_id, &parties.alice.pubkey(), &parties.x_mint, &parties.y_mint, &parties.alice_x,
+            &parties.config, &parties.x_mint_rule, 10, reserve_price, penalty, self.commit_deadline,
+            self.reveal_deadline,
+        )
+        .unwrap()
+    }
+
+    fn commit(&self, bidder: &Keypair, bidder_y: &Pubkey, bid: u64, collateral: u64) -> Instruction {
+        let parties = &self.parties;
+        let commitment = sealed_bid_commitment(&bidder.pubkey(), bid, &SALT);
+        instruction::commit_bid(
+            &parties.program_id, &bidder.pubkey(), &self.auction, &parties.y_mint, bidder_y, &parties.config,
+            commitment, collateral,
+        )
+        .unwrap()
+    }
+
+    fn reveal(&self, bidder: &Keypair, bid: u64) -> Instruction {
+        let parties = &self.parties;
+        instruction::reveal_bid(
+            &parties.program_id, &bidder.pubkey(), &self.auction, &parties.config, &parties.y_mint_rule, bid, SALT,
+        )
+        .unwrap()
+    }
+
+    fn settle(&self, x_destination: &Pubkey) -> Instruction {
+        let parties = &self.parties;
+        instruction::settle_sealed_auction(
+            &parties.program_id, &self.auction, &parties.x_mint, &parties.y_mint, &parties.alice.pubkey(),
+            &parties.alice_y, x_destination, &parties.config,
+        )
+        .unwrap()
+    }
+
+    fn withdraw(&self, bidder: &Keypair, bidder_y: &Pubkey) -> Instruction {
+        let parties = &self.parties;
+        instruction::withdraw_collateral(
+            &parties.program_id, &bidder.pubkey(), &self.auction, &parties.y_mint, bidder_y, &parties.alice.pubkey(),
+            &parties.alice_y,
+        )
+        .unwrap()
+    }
+
+    /// Moves the clock to `unix_timestamp`.
+    async fn warp(&self, context: &mut ProgramTestContext, unix_timestamp: i64) {
+        let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        context.set_sysvar(&clock);
+    }
+}
+
+#[tokio::test]
+async fn test_sealed_auction() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+    let (y_vault, _) = find_auction_vault_address(&program_id, &a.auction, &p.y_mint);
+
+    send(&mut context, &[a.init(0, 0)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[a.commit(&p.bob, &p.bob_y, 30, 50)], &[&p.bob]).await.unwrap();
+    send(&mut context, &[a.commit(&a.carol, &a.carol_y, 45, 60)], &[&a.carol]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &y_vault).await, 110);
+
+    // reveals only open once commitments are closed
+    let result = send(&mut context, &[a.reveal(&p.bob, 30)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::RevealNotOpen as u32);
+
+    a.warp(&mut context, a.commit_deadline).await;
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[a.reveal(&p.bob, 30)], &[&p.bob]).await.unwrap();
+    send(&mut context, &[a.reveal(&a.carol, 45)], &[&a.carol]).await.unwrap();
+    let account = context.banks_client.get_account(a.auction).await.unwrap().unwrap();
+    let auction = SealedAuction::load(&account.data).unwrap();
+    assert_eq!((auction.highest_bidder, auction.highest_bid), (a.carol.pubkey(), 45));
+
+    let result = send(&mut context, &[a.settle(&a.carol_x)], &[]).await;
+    assert_custom_error(result, EscrowError::AuctionNotEnded as u32);
+    a.warp(&mut context, a.reveal_deadline).await;
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[a.settle(&a.carol_x)], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &a.carol_x).await, 10);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 45);
+
+    // carol gets back what her bid left of her collateral, bob all of his
+    send(&mut context, &[a.withdraw(&a.carol, &a.carol_y)], &[&a.carol]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &a.carol_y).await, 55);
+    send(&mut context, &[a.withdraw(&p.bob, &p.bob_y)], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 100);
+
+    let (bob_bid, _) = find_sealed_bid_address(&program_id, &a.auction, &p.bob.pubkey());
+    for account in [a.auction, y_vault, bob_bid] {
+        assert!(context.banks_client.get_account(account).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_sealed_auction_unrevealed_penalty() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+
+    send(&mut context, &[a.init(0, 15)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[a.commit(&p.bob, &p.bob_y, 30, 50)], &[&p.bob]).await.unwrap();
+    send(&mut context, &[a.commit(&a.carol, &a.carol_y, 45, 60)], &[&a.carol]).await.unwrap();
+    a.warp(&mut context, a.commit_deadline).await;
+    send(&mut context, &[a.reveal(&p.bob, 30)], &[&p.bob]).await.unwrap();
+
+    // withdrawals wait for settlement
+    let result = send(&mut context, &[a.withdraw(&a.carol, &a.carol_y)], &[&a.carol]).await;
+    assert_custom_error(result, EscrowError::AuctionNotEnded as u32);
+
+    a.warp(&mut context, a.reveal_deadline).await;
+    let result = send(&mut context, &[a.reveal(&a.carol, 45)], &[&a.carol]).await;
+    assert_custom_error(result, EscrowError::RevealNotOpen as u32);
+    send(&mut context, &[a.settle(&p.bob_x)], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 30);
+
+    // carol never revealed and loses the penalty to alice
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[a.withdraw(&a.carol, &a.carol_y)], &[&a.carol]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &a.carol_y).await, 85);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 45);
+    send(&mut context, &[a.withdraw(&p.bob, &p.bob_y)], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 70);
+    assert!(context.banks_client.get_account(a.auction).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_sealed_auction_while_paused() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &p.config, &payer).unwrap();
+    send(&mut context, &[init_config, a.init(0, 0)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[a.commit(&p.bob, &p.bob_y, 30, 50)], &[&p.bob]).await.unwrap();
+    let set_paused = |paused| instruction::set_paused(&program_id, &payer, &p.config, paused).unwrap();
+
+    a.warp(&mut context, a.commit_deadline).await;
+    send(&mut context, &[set_paused(true)], &[]).await.unwrap();
+    let result = send(&mut context, &[a.reveal(&p.bob, 30)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::Paused as u32);
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[set_paused(false), a.reveal(&p.bob, 30)], &[&p.bob]).await.unwrap();
+
+    a.warp(&mut context, a.reveal_deadline).await;
+    send(&mut context, &[set_paused(true)], &[]).await.unwrap();
+    let result = send(&mut context, &[a.settle(&p.bob_x)], &[]).await;
+    assert_custom_error(result, EscrowError::Paused as u32);
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[set_paused(false), a.settle(&p.bob_x)], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 10);
+
+    // collateral can still be taken back while paused
+    send(&mut context, &[set_paused(true)], &[]).await.unwrap();
+    send(&mut context, &[a.withdraw(&p.bob, &p.bob_y)], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 70);
+}
+
+#[tokio::test]
+async fn test_sealed_auction_invalid_reveal() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+
+    send(&mut context, &[a.init(0, 0)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[a.commit(&p.bob, &p.bob_y, 30, 50)], &[&p.bob]).await.unwrap();
+    // a bid above the collateral can never be revealed
+    send(&mut context, &[a.commit(&a.carol, &a.carol_y, 70, 60)], &[&a.carol]).await.unwrap();
+    refresh_blockhash(&mut context).await;
+    let result = send(&mut context, &[a.commit(&p.bob, &p.bob_y, 30, 50)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::AlreadyInitialized as u32);
+    a.warp(&mut context, a.commit_deadline).await;
+
+    let result = send(&mut context, &[a.reveal(&p.bob, 31)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::InvalidReveal as u32);
+    let result = send(&mut context, &[a.reveal(&a.carol, 70)], &[&a.carol]).await;
+    assert_custom_error(result, EscrowError::InvalidReveal as u32);
+    send(&mut context, &[a.reveal(&p.bob, 30)], &[&p.bob]).await.unwrap();
+    refresh_blockhash(&mut context).await;
+    let result = send(&mut context, &[a.reveal(&p.bob, 30)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::InvalidReveal as u32);
+
+    let (bob_bid, _) = find_sealed_bid_address(&program_id, &a.auction, &p.bob.pubkey());
+    let account = context.banks_client.get_account(bob_bid).await.unwrap().unwrap();
+    let bid = SealedBid::load(&account.data).unwrap();
+    assert!(bid.revealed);
+    assert_eq!(bid.bid, 30);
+}
+
+#[tokio::test]
+async fn test_sealed_auction_below_reserve() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+
+    send(&mut context, &[a.init(40, 10)], &[&p.alice]).await.unwrap();
+    send(&mut context, &[a.commit(&p.bob, &p.bob_y, 30, 50)], &[&p.bob]).await.unwrap();
+    // no bids after the commit deadline
+    a.warp(&mut context, a.commit_deadline).await;
+    let result = send(&mut context, &[a.commit(&a.carol, &a.carol_y, 45, 60)], &[&a.carol]).await;
+    assert_custom_error(result, EscrowError::AuctionEnded as u32);
+
+    // an honest reveal under the reserve cannot win but is not penalized
+    send(&mut context, &[a.reveal(&p.bob, 30)], &[&p.bob]).await.unwrap();
+    a.warp(&mut context, a.reveal_deadline).await;
+    send(&mut context, &[a.settle(&p.alice_x)], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 100);
+    refresh_blockhash(&mut context).await;
+    let result = send(&mut context, &[a.settle(&p.alice_x)], &[]).await;
+    assert_custom_error(result, EscrowError::AuctionSettled as u32);
+
+    send(&mut context, &[a.withdraw(&p.bob, &p.bob_y)], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_y).await, 100);
+    assert_eq!(token_balance(&mut context, &p.alice_y).await, 0);
+    assert!(context.banks_client.get_account(a.auction).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_sealed_auction_without_bids() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let a = Auction::new(&mut context, &program_id).await;
+    let p = &a.parties;
+
+    send(&mut context, &[a.init(0, 0)], &[&p.alice]).await.unwrap();
+    a.warp(&mut context, a.reveal_deadline).await;
+    send(&mut context, &[a.settle(&p.alice_x)], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 100);
+    assert!(context.banks_client.get_account(a.auction).await.unwrap().is_none());
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::*;
+use solana_escrow::error::EscrowError;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+fn approve(source: &Pubkey, delegate: &Pubkey, owner: &Keypair, amount: u64) -> Instruction {
+    spl_token::instruction::approve(&spl_token::id(), source, delegate, &owner.pubkey(), &[], amount).unwrap()
+}
+
+async fn setup(context: &mut ProgramTestContext, program_id: &Pubkey) -> Parties {
+    let parties = Parties::new(context, program_id).await;
+    send(context, &[parties.init_escrow(10, 20)], &[&parties.alice]).await.unwrap();
+    let alice_approve = approve(&parties.alice_x, &parties.escrow, &parties.alice, 10);
+    send(context, &[alice_approve], &[&parties.alice]).await.unwrap();
+    let bob_approve = approve(&parties.bob_y, &parties.escrow, &parties.bob, 20);
+    send(context, &[bob_approve], &[&parties.bob]).await.unwrap();
+    parties
+}
+
+#[tokio::test]
+async fn test_settle_delegated() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = setup(&mut context, &program_id).await;
+
+    send(&mut context, &[parties.settle_delegated()], &[]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 90);
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &parties.bob_y).await, 80);
+    assert_eq!(token_balance(&mut context, &parties.x_vault).await, 0);
+
+    // the approvals are used up, so the trade cannot be replayed
+    refresh_blockhash(&mut context).await;
+    let result = send(&mut context, &[parties.settle_delegated()], &[]).await;
+    assert_custom_error(result, EscrowError::InvalidDelegation as u32);
+}
+
+#[tokio::test]
+async fn test_settle_delegated_fails_when_funds_moved() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = setup(&mut context, &program_id).await;
+
+    // bob keeps the approval but moves his y elsewhere
+    let elsewhere = create_token_account(&mut context, &parties.y_mint, &parties.bob.pubkey(), 0).await;
+    let move_out =
+        spl_token::instruction::transfer(&spl_token::id(), &parties.bob_y, &elsewhere, &parties.bob.pubkey(), &[], 95)
+            .unwrap();
+    send(&mut context, &[move_out], &[&parties.bob]).await.unwrap();
+
+    let result = send(&mut context, &[parties.settle_delegated()], &[]).await;
+    assert_custom_error(result, EscrowError::InsufficientDelegatedFunds as u32);
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 100);
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 0);
+}
+
+#[tokio::test]
+async fn test_settle_delegated_requires_exact_approvals() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = setup(&mut context, &program_id).await;
+
+    let revoke = spl_token::instruction::revoke(&spl_token::id(), &parties.alice_x, &parties.alice.pubkey(), &[]).unwrap();
+    send(&mut context, &[revoke], &[&parties.alice]).await.unwrap();
+    let result = send(&mut context, &[parties.settle_delegated()], &[]).await;
+    assert_custom_error(result, EscrowError::InvalidDelegation as u32);
+
+    let over_approve = approve(&parties.alice_x, &parties.escrow, &parties.alice, 50);
+    send(&mut context, &[over_approve], &[&parties.alice]).await.unwrap();
+    let result = send(&mut context, &[parties.settle_delegated()], &[]).await;
+    assert_custom_error(result, EscrowError::InvalidDelegation as u32);
+    assert_eq!(token_balance(&mut context, &parties.bob_y).await, 100);
+}
+
+#[tokio::test]
+async fn test_settle_delegated_rejects_custodial_deposits() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = setup(&mut context, &program_id).await;
+    let reapprove = approve(&parties.alice_x, &parties.escrow, &parties.alice, 10);
+    send(&mut context, &[parties.alice_deposit(10), reapprove], &[&parties.alice]).await.unwrap();
+
+    let result = send(&mut context, &[parties.settle_delegated()], &[]).await;
+    assert_custom_error(result, EscrowError::EscrowNotEmpty as u32);
+}
+
+#[tokio::test]
+async fn test_settle_delegated_rejects_other_token_program() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = setup(&mut context, &program_id).await;
+
+    // the escrow is the delegate of both sources, so it only signs for the real token program
+    let mut settle = parties.settle_delegated();
+    settle.accounts[7].pubkey = Pubkey::new_unique();
+    let result = send(&mut context, &[settle], &[]).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::IncorrectProgramId)
+    );
+    assert_eq!(token_balance(&mut context, &parties.alice_x).await, 100);
+    assert_eq!(token_balance(&mut context, &parties.bob_y).await, 100);
+}
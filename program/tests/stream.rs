@@ -0,0 +1,185 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{find_stream_address, find_stream_vault_address, Stream},
+};
+use solana_program_test::*;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Signer, sysvar::clock::Clock};
+
+/// Alice streaming x to bob from `start`.
+struct Streaming {
+    parties: Parties,
+    stream: Pubkey,
+    vault: Pubkey,
+    start: i64,
+}
+
+impl Streaming {
+    async fn new(context: &mut ProgramTestContext, program_id: &Pubkey) -> Self {
+        let parties = Parties::new(context, program_id).await;
+        let (stream, _) =
+            find_stream_address(program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint);
+        let (vault, _) = find_stream_vault_address(program_id, &stream);
+        let start = context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+        Self { parties, stream, vault, start }
+    }
+
+    fn init(&self, rate: u64, start: i64, end: i64) -> Instruction {
+        let parties = &self.parties;
+        instruction::init_stream(
+            &parties.program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &parties.alice_x,
+            &parties.config, &parties.x_mint_rule, rate, start, end,
+        )
+        .unwrap()
+    }
+
+    fn withdraw(&self, withdrawer: &Pubkey, destination: &Pubkey, amount: u64) -> Instruction {
+        let parties = &self.parties;
+        instruction::withdraw(
+            &parties.program_id, withdrawer, &self.vault, &self.stream, destination, &parties.config, amount,
+        )
+        .unwrap()
+    }
+
+    fn cancel(&self, sender: &Pubkey) -> Instruction {
+        let parties = &self.parties;
+        instruction::cancel_stream(&parties.program_id, sender, &self.stream, &parties.alice_x, &parties.bob_x).unwrap()
+    }
+
+    /// Moves the clock to `elapsed` seconds after the start.
+    async fn warp(&self, context: &mut ProgramTestContext, elapsed: i64) {
+        let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp = self.start + elapsed;
+        context.set_sysvar(&clock);
+        refresh_blockhash(context).await;
+    }
+}
+
+#[tokio::test]
+async fn test_stream_withdraw_accrued() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let s = Streaming::new(&mut context, &program_id).await;
+    let p = &s.parties;
+    let bob = p.bob.pubkey();
+
+    send(&mut context, &[s.init(2, s.start, s.start + 50)], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &s.vault).await, 100);
+
+    s.warp(&mut context, 30).await;
+    let result = send(&mut context, &[s.withdraw(&bob, &p.bob_x, 61)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::AmountNotVested as u32);
+    send(&mut context, &[s.withdraw(&bob, &p.bob_x, 45)], &[&p.bob]).await.unwrap();
+    send(&mut context, &[s.withdraw(&bob, &p.bob_x, 15)], &[&p.bob]).await.unwrap();
+    let result = send(&mut context, &[s.withdraw(&bob, &p.bob_x, 1)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::AmountNotVested as u32);
+    let account = context.banks_client.get_account(s.stream).await.unwrap().unwrap();
+    assert_eq!(Stream::load(&account.data).unwrap().withdrawn, 60);
+
+    // nothing accrues past the end
+    s.warp(&mut context, 1_000).await;
+    let result = send(&mut context, &[s.withdraw(&bob, &p.bob_x, 41)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::AmountNotVested as u32);
+    send(&mut context, &[s.withdraw(&bob, &p.bob_x, 40)], &[&p.bob]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 100);
+
+    // the sender closes the drained stream
+    send(&mut context, &[s.cancel(&p.alice.pubkey())], &[&p.alice]).await.unwrap();
+    for account in [s.stream, s.vault] {
+        assert!(context.banks_client.get_account(account).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_stream_cancel_splits_vault() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let s = Streaming::new(&mut context, &program_id).await;
+    let p = &s.parties;
+
+    send(&mut context, &[s.init(1, s.start, s.start + 100)], &[&p.alice]).await.unwrap();
+    s.warp(&mut context, 20).await;
+    send(&mut context, &[s.withdraw(&p.bob.pubkey(), &p.bob_x, 10)], &[&p.bob]).await.unwrap();
+
+    // the recipient gets what accrued until the cancel, the sender the rest
+    s.warp(&mut context, 35).await;
+    send(&mut context, &[s.cancel(&p.alice.pubkey())], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 35);
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 65);
+    for account in [s.stream, s.vault] {
+        assert!(context.banks_client.get_account(account).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_stream_cancel_while_paused() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let s = Streaming::new(&mut context, &program_id).await;
+    let p = &s.parties;
+
+    send(&mut context, &[s.init(1, s.start, s.start + 100)], &[&p.alice]).await.unwrap();
+    let payer = context.payer.pubkey();
+    let init_config = instruction::init_config(&program_id, &payer, &p.config, &payer).unwrap();
+    let set_paused = instruction::set_paused(&program_id, &payer, &p.config, true).unwrap();
+    send(&mut context, &[init_config, set_paused], &[]).await.unwrap();
+
+    // withdrawing is paused, cancelling still pays out both sides
+    s.warp(&mut context, 40).await;
+    let result = send(&mut context, &[s.withdraw(&p.bob.pubkey(), &p.bob_x, 10)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::Paused as u32);
+    send(&mut context, &[s.cancel(&p.alice.pubkey())], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 40);
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 60);
+    assert!(context.banks_client.get_account(s.stream).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_stream_cancel_before_start() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let s = Streaming::new(&mut context, &program_id).await;
+    let p = &s.parties;
+
+    send(&mut context, &[s.init(1, s.start + 100, s.start + 200)], &[&p.alice]).await.unwrap();
+    let result = send(&mut context, &[s.withdraw(&p.bob.pubkey(), &p.bob_x, 1)], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::AmountNotVested as u32);
+    send(&mut context, &[s.cancel(&p.alice.pubkey())], &[&p.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &p.alice_x).await, 100);
+    assert_eq!(token_balance(&mut context, &p.bob_x).await, 0);
+}
+
+#[tokio::test]
+async fn test_stream_parties() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let s = Streaming::new(&mut context, &program_id).await;
+    let p = &s.parties;
+    send(&mut context, &[s.init(1, s.start, s.start + 100)], &[&p.alice]).await.unwrap();
+    s.warp(&mut context, 50).await;
+
+    // only the recipient withdraws and only the sender cancels
+    let result = send(&mut context, &[s.withdraw(&p.alice.pubkey(), &p.alice_x, 10)], &[&p.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidParty as u32);
+    let result = send(&mut context, &[s.cancel(&p.bob.pubkey())], &[&p.bob]).await;
+    assert_custom_error(result, EscrowError::Unauthorized as u32);
+    assert_eq!(token_balance(&mut context, &s.vault).await, 100);
+}
+
+#[tokio::test]
+async fn test_stream_invalid_terms() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let s = Streaming::new(&mut context, &program_id).await;
+    let alice = &s.parties.alice;
+
+    let now = s.start;
+    let terms = [(0, now, now + 10), (1, now + 10, now + 10), (1, now - 20, now - 10), (u64::MAX, now, now + 2)];
+    for (rate, start, end) in terms {
+        let result = send(&mut context, &[s.init(rate, start, end)], &[alice]).await;
+        assert_custom_error(result, EscrowError::InvalidStream as u32);
+    }
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::*;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{Comparator, Condition, ConditionValue, Escrow, EscrowTerms, VestingSchedule},
+};
+use solana_program_test::*;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Signer, sysvar::clock::Clock};
+
+fn init_with_terms(parties: &Parties, terms: EscrowTerms) -> Instruction {
+    instruction::init_escrow_with_terms(
+        &parties.program_id, &parties.alice.pubkey(), &parties.bob.pubkey(), &parties.x_mint, &parties.y_mint,
+        &parties.x_vault, &parties.y_vault, &parties.escrow, &parties.config, &parties.x_mint_rule,
+        &parties.y_mint_rule, 10, 20, terms,
+    )
+    .unwrap()
+}
+
+fn init_vesting(parties: &Parties, vesting_a: VestingSchedule, vesting_b: VestingSchedule) -> Instruction {
+    init_with_terms(parties, EscrowTerms { vesting_a, vesting_b, ..EscrowTerms::default() })
+}
+
+async fn now(context: &mut ProgramTestContext) -> i64 {
+    context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp
+}
+
+async fn warp(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+    refresh_blockhash(context).await;
+}
+
+async fn load_escrow(context: &mut ProgramTestContext, parties: &Parties) -> Escrow {
+    let account = context.banks_client.get_account(parties.escrow).await.unwrap().unwrap();
+    *Escrow::load(&account.data).unwrap()
+}
+
+#[tokio::test]
+async fn test_vesting_leg_released_gradually() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    // bob's 20 y vest to alice over 1000 seconds with a cliff after 100, alice's x moves at once
+    let start = now(&mut context).await;
+    let vesting_b = VestingSchedule::new(start + 100, start, start + 1_000);
+    let init = init_vesting(&parties, VestingSchedule::default(), vesting_b);
+    send(&mut context, &[init], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+
+    let result = send(&mut context, &[parties.alice_withdraw(1)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::AmountNotVested as u32);
+
+    warp(&mut context, start + 500).await;
+    send(&mut context, &[parties.alice_withdraw(6)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_withdraw(4)], &[&parties.alice]).await.unwrap();
+    let result = send(&mut context, &[parties.alice_withdraw(1)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::AmountNotVested as u32);
+    let escrow = load_escrow(&mut context, &parties).await;
+    assert_eq!((escrow.state, u64::from(escrow.claimed_b)), (3, 10));
+    assert_eq!(escrow.status().vault_y_amount, 10);
+
+    // the immediate leg still moves whole
+    let result = send(&mut context, &[parties.bob_withdraw(5)], &[&parties.bob]).await;
+    assert_custom_error(result, EscrowError::ExpectedAmountMismatch as u32);
+    send(&mut context, &[parties.bob_withdraw(10)], &[&parties.bob]).await.unwrap();
+    assert_eq!(load_escrow(&mut context, &parties).await.state, 5);
+
+    warp(&mut context, start + 1_000).await;
+    send(&mut context, &[parties.alice_withdraw(10)], &[&parties.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+    let escrow = load_escrow(&mut context, &parties).await;
+    assert_eq!(escrow.state, 0);
+    assert_eq!((u64::from(escrow.claimed_a), u64::from(escrow.claimed_b)), (0, 0));
+}
+
+#[tokio::test]
+async fn test_vesting_both_legs() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    let start = now(&mut context).await;
+    let vesting = VestingSchedule::new(start, start, start + 100);
+    send(&mut context, &[init_vesting(&parties, vesting, vesting)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+
+    // refunds before bob deposits are not subject to vesting
+    let refund = instruction::withdraw(
+        &program_id, &parties.alice.pubkey(), &parties.x_vault, &parties.escrow, &parties.alice_x, &parties.config, 10,
+    )
+    .unwrap();
+    send(&mut context, &[refund], &[&parties.alice]).await.unwrap();
+    refresh_blockhash(&mut context).await;
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+
+    warp(&mut context, start + 30).await;
+    send(&mut context, &[parties.bob_withdraw(3)], &[&parties.bob]).await.unwrap();
+    send(&mut context, &[parties.alice_withdraw(6)], &[&parties.alice]).await.unwrap();
+    warp(&mut context, start + 100).await;
+    send(&mut context, &[parties.bob_withdraw(7)], &[&parties.bob]).await.unwrap();
+    assert_eq!(load_escrow(&mut context, &parties).await.state, 5);
+    send(&mut context, &[parties.alice_withdraw(14)], &[&parties.alice]).await.unwrap();
+
+    assert_eq!(token_balance(&mut context, &parties.bob_x).await, 10);
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 20);
+    assert_eq!(load_escrow(&mut context, &parties).await.state, 0);
+}
+
+#[tokio::test]
+async fn test_vesting_invalid_schedule() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    // the cliff has to fall between start and end
+    let vesting = VestingSchedule::new(300, 100, 200);
+    let init = init_vesting(&parties, VestingSchedule::default(), vesting);
+    let result = send(&mut context, &[init], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::InvalidVestingSchedule as u32);
+}
+
+#[tokio::test]
+async fn test_vesting_with_condition() {
+    let program_id = Pubkey::new_unique();
+    let mut context = program_test(&program_id).start_with_context().await;
+    let parties = Parties::new(&mut context, &program_id).await;
+
+    // bob's 20 y vest to alice over 100 seconds, and only while the supply of y, at offset 36
+    // of its mint, is at least 200
+    let start = now(&mut context).await;
+    let terms = EscrowTerms {
+        vesting_b: VestingSchedule::new(start, start, start + 100),
+        condition: Condition::new(parties.y_mint, spl_token::id(), 36, Comparator::Ge, ConditionValue::U64(200)),
+        ..EscrowTerms::default()
+    };
+    send(&mut context, &[init_with_terms(&parties, terms)], &[&parties.alice]).await.unwrap();
+    assert_eq!(load_escrow(&mut context, &parties).await.condition, terms.condition);
+    send(&mut context, &[parties.alice_deposit(10)], &[&parties.alice]).await.unwrap();
+    send(&mut context, &[parties.bob_deposit(20)], &[&parties.bob]).await.unwrap();
+
+    let alice_withdraw = |amount| {
+        instruction::withdraw_conditional(
+            &program_id, &parties.alice.pubkey(), &parties.y_vault, &parties.escrow, &parties.alice_y,
+            &parties.config, &parties.y_mint, amount,
+        )
+        .unwrap()
+    };
+    warp(&mut context, start + 50).await;
+    let result = send(&mut context, &[alice_withdraw(5)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::ConditionNotMet as u32);
+
+    // once the condition holds, the leg still only releases what has vested
+    let payer = context.payer.pubkey();
+    create_token_account(&mut context, &parties.y_mint, &payer, 100).await;
+    send(&mut context, &[alice_withdraw(5)], &[&parties.alice]).await.unwrap();
+    let result = send(&mut context, &[alice_withdraw(6)], &[&parties.alice]).await;
+    assert_custom_error(result, EscrowError::AmountNotVested as u32);
+    send(&mut context, &[alice_withdraw(5)], &[&parties.alice]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &parties.alice_y).await, 10);
+    assert_eq!(u64::from(load_escrow(&mut context, &parties).await.claimed_b), 10);
+}